@@ -1,6 +1,8 @@
 use revm::primitives::{alloy_primitives::Uint, Address};
 use rustry_macros::{huff, rustry_test};
-use rustry_test::{common::contract::Contract, prelude::*, utils::constants::ether};
+use rustry_test::{
+    assert_reverts, common::contract::Contract, prelude::*, utils::constants::ether,
+};
 
 fn set_up() {
     let mut provider = Provider::default();
@@ -34,9 +36,8 @@ fn test_novalue() {
 #[rustry_test(set_up)]
 fn test_value() {
     provider.mint(ether(), Address::ZERO);
-    assert!(!provider
-        .send(non_payable.address, Uint::from(10).into())
-        .is_success());
+    let res = provider.send(non_payable.address, Uint::from(10).into());
+    assert_reverts!(res, data = vec![]);
 }
 
 fn main() {}
@@ -1,8 +1,11 @@
 use revm::primitives::{Address, Bytes, U256};
-use rustry_macros::{rustry_test, solidity};
+use rustry_macros::{invariant_test, rustry_test, solidity};
 use rustry_test::{
+    assert_reverts,
     common::contract::Contract,
+    gas_snapshot,
     prelude::*,
+    provider::stack_trace::StackTrace,
     utils::{
         abi::{abi_decode, abi_encode_signature, AbiType},
         constants::bytes_zero,
@@ -28,6 +31,11 @@ fn set_up() {
                 function increment() public {
                     number++;
                 }
+
+                function decrement() public {
+                    require(number > 0, 'counter is zero');
+                    number--;
+                }
             }
         "
     };
@@ -58,6 +66,19 @@ fn test_increment() {
     assert_eq!(number, U256::from(1));
 }
 
+#[rustry_test(set_up)]
+fn test_decrement_zero() {
+    let res = provider.call(
+        counter.address,
+        abi_encode_signature("decrement()", vec![]).into(),
+    );
+    assert_reverts!(res, "counter is zero");
+    // `res.success()` would fail with this trace, pointing at the `require` of this file
+    let StackTrace(frames) = res.receipt().stack_trace.clone().unwrap();
+    let location = frames.last().unwrap().location.clone().unwrap();
+    assert_eq!((location.line, location.column), (36, 21));
+}
+
 #[rustry_test(set_up)]
 fn test_increment_method() {
     counter.methods.increment(&mut provider);
@@ -65,6 +86,36 @@ fn test_increment_method() {
     assert_eq!(number, U256::from(1));
 }
 
+#[rustry_test(set_up)]
+fn test_increment_gas() {
+    let number = gas_snapshot!("Counter::increment", || {
+        counter.methods.increment(&mut provider);
+        counter.methods.number(&mut provider)
+    });
+    assert_eq!(number, U256::from(1));
+}
+
+#[rustry_test(set_up, runs = 64, seed = 42)]
+fn test_fuzz_set_number(x: U256) {
+    provider
+        .call(
+            counter.address,
+            abi_encode_signature("setNumber(uint256)", vec![x.to_be_bytes::<32>().to_vec()]).into(),
+        )
+        .success();
+    let number = get_number(counter.address, &mut provider);
+    assert_eq!(number, x);
+}
+
+#[invariant_test(set_up, runs = 16, depth = 16, seed = 42)]
+fn invariant_number_readable() {
+    let ret = provider.staticcall(
+        counter.address,
+        abi_encode_signature("number()", vec![]).into(),
+    );
+    assert!(ret.is_success());
+}
+
 fn get_number(caddr: Address, provider: &mut Provider) -> U256 {
     let ret = provider.staticcall(caddr, abi_encode_signature("number()", vec![]).into());
     assert!(ret.is_success());
@@ -0,0 +1,76 @@
+use revm::primitives::{Address, Log, U256};
+use rustry_macros::{rustry_test, solidity};
+use rustry_test::{
+    prelude::*,
+    utils::{
+        abi::get_event_topic,
+        codec::{encode, AbiValue},
+    },
+};
+
+#[allow(unused)]
+fn set_up() {
+    let mut provider = Provider::default();
+
+    solidity! {
+        let token = "
+            // SPDX-License-Identifier: AGPLv3
+            pragma solidity 0.8.20;
+
+            contract Token {
+                event Transfer(address indexed from, address indexed to, uint256 value);
+
+                mapping(address => uint256) public balanceOf;
+
+                function transfer(address to, uint256 value) public returns (bool) {
+                    balanceOf[msg.sender] -= value;
+                    balanceOf[to] += value;
+                    emit Transfer(msg.sender, to, value);
+                    return true;
+                }
+            }
+        "
+    }
+
+    let token = token.deploy(&mut provider);
+}
+
+#[rustry_test(set_up)]
+fn test_decode_transfer() {
+    let (alice, bob) = (Address::repeat_byte(1), Address::repeat_byte(2));
+    let log = Log {
+        address: token.address,
+        topics: vec![
+            get_event_topic("Transfer(address,address,uint256)"),
+            alice.into_word(),
+            bob.into_word(),
+        ],
+        data: encode(&[AbiValue::Uint(U256::from(7))]).into(),
+    };
+
+    let events = token.decode_logs(&[log]);
+    let [token::ContractEvent::Transfer(token::Transfer { from, to, value })] = &events[..] else {
+        panic!("expected a single transfer, got {events:?}");
+    };
+    assert_eq!((*from, *to, *value), (alice, bob, U256::from(7)));
+}
+
+#[rustry_test(set_up)]
+fn test_decode_other_address() {
+    let log = Log {
+        address: Address::repeat_byte(3),
+        topics: vec![
+            get_event_topic("Transfer(address,address,uint256)"),
+            Address::ZERO.into_word(),
+            Address::ZERO.into_word(),
+        ],
+        data: encode(&[AbiValue::Uint(U256::from(7))]).into(),
+    };
+
+    assert_eq!(
+        token.decode_logs(&[log]),
+        Vec::<token::ContractEvent>::new()
+    );
+}
+
+fn main() {}
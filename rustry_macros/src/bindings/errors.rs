@@ -0,0 +1,175 @@
+use super::{dedup_names, param_ident, to_snake_case};
+use proc_macro2::{Ident, Span, TokenStream};
+use quote::quote;
+use rustry_test::compilers::solidity::{solc, types::abi_to_rust_type};
+
+pub struct ErrorParam {
+    pub name: String,
+    /// Canonical ABI type
+    pub ty: String,
+}
+
+pub struct ErrorDef {
+    pub name: String,
+    pub params: Vec<ErrorParam>,
+}
+
+impl ErrorDef {
+    pub fn signature(&self) -> String {
+        let types: Vec<&str> = self.params.iter().map(|param| param.ty.as_str()).collect();
+        format!("{}({})", self.name, types.join(","))
+    }
+}
+
+impl From<&solc::AbiEntry> for ErrorDef {
+    fn from(entry: &solc::AbiEntry) -> Self {
+        ErrorDef {
+            name: entry.name.clone(),
+            params: entry
+                .inputs
+                .iter()
+                .map(|input| ErrorParam {
+                    name: input.name.clone(),
+                    ty: input.canonical_type(),
+                })
+                .collect(),
+        }
+    }
+}
+
+/// A struct per custom error and the `ContractError` enum wrapping them.
+/// `taken` are the names already used by the other generated items.
+pub fn make_errors(errors: Vec<ErrorDef>, taken: &[String]) -> TokenStream {
+    let names = dedup_names(errors.iter().map(|error| error.name.as_str()));
+    let names: Vec<Ident> = names
+        .into_iter()
+        .map(|name| {
+            if taken.contains(&name) || name == "Reason" {
+                format!("{name}Error")
+            } else {
+                name
+            }
+        })
+        .map(|name| Ident::new(&name, Span::call_site()))
+        .collect();
+
+    let structs = errors.iter().zip(&names).map(|(error, name)| {
+        let fields: Vec<Ident> = error
+            .params
+            .iter()
+            .enumerate()
+            .map(|(i, param)| param_ident(&param.name, i))
+            .collect();
+        let types = error
+            .params
+            .iter()
+            .map(|param| abi_to_rust_type(&param.ty).parse::<TokenStream>().unwrap());
+        let params = error.params.iter().map(|param| &param.ty);
+        let signature = error.signature();
+        let error_name = &error.name;
+
+        quote! {
+            #[allow(non_snake_case, non_camel_case_types)]
+            #[derive(Debug, Clone, PartialEq)]
+            pub struct #name {
+                #(pub #fields: #types),*
+            }
+
+            impl #name {
+                pub const SIGNATURE: &'static str = #signature;
+
+                pub fn selector() -> [u8; 4] {
+                    rustry_test::utils::abi::get_sig(Self::SIGNATURE)
+                }
+
+                #[allow(unused_mut, unused_variables)]
+                pub fn decode(data: &[u8]) -> Option<Self> {
+                    let encoded = data.strip_prefix(&Self::selector())?;
+                    let types = [#(#params),*]
+                        .into_iter()
+                        .map(rustry_test::utils::codec::ParamType::parse)
+                        .collect::<Option<Vec<_>>>()?;
+                    let mut values = rustry_test::utils::codec::decode(&types, encoded)?.into_iter();
+                    Some(Self {
+                        #(#fields: rustry_test::utils::codec::FromAbi::from_abi(values.next()?)?),*
+                    })
+                }
+            }
+
+            impl std::fmt::Display for #name {
+                fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                    let values: Vec<String> = vec![
+                        #(rustry_test::utils::codec::ToAbi::to_abi(&self.#fields).to_string()),*
+                    ];
+                    write!(f, "{}({})", #error_name, values.join(", "))
+                }
+            }
+        }
+    });
+
+    let accessors = names.iter().map(|name| {
+        let accessor = Ident::new(
+            &format!("as_{}", to_snake_case(&name.to_string())),
+            Span::call_site(),
+        );
+        quote! {
+            pub fn #accessor(&self) -> Option<&#name> {
+                match self {
+                    Self::#name(error) => Some(error),
+                    _ => None,
+                }
+            }
+        }
+    });
+
+    let error_names = names.iter().map(|name| name.to_string());
+
+    quote! {
+        #(#structs)*
+
+        /// A custom error of the contract, or the standard reason if none matches.
+        #[allow(non_camel_case_types)]
+        #[derive(Debug, Clone, PartialEq)]
+        pub enum ContractError {
+            #(#names(#names),)*
+            Reason(rustry_test::common::revert::RevertReason),
+        }
+
+        impl ContractError {
+            pub fn decode(data: &[u8]) -> Self {
+                #(
+                    if let Some(error) = #names::decode(data) {
+                        return Self::#names(error);
+                    }
+                )*
+                Self::Reason(rustry_test::common::revert::RevertReason::decode(data))
+            }
+
+            pub fn name(&self) -> &'static str {
+                match *self {
+                    #(Self::#names(_) => #error_names,)*
+                    Self::Reason(_) => "Reason",
+                }
+            }
+
+            #(#accessors)*
+
+            pub fn as_reason(&self) -> Option<&rustry_test::common::revert::RevertReason> {
+                match self {
+                    Self::Reason(reason) => Some(reason),
+                    #[allow(unreachable_patterns)]
+                    _ => None,
+                }
+            }
+        }
+
+        impl std::fmt::Display for ContractError {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                match self {
+                    #(Self::#names(error) => write!(f, "{error}"),)*
+                    Self::Reason(reason) => write!(f, "{reason}"),
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,178 @@
+use super::{dedup_names, param_ident, to_snake_case};
+use proc_macro2::{Ident, Span, TokenStream};
+use quote::quote;
+use rustry_test::compilers::{
+    solidity::{solc, types::abi_to_rust_type},
+    vyper::vyperc,
+};
+
+pub struct EventParam {
+    pub name: String,
+    /// Canonical ABI type
+    pub ty: String,
+    pub indexed: bool,
+}
+
+pub struct EventDef {
+    pub name: String,
+    pub anonymous: bool,
+    pub params: Vec<EventParam>,
+}
+
+impl EventDef {
+    fn signature(&self) -> String {
+        let types: Vec<&str> = self.params.iter().map(|param| param.ty.as_str()).collect();
+        format!("{}({})", self.name, types.join(","))
+    }
+}
+
+impl From<&solc::AbiEntry> for EventDef {
+    fn from(entry: &solc::AbiEntry) -> Self {
+        EventDef {
+            name: entry.name.clone(),
+            anonymous: entry.anonymous.unwrap_or_default(),
+            params: entry
+                .inputs
+                .iter()
+                .map(|input| EventParam {
+                    name: input.name.clone(),
+                    ty: input.canonical_type(),
+                    indexed: input.indexed.unwrap_or_default(),
+                })
+                .collect(),
+        }
+    }
+}
+
+impl From<&vyperc::AbiEntry> for EventDef {
+    fn from(entry: &vyperc::AbiEntry) -> Self {
+        EventDef {
+            name: entry.name.clone(),
+            anonymous: entry.anonymous.unwrap_or_default(),
+            params: entry
+                .inputs
+                .iter()
+                .flatten()
+                .map(|input| EventParam {
+                    name: input.name.clone(),
+                    ty: input.canonical_type(),
+                    indexed: input.indexed.unwrap_or_default(),
+                })
+                .collect(),
+        }
+    }
+}
+
+/// A struct per event and the `ContractEvent` enum wrapping them.
+pub fn make_events(mut events: Vec<EventDef>) -> TokenStream {
+    // anonymous events are matched loosely, try them last
+    events.sort_by_key(|event| event.anonymous);
+    let names = dedup_names(events.iter().map(|event| event.name.as_str()));
+    let names: Vec<Ident> = names
+        .iter()
+        .map(|name| Ident::new(name, Span::call_site()))
+        .collect();
+
+    let structs = events.iter().zip(&names).map(|(event, name)| {
+        let fields: Vec<Ident> = event
+            .params
+            .iter()
+            .enumerate()
+            .map(|(i, param)| param_ident(&param.name, i))
+            .collect();
+        let types = event.params.iter().map(|param| {
+            let ty = if param.indexed && is_hashed_topic(&param.ty) {
+                String::from("revm::primitives::B256")
+            } else {
+                abi_to_rust_type(&param.ty)
+            };
+            ty.parse::<TokenStream>().unwrap()
+        });
+        let params = event.params.iter().map(|param| {
+            let ty = &param.ty;
+            let indexed = param.indexed;
+            quote! { (#ty, #indexed) }
+        });
+        let signature = if event.anonymous {
+            quote! { None }
+        } else {
+            let signature = event.signature();
+            quote! { Some(#signature) }
+        };
+
+        quote! {
+            #[allow(non_snake_case, non_camel_case_types)]
+            #[derive(Debug, Clone, PartialEq)]
+            pub struct #name {
+                #(pub #fields: #types),*
+            }
+
+            impl #name {
+                #[allow(unused_mut)]
+                pub fn decode(log: &revm::primitives::Log) -> Option<Self> {
+                    let mut values = rustry_test::utils::codec::decode_log(
+                        log,
+                        #signature,
+                        &[#(#params),*],
+                    )?
+                    .into_iter();
+                    Some(Self {
+                        #(#fields: rustry_test::utils::codec::FromAbi::from_abi(values.next()?)?),*
+                    })
+                }
+            }
+        }
+    });
+
+    let accessors = names.iter().map(|name| {
+        let accessor = Ident::new(
+            &format!("as_{}", to_snake_case(&name.to_string())),
+            Span::call_site(),
+        );
+        quote! {
+            #[allow(unreachable_patterns)]
+            pub fn #accessor(&self) -> Option<&#name> {
+                match self {
+                    Self::#name(event) => Some(event),
+                    _ => None,
+                }
+            }
+        }
+    });
+
+    let event_names = names.iter().map(|name| name.to_string());
+
+    quote! {
+        #(#structs)*
+
+        #[allow(non_camel_case_types)]
+        #[derive(Debug, Clone, PartialEq)]
+        pub enum ContractEvent {
+            #(#names(#names)),*
+        }
+
+        impl ContractEvent {
+            pub fn decode(log: &revm::primitives::Log) -> Option<Self> {
+                #(
+                    if let Some(event) = #names::decode(log) {
+                        return Some(Self::#names(event));
+                    }
+                )*
+                None
+            }
+
+            pub fn name(&self) -> &'static str {
+                match *self {
+                    #(Self::#names(_) => #event_names),*
+                }
+            }
+
+            #(#accessors)*
+        }
+    }
+}
+
+/// Indexed parameters of these types are only stored as their hash.
+fn is_hashed_topic(ty: &str) -> bool {
+    ty == "string" || ty == "bytes" || ty.ends_with(']') || ty.starts_with('(')
+}
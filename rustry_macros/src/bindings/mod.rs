@@ -0,0 +1,56 @@
+pub mod errors;
+pub mod events;
+pub mod storage;
+
+use proc_macro2::{Ident, Span};
+use std::collections::HashMap;
+
+/// Identifier of a parameter, which may be unnamed or a Rust keyword in the ABI.
+pub fn param_ident(name: &str, i: usize) -> Ident {
+    if name.is_empty() || name == "_" {
+        Ident::new(&format!("arg{i}"), Span::call_site())
+    } else if syn::parse_str::<Ident>(name).is_ok() {
+        Ident::new(name, Span::call_site())
+    } else if matches!(name, "self" | "super" | "crate" | "Self") {
+        // can't be raw identifiers
+        Ident::new(&format!("{name}_"), Span::call_site())
+    } else {
+        Ident::new_raw(name, Span::call_site())
+    }
+}
+
+/// `OwnershipTransferred` -> `ownership_transferred`
+pub fn to_snake_case(name: &str) -> String {
+    let mut snake = String::new();
+    for (i, c) in name.chars().enumerate() {
+        if c.is_uppercase() {
+            if i != 0 && !snake.ends_with('_') {
+                snake.push('_');
+            }
+            snake.extend(c.to_lowercase());
+        } else {
+            snake.push(c);
+        }
+    }
+    snake
+}
+
+/// Suffix the overloaded names with a counter so that they don't collide.
+pub fn dedup_names<'a>(names: impl Iterator<Item = &'a str> + Clone) -> Vec<String> {
+    let mut names_occur: HashMap<&str, usize> = HashMap::new();
+    names.clone().for_each(|name| {
+        *names_occur.entry(name).or_default() += 1;
+    });
+    let mut seen: HashMap<&str, usize> = HashMap::new();
+    names
+        .map(|name| {
+            if names_occur[name] > 1 {
+                let count = seen.entry(name).or_default();
+                *count += 1;
+                format!("{name}{count}")
+            } else {
+                name.to_string()
+            }
+        })
+        .collect()
+}
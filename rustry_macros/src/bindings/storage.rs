@@ -0,0 +1,137 @@
+use super::param_ident;
+use proc_macro2::{Ident, Span, TokenStream};
+use quote::quote;
+use rustry_test::common::storage::{Encoding, StorageLayout, StorageType, TypeKind, Variable};
+use std::collections::HashSet;
+
+fn variable_tokens(variable: &Variable) -> TokenStream {
+    let Variable {
+        label,
+        slot,
+        offset,
+        ty,
+    } = variable;
+    let limbs = slot.as_limbs();
+    quote! {
+        rustry_test::common::storage::Variable {
+            label: #label.to_string(),
+            slot: revm::primitives::U256::from_limbs([#(#limbs),*]),
+            offset: #offset,
+            ty: #ty.to_string(),
+        }
+    }
+}
+
+fn type_tokens(ty: &StorageType) -> TokenStream {
+    let StorageType { label, size, kind } = ty;
+    let kind = match kind {
+        TypeKind::Value => quote! { Value },
+        TypeKind::Mapping { key, value } => quote! {
+            Mapping { key: #key.to_string(), value: #value.to_string() }
+        },
+        TypeKind::DynamicArray { base } => quote! {
+            DynamicArray { base: #base.to_string() }
+        },
+        TypeKind::StaticArray { base, length } => quote! {
+            StaticArray { base: #base.to_string(), length: #length }
+        },
+        TypeKind::Bytes => quote! { Bytes },
+        TypeKind::Struct { members } => {
+            let members = members.iter().map(variable_tokens);
+            quote! { Struct { members: vec![#(#members),*] } }
+        }
+    };
+    quote! {
+        rustry_test::common::storage::StorageType {
+            label: #label.to_string(),
+            size: #size,
+            kind: rustry_test::common::storage::TypeKind::#kind,
+        }
+    }
+}
+
+/// The `StorageLayout` registered in the provider on deployment.
+pub fn layout_tokens(layout: &StorageLayout) -> TokenStream {
+    let encoding = match layout.encoding {
+        Encoding::Solidity => quote! { Solidity },
+        Encoding::Vyper => quote! { Vyper },
+    };
+    let variables = layout.variables.iter().map(variable_tokens);
+    let ids = layout.types.keys();
+    let types = layout.types.values().map(type_tokens);
+    quote! {
+        Some(rustry_test::common::storage::StorageLayout {
+            encoding: rustry_test::common::storage::Encoding::#encoding,
+            variables: vec![#(#variables),*],
+            types: std::collections::HashMap::from([#((#ids.to_string(), #types)),*]),
+        })
+    }
+}
+
+/// The `ContractStorage` wrapper with an accessor per variable, taking a key per mapping level,
+/// and `DeployedContract::storage` to get it.
+pub fn make_storage(layout: &StorageLayout) -> TokenStream {
+    let mut seen = HashSet::new();
+    let accessors = layout
+        .variables
+        .iter()
+        .enumerate()
+        // the first one is the one found by `variable`
+        .filter(|(_, variable)| seen.insert(variable.label.as_str()))
+        .filter_map(|(i, variable)| {
+            let mut ty = layout.types.get(&variable.ty)?;
+            let mut keys = Vec::new();
+            while let TypeKind::Mapping { value, .. } = &ty.kind {
+                keys.push(Ident::new(&format!("key{}", keys.len()), Span::call_site()));
+                ty = layout.types.get(value)?;
+            }
+            let (ret, read) = match &ty.kind {
+                TypeKind::Value if ty.label == "bool" => (quote! { bool }, quote! { .bool() }),
+                TypeKind::Value
+                    if ty.label == "address"
+                        || ty.label == "address payable"
+                        || ty.label.starts_with("contract ") =>
+                {
+                    (
+                        quote! { revm::primitives::Address },
+                        quote! { .address() },
+                    )
+                }
+                TypeKind::Value => (quote! { revm::primitives::U256 }, quote! { .value() }),
+                TypeKind::Bytes if ty.label.to_lowercase().starts_with("string") => {
+                    (quote! { String }, quote! { .string() })
+                }
+                TypeKind::Bytes => (quote! { revm::primitives::Bytes }, quote! { .bytes() }),
+                _ => (
+                    quote! { rustry_test::common::storage::StorageRef<'a> },
+                    TokenStream::new(),
+                ),
+            };
+            let name = param_ident(&variable.label, i);
+            let label = &variable.label;
+            Some(quote! {
+                pub fn #name(&self, #(#keys: impl rustry_test::common::storage::StorageKey),*) -> #ret {
+                    self.0.variable(#label).unwrap()#(.key(#keys))*#read
+                }
+            })
+        });
+
+    quote! {
+        /// The variables of the contract, read from the provider.
+        pub struct ContractStorage<'a>(pub rustry_test::common::storage::ContractStorage<'a>);
+
+        #[allow(dead_code, non_snake_case)]
+        impl<'a> ContractStorage<'a> {
+            #(#accessors)*
+        }
+
+        impl DeployedContract {
+            pub fn storage<'a>(&self, provider: &'a rustry_test::provider::Provider) -> ContractStorage<'a> {
+                ContractStorage(
+                    rustry_test::common::storage::ContractStorage::new(provider, self.address)
+                        .expect("the contract is not registered in this provider"),
+                )
+            }
+        }
+    }
+}
@@ -1,26 +1,40 @@
 #![feature(proc_macro_span)]
 #![feature(slice_take)]
 
+mod bindings;
 mod harness; // TODO wat do ?
 
+use bindings::{
+    errors::{make_errors, ErrorDef},
+    events::{make_events, EventDef},
+    storage::{layout_tokens, make_storage},
+};
 use proc_macro::{Span, TokenStream};
 use proc_macro2::Ident;
 use quote::{quote, ToTokens};
-use rustry_test::compilers::{
-    builder::{BinError, Compiler, CompilerError, CompilerKinds},
-    huff::huffc::HuffcOut,
-    solidity::{
-        solc::{self, EntryUtils, SolcOut},
-        types::internal_to_type,
+use rustry_test::{
+    common::storage::StorageLayout,
+    compilers::{
+        builder::{BinError, Compiler, CompilerError, CompilerKinds},
+        huff::huffc::HuffcOut,
+        solidity::{
+            solc::{self, EntryUtils, SolcOut},
+            source_map::function_name,
+            types::internal_to_type,
+        },
+        vyper::vyperc::VypercOut,
     },
-    vyper::vyperc::VypercOut,
 };
 use std::{collections::HashMap, iter};
-use syn::{parse_macro_input, Error, ItemFn};
+use syn::{
+    parse::{Parse, ParseStream},
+    parse_macro_input, Error, ItemFn, Token,
+};
 
 /// # Examples
 ///
 /// ```
+/// use revm::primitives::U256;
 /// use rustry_macros::rustry_test;
 ///
 /// fn set_up() {
@@ -35,7 +49,8 @@ use syn::{parse_macro_input, Error, ItemFn};
 ///     // assert_eq!(counter.number(), 1);
 /// }
 ///
-/// #[rustry_test(set_up)]
+/// // tests with parameters are fuzzed, `runs` and `seed` are optional
+/// #[rustry_test(set_up, runs = 1000, seed = 42)]
 /// fn testFuzz_set_number(x: U256) {
 ///     // counter.setNumber(x).send().await;
 ///     // assert_eq!(counter.number(), x);
@@ -44,8 +59,14 @@ use syn::{parse_macro_input, Error, ItemFn};
 #[proc_macro_attribute]
 pub fn rustry_test(args: TokenStream, input: TokenStream) -> TokenStream {
     let mut set_up_name = None;
+    let mut runs: Option<syn::LitInt> = None;
+    let mut seed: Option<syn::LitInt> = None;
     let set_up_parser = syn::meta::parser(|meta| {
-        if set_up_name.is_some() {
+        if meta.path.is_ident("runs") {
+            runs = Some(meta.value()?.parse()?);
+        } else if meta.path.is_ident("seed") {
+            seed = Some(meta.value()?.parse()?);
+        } else if set_up_name.is_some() {
             return Err(Error::new_spanned(
                 args.clone().to_string(),
                 "should have only one function name",
@@ -58,56 +79,199 @@ pub fn rustry_test(args: TokenStream, input: TokenStream) -> TokenStream {
     let ar = args.clone();
     parse_macro_input!(ar with set_up_parser);
 
+    let set_up_block = set_up_block(set_up_name);
+    let fun = parse_macro_input!(input as ItemFn);
+    let fname = fun.sig.ident;
+    let block = fun.block;
+
+    let def = default_set_up();
+
+    let mut pats = Vec::new();
+    let mut tys = Vec::new();
+    for input in fun.sig.inputs {
+        match input {
+            syn::FnArg::Typed(arg) => {
+                pats.push(arg.pat);
+                tys.push(arg.ty);
+            }
+            syn::FnArg::Receiver(receiver) => {
+                return Error::new_spanned(receiver, "a test can't take self")
+                    .to_compile_error()
+                    .into()
+            }
+        }
+    }
+
+    if pats.is_empty() {
+        if let Some(lit) = runs.or(seed) {
+            return Error::new_spanned(lit, "only the tests with parameters are fuzzed")
+                .to_compile_error()
+                .into();
+        }
+
+        return quote! {
+            // #[tokio::test]
+            #[test]
+            pub fn #fname() {
+                let _gas_report = rustry_test::gas::ReportGuard;
+                let _coverage = rustry_test::coverage::CoverageGuard;
+                #def
+                #set_up_block
+                #block
+            }
+        }
+        .into();
+    }
+
+    // the inputs are generated as a tuple, and the whole test runs again for each of them
+    let runs = runs.map(|runs| quote! { config.runs = #runs; });
+    let seed = seed.map(|seed| quote! { config.seed = #seed; });
+    quote! {
+        #[test]
+        pub fn #fname() {
+            let _gas_report = rustry_test::gas::ReportGuard;
+            let _coverage = rustry_test::coverage::CoverageGuard;
+            let mut config = rustry_test::fuzz::FuzzConfig {
+                name: Some(concat!(module_path!(), "::", stringify!(#fname)).to_string()),
+                ..Default::default()
+            };
+            #runs
+            #seed
+            config.override_from_env();
+            rustry_test::fuzz::run(&config, |(#(#pats,)*): (#(#tys,)*)| {
+                #def
+                #set_up_block
+                #block
+            });
+        }
+    }
+    .into()
+}
+
+/// The statements of the `set_up` function, pasted at the start of the tests.
+fn set_up_block(set_up_name: Option<syn::Path>) -> proc_macro2::TokenStream {
+    let Some(fname) = set_up_name else {
+        return proc_macro2::TokenStream::new();
+    };
     let span = Span::call_site();
     let macro_path = span.source_file().path().canonicalize().unwrap();
     let code = std::fs::read_to_string(macro_path).unwrap();
     let syntax = syn::parse_file(&code).unwrap();
-    let set_up_block = if let Some(fname) = set_up_name {
-        if let Some(set_up_fn) = syntax.items.into_iter().find(|item| {
-            if let syn::Item::Fn(_fn) = item {
-                _fn.sig.ident == fname.clone().into_token_stream().to_string()
-            } else {
-                false
-            }
-        }) {
-            match set_up_fn {
-                syn::Item::Fn(syn::ItemFn { block, .. }) => {
-                    let block: syn::Block = *block;
-                    let stmts = block.stmts;
-                    quote! {
-                        #(#stmts)*
-                    }
+    if let Some(set_up_fn) = syntax.items.into_iter().find(|item| {
+        if let syn::Item::Fn(_fn) = item {
+            _fn.sig.ident == fname.clone().into_token_stream().to_string()
+        } else {
+            false
+        }
+    }) {
+        match set_up_fn {
+            syn::Item::Fn(syn::ItemFn { block, .. }) => {
+                let block: syn::Block = *block;
+                let stmts = block.stmts;
+                quote! {
+                    #(#stmts)*
                 }
-                _ => unreachable!(),
             }
-        } else {
-            syn::Error::new_spanned(fname, "invalid set_up function name").to_compile_error()
+            _ => unreachable!(),
         }
     } else {
-        proc_macro2::TokenStream::new()
-    };
+        syn::Error::new_spanned(fname, "invalid set_up function name").to_compile_error()
+    }
+}
+
+/// # Examples
+///
+/// ```
+/// use rustry_macros::invariant_test;
+///
+/// fn set_up() {
+///     // let mut provider = Provider::default();
+///     // let token = deploy_token(&mut provider);
+///     // let handler = deploy_handler(&mut provider, token.address);
+/// }
+///
+/// // random calls are made to the deployed contracts, or only to the `targets`,
+/// // and the body checks the state after each of them
+/// #[invariant_test(set_up, runs = 64, depth = 32, seed = 42, targets = [handler])]
+/// fn invariant_supply() {
+///     // assert_eq!(token.methods.totalSupply(&mut provider), handler.methods.minted(&mut provider));
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn invariant_test(args: TokenStream, input: TokenStream) -> TokenStream {
+    let mut set_up_name = None;
+    let mut settings = Vec::new();
+    let mut targets = Vec::new();
+    let set_up_parser = syn::meta::parser(|meta| {
+        if let Some(setting) = ["runs", "depth", "seed", "fail_on_revert"]
+            .into_iter()
+            .find(|setting| meta.path.is_ident(setting))
+        {
+            let setting = Ident::new(setting, proc_macro2::Span::call_site());
+            let value: syn::Lit = meta.value()?.parse()?;
+            settings.push(quote! { config.#setting = #value; });
+        } else if meta.path.is_ident("targets") {
+            let array: syn::ExprArray = meta.value()?.parse()?;
+            targets.extend(array.elems);
+        } else if set_up_name.is_some() {
+            return Err(Error::new_spanned(
+                args.clone().to_string(),
+                "should have only one function name",
+            ));
+        } else {
+            set_up_name = Some(meta.path);
+        }
+        Ok(())
+    });
+    let ar = args.clone();
+    parse_macro_input!(ar with set_up_parser);
+
     let fun = parse_macro_input!(input as ItemFn);
+    if set_up_name.is_none() {
+        return Error::new_spanned(
+            fun.sig.ident,
+            "an invariant test needs a set_up deploying the contracts",
+        )
+        .to_compile_error()
+        .into();
+    }
+    if let Some(input) = fun.sig.inputs.first() {
+        return Error::new_spanned(input, "an invariant test takes no parameter")
+            .to_compile_error()
+            .into();
+    }
+    let set_up_block = set_up_block(set_up_name);
     let fname = fun.sig.ident;
     let block = fun.block;
-
     let def = default_set_up();
 
+    // the set_up runs again for each sequence, the body is checked after each call
     quote! {
-        // #[tokio::test]
         #[test]
         pub fn #fname() {
-            #def
-            #set_up_block
-            #block
+            let _gas_report = rustry_test::gas::ReportGuard;
+            let _coverage = rustry_test::coverage::CoverageGuard;
+            let mut config = rustry_test::fuzz::invariant::InvariantConfig::default();
+            #(#settings)*
+            config.override_from_env();
+            rustry_test::fuzz::invariant::run(&config, |run: &mut rustry_test::fuzz::invariant::InvariantRun| {
+                #def
+                #set_up_block
+                #(run.target(#targets.address);)*
+                run.drive(&mut provider, |provider: &mut rustry_test::provider::Provider| {
+                    #[allow(unused_mut)]
+                    let mut provider = provider;
+                    #block
+                });
+            });
         }
     }
     .into()
 }
 
-// TODO figure out the source mappings
 #[proc_macro]
 pub fn solidity(input: TokenStream) -> TokenStream {
-    let lit_str = parse_macro_input!(input as syn::LitStr);
+    let ContractInput { binding, lit_str } = parse_macro_input!(input as ContractInput);
     let source_code = lit_str.value();
 
     let solc = Compiler {
@@ -119,10 +283,12 @@ pub fn solidity(input: TokenStream) -> TokenStream {
         Ok(out) => {
             let solc_out = SolcOut::try_from(out).unwrap();
             let contracts = solc_out.contracts.unwrap();
-            let contract = contracts
+            // the main contract is usually the last one, after its interfaces and libraries
+            let (contract_name, contract) = contracts
                 .get("source_code.sol")
                 .unwrap()
-                .get("Counter")
+                .iter()
+                .max_by_key(|(name, _)| contract_position(&source_code, name))
                 .unwrap();
 
             let bytecode = &contract
@@ -142,6 +308,24 @@ pub fn solidity(input: TokenStream) -> TokenStream {
                 .filter(|entry| entry.entry_type == "function")
                 .collect();
 
+            let abi = contract.abi.as_ref().unwrap();
+            let event_defs: Vec<EventDef> = abi
+                .iter()
+                .filter(|entry| entry.entry_type == "event")
+                .map(EventDef::from)
+                .collect();
+            let event_names: Vec<String> =
+                event_defs.iter().map(|event| event.name.clone()).collect();
+            let events = make_events(event_defs);
+            let error_defs: Vec<ErrorDef> = abi
+                .iter()
+                .filter(|entry| entry.entry_type == "error")
+                .map(ErrorDef::from)
+                .collect();
+            let error_signatures: Vec<String> =
+                error_defs.iter().map(ErrorDef::signature).collect();
+            let errors = make_errors(error_defs, &event_names);
+
             // ugly shit, use Iterator::partition
             let mut names_occur: HashMap<String, usize> = HashMap::new();
             functions.iter().for_each(|func| {
@@ -166,9 +350,25 @@ pub fn solidity(input: TokenStream) -> TokenStream {
                 .collect();
 
             let impl_fns = functions.iter().map(|(func, meth_name)| {
-                let signature: proc_macro2::TokenStream = func.signature().parse().unwrap();
-                let inputs_w_types = func.inputs.iter().map(|input| {
-                    let iname: proc_macro2::TokenStream = input.name.clone().parse().unwrap();
+                let signature = func.signature();
+                let inames: Vec<Ident> = func
+                    .inputs
+                    .iter()
+                    .enumerate()
+                    .map(|(i, input)| bindings::param_ident(&input.name, i))
+                    .collect();
+                let calldata = quote! {
+                    [
+                        rustry_test::utils::abi::get_sig(#signature).to_vec(),
+                        rustry_test::utils::codec::encode(&[
+                            #(rustry_test::utils::codec::ToAbi::to_abi(&#inames)),*
+                        ]),
+                    ]
+                    .concat()
+                    .into()
+                };
+                let inputs_w_types = func.inputs.iter().enumerate().map(|(i, input)| {
+                    let iname = bindings::param_ident(&input.name, i);
                     let itype: proc_macro2::TokenStream =
                         internal_to_type(&input.type_type).parse().unwrap();
                     quote! {
@@ -186,20 +386,17 @@ pub fn solidity(input: TokenStream) -> TokenStream {
 
                 let fn_call = match func.state_mutability.as_str() {
                     "nonpayable" => quote! {
-                        provider.call(self.address, abi_encode_signature(stringify!(#signature), vec![]).into());
+                        let ret = provider.call(self.address, #calldata);
                     },
                     "view" => quote! {
-                        let ret = provider.staticcall(
-                            self.address, 
-                            abi_encode_signature(stringify!(#signature), vec![]).into()
-                        );
+                        let ret = provider.staticcall(self.address, #calldata);
                     },
                     _ => unimplemented!(),
                 };
 
                 let mut outputs = func.outputs.iter();
                 let (output, fn_ret) = if let Some(output) = outputs.next() {
-                    let output: proc_macro2::TokenStream = internal_to_type(&output.type_type).parse().unwrap();
+                    let _output: proc_macro2::TokenStream = internal_to_type(&output.type_type).parse().unwrap();
                     if outputs.next().is_some() {
                         return syn::Error::new_spanned(
                             lit_str.clone(), 
@@ -214,11 +411,15 @@ pub fn solidity(input: TokenStream) -> TokenStream {
                         quote! {
                             // TODO once we support non U256 types
                             // stringify!(#output)
-                            U256
+                            revm::primitives::U256
                         },
                         quote! {
                             let data = ret.get_data();
-                            U256::from_be_bytes::<32>(abi_decode(data, vec![AbiType::Uint]).try_into().unwrap())
+                            revm::primitives::U256::from_be_bytes::<32>(
+                                rustry_test::utils::abi::abi_decode(data, vec![rustry_test::utils::abi::AbiType::Uint])
+                                    .try_into()
+                                    .unwrap()
+                            )
                         }
                     )
                 } else {
@@ -245,7 +446,55 @@ pub fn solidity(input: TokenStream) -> TokenStream {
                 }
             });
 
-            make_contract_instance(impl_fns, bytecode)
+            let storage_layout = contract.storage_layout.as_ref().map(StorageLayout::from);
+            let info = contract_info(
+                contract_name,
+                functions.iter().map(|(func, _)| FunctionDef {
+                    name: &func.name,
+                    inputs: func.inputs.iter().map(|input| input.canonical_type()).collect(),
+                    outputs: func.outputs.iter().map(|output| output.canonical_type()).collect(),
+                    state_mutability: &func.state_mutability,
+                }),
+                &error_signatures,
+                match contract
+                    .evm
+                    .as_ref()
+                    .and_then(|evm| evm.deployed_bytecode.as_ref())
+                {
+                    Some(deployed) => contract_sources(
+                        &lit_str,
+                        "source_code.sol",
+                        &solc_out.sources,
+                        &deployed.bytecode_data.source_map,
+                        deployed.bytecode_data.generated_sources.iter().map(|source| {
+                            (source.id, source.name.clone(), source.contents.clone())
+                        }),
+                        deployed
+                            .bytecode_data
+                            .function_debug_data
+                            .iter()
+                            .filter_map(|(name, data)| {
+                                Some((
+                                    function_name(name).to_string(),
+                                    usize::try_from(data.entry_point?).ok()?,
+                                    data.parameter_slots.unwrap_or_default() as usize,
+                                    data.return_slots.unwrap_or_default() as usize,
+                                ))
+                            }),
+                    ),
+                    None => quote! { None },
+                },
+                storage_layout.as_ref(),
+            );
+
+            make_contract_instance(
+                binding,
+                impl_fns,
+                quote! { #events #errors },
+                info,
+                storage_layout.as_ref().map(make_storage).unwrap_or_default(),
+                bytecode,
+            )
         }
         Err(err) => match err {
             CompilerError::BuilderError(_) => todo!(),
@@ -261,7 +510,7 @@ pub fn solidity(input: TokenStream) -> TokenStream {
 
 #[proc_macro]
 pub fn vyper(input: TokenStream) -> TokenStream {
-    let lit_str = parse_macro_input!(input as syn::LitStr);
+    let ContractInput { binding, lit_str } = parse_macro_input!(input as ContractInput);
     let source_code = lit_str.value();
 
     let vyperc = Compiler {
@@ -290,7 +539,75 @@ pub fn vyper(input: TokenStream) -> TokenStream {
                 .trim_start_matches("0x")
                 .to_string();
 
-            make_contract_instance(iter::empty::<proc_macro2::TokenStream>(), bytecode)
+            let events = make_events(
+                contract
+                    .abi
+                    .iter()
+                    .flatten()
+                    .filter(|entry| entry.entry_type == "event")
+                    .map(EventDef::from)
+                    .collect(),
+            );
+
+            let errors = make_errors(Vec::new(), &[]);
+
+            let storage_layout = contract.layout.as_ref().map(StorageLayout::from);
+            let info = contract_info(
+                "Contract",
+                contract
+                    .abi
+                    .iter()
+                    .flatten()
+                    .filter(|entry| entry.entry_type == "function")
+                    .map(|entry| FunctionDef {
+                        name: &entry.name,
+                        inputs: entry
+                            .inputs
+                            .iter()
+                            .flatten()
+                            .map(|input| input.canonical_type())
+                            .collect(),
+                        outputs: entry
+                            .outputs
+                            .iter()
+                            .flatten()
+                            .map(|output| output.canonical_type())
+                            .collect(),
+                        state_mutability: entry.state_mutability.as_deref().unwrap_or("nonpayable"),
+                    }),
+                &[],
+                match (
+                    &vyc_out.sources,
+                    contract
+                        .evm
+                        .as_ref()
+                        .and_then(|evm| evm.deployed_bytecode.as_ref())
+                        .and_then(|deployed| deployed.bytecode_data.source_map.as_ref()),
+                ) {
+                    (Some(sources), Some(source_map)) => contract_sources(
+                        &lit_str,
+                        "source_code.vy",
+                        sources,
+                        source_map,
+                        iter::empty(),
+                        iter::empty(),
+                    ),
+                    _ => quote! { None },
+                },
+                storage_layout.as_ref(),
+            );
+
+            make_contract_instance(
+                binding,
+                iter::empty::<proc_macro2::TokenStream>(),
+                quote! { #events #errors },
+                info,
+                storage_layout
+                    .as_ref()
+                    .map(make_storage)
+                    .unwrap_or_default(),
+                bytecode,
+            )
         }
         Err(err) => match err {
             CompilerError::BuilderError(_) => todo!(),
@@ -306,7 +623,7 @@ pub fn vyper(input: TokenStream) -> TokenStream {
 
 #[proc_macro]
 pub fn huff(input: TokenStream) -> TokenStream {
-    let lit_str = parse_macro_input!(input as syn::LitStr);
+    let ContractInput { binding, lit_str } = parse_macro_input!(input as ContractInput);
     let source_code = lit_str.value();
 
     let huffc = Compiler {
@@ -319,87 +636,343 @@ pub fn huff(input: TokenStream) -> TokenStream {
             let huffc_out = HuffcOut::try_from(out).unwrap();
             let bytecode = huffc_out.bytecode;
 
-            make_contract_instance(iter::empty::<proc_macro2::TokenStream>(), &bytecode)
+            make_contract_instance(
+                binding,
+                iter::empty::<proc_macro2::TokenStream>(),
+                {
+                    let events = make_events(Vec::new());
+                    let errors = make_errors(Vec::new(), &[]);
+                    quote! { #events #errors }
+                },
+                contract_info("Contract", iter::empty(), &[], quote! { None }, None),
+                proc_macro2::TokenStream::new(),
+                &bytecode,
+            )
         }
         Err(err) => panic!("{:?}", err),
     }
     .into()
 }
 
+/// The source of a contract, either alone or bound with `let NAME = "..."`.
+/// A bound contract has its generated types in the `NAME` module, e.g. `NAME::ContractEvent`.
+struct ContractInput {
+    binding: Option<ContractBinding>,
+    lit_str: syn::LitStr,
+}
+
+#[derive(Clone)]
+struct ContractBinding {
+    mutability: Option<Token![mut]>,
+    name: Ident,
+}
+
+impl Parse for ContractInput {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let binding = if input.peek(Token![let]) {
+            input.parse::<Token![let]>()?;
+            let mutability = input.parse()?;
+            let name = input.parse()?;
+            input.parse::<Token![=]>()?;
+            Some(ContractBinding { mutability, name })
+        } else {
+            None
+        };
+        let lit_str = input.parse()?;
+        input.parse::<Option<Token![;]>>()?;
+        Ok(Self { binding, lit_str })
+    }
+}
+
+/// The source without its comments, each one replaced by a space.
+fn strip_comments(source: &str) -> String {
+    let mut code = String::with_capacity(source.len());
+    let mut chars = source.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (c, chars.peek()) {
+            ('/', Some('/')) => {
+                while chars.next_if(|c| *c != '\n').is_some() {}
+                code.push(' ');
+            }
+            ('/', Some('*')) => {
+                chars.next();
+                let mut previous = ' ';
+                for c in chars.by_ref() {
+                    if previous == '*' && c == '/' {
+                        break;
+                    }
+                    previous = c;
+                }
+                code.push(' ');
+            }
+            _ => code.push(c),
+        }
+    }
+    code
+}
+
+/// Where the last `contract <name>` declaration is, the name being a whole word.
+fn contract_position(source: &str, name: &str) -> Option<usize> {
+    let code = strip_comments(source);
+    let mut words = Vec::new();
+    let mut start = None;
+    for (i, c) in code.char_indices().chain([(code.len(), ' ')]) {
+        if c.is_alphanumeric() || c == '_' || c == '$' {
+            start.get_or_insert(i);
+        } else if let Some(start) = start.take() {
+            words.push((start, &code[start..i]));
+        }
+    }
+    words
+        .windows(2)
+        .filter(|words| words[0].1 == "contract" && words[1].1 == name)
+        .map(|words| words[0].0)
+        .last()
+}
+
 fn default_set_up() -> proc_macro2::TokenStream {
     quote! {
         let provider = 0;
     }
 }
 
+struct FunctionDef<'a> {
+    name: &'a str,
+    /// Canonical ABI types
+    inputs: Vec<String>,
+    outputs: Vec<String>,
+    state_mutability: &'a str,
+}
+
+/// The `ContractInfo` registered in the provider on deployment.
+fn contract_info<'a>(
+    name: &str,
+    functions: impl Iterator<Item = FunctionDef<'a>>,
+    error_signatures: &[String],
+    sources: proc_macro2::TokenStream,
+    storage_layout: Option<&StorageLayout>,
+) -> proc_macro2::TokenStream {
+    let functions = functions.map(|function| {
+        let FunctionDef {
+            name,
+            inputs,
+            outputs,
+            state_mutability,
+        } = function;
+        quote! {
+            rustry_test::common::contract::FunctionInfo {
+                name: #name.to_string(),
+                inputs: vec![#(#inputs.to_string()),*],
+                outputs: vec![#(#outputs.to_string()),*],
+                state_mutability: #state_mutability.to_string(),
+            }
+        }
+    });
+    let storage_layout = storage_layout.map_or_else(|| quote! { None }, layout_tokens);
+    quote! {
+        rustry_test::common::contract::ContractInfo {
+            name: #name.to_string(),
+            functions: vec![#(#functions),*],
+            errors: vec![#(#error_signatures.to_string()),*],
+            sources: #sources,
+            storage_layout: #storage_layout,
+        }
+    }
+}
+
+/// The `ContractSources` of the deployed code, the literal being located in the Rust file.
+/// `generated` are the id, name and content of the sources generated by the compiler.
+fn contract_sources(
+    lit_str: &syn::LitStr,
+    literal_file: &str,
+    sources: &HashMap<String, HashMap<String, i32>>,
+    source_map: &str,
+    generated: impl Iterator<Item = (i32, String, String)>,
+    functions: impl Iterator<Item = (String, usize, usize, usize)>,
+) -> proc_macro2::TokenStream {
+    let span = lit_str.span().unwrap();
+    let path = span.source_file().path();
+    let token = lit_str.token().to_string();
+    // the content starts after the quote and the `r#` of a raw string
+    let prefix = token.find('"').unwrap_or(0) + 1;
+    // the literals of a `set_up` are parsed again, without their spans
+    let rust_file = std::fs::read_to_string(&path).unwrap_or_default();
+    let (line, column) = match rust_file.find(&token) {
+        Some(offset) => {
+            let before = &rust_file[..offset];
+            let line_start = before.rfind('\n').map_or(0, |newline| newline + 1);
+            (
+                before.matches('\n').count() + 1,
+                offset - line_start + 1 + prefix,
+            )
+        }
+        None => (span.line(), span.column() + prefix),
+    };
+    let path = path.display().to_string();
+
+    let files = sources
+        .iter()
+        .filter_map(|(name, source)| {
+            let id = *source.get("id")?;
+            let (content, origin) = if name == literal_file {
+                let origin = quote! {
+                    Some(rustry_test::compilers::solidity::source_map::Origin {
+                        path: #path.to_string(),
+                        line: #line,
+                        column: #column,
+                    })
+                };
+                (lit_str.value(), origin)
+            } else {
+                (std::fs::read_to_string(name).ok()?, quote! { None })
+            };
+            Some((id, name.clone(), content, origin))
+        })
+        .chain(generated.map(|(id, name, content)| (id, name, content, quote! { None })))
+        .map(|(id, name, content, origin)| {
+            quote! {
+                rustry_test::compilers::solidity::source_map::SourceFile {
+                    id: #id,
+                    name: #name.to_string(),
+                    content: #content.to_string(),
+                    origin: #origin,
+                }
+            }
+        });
+    let functions = functions.map(|(name, entry_point, parameter_slots, return_slots)| {
+        quote! {
+            rustry_test::compilers::solidity::source_map::InternalFunction {
+                name: #name.to_string(),
+                entry_point: #entry_point,
+                parameter_slots: #parameter_slots,
+                return_slots: #return_slots,
+            }
+        }
+    });
+    quote! {
+        Some(rustry_test::compilers::solidity::source_map::ContractSources {
+            source_map: #source_map.to_string(),
+            files: vec![#(#files),*],
+            functions: vec![#(#functions),*],
+        })
+    }
+}
+
 fn make_contract_instance(
+    binding: Option<ContractBinding>,
     impl_fns: impl Iterator<Item = proc_macro2::TokenStream>,
+    bindings: proc_macro2::TokenStream,
+    info: proc_macro2::TokenStream,
+    storage: proc_macro2::TokenStream,
     bytecode: &String,
 ) -> proc_macro2::TokenStream {
-    quote! {
-        {
-            #[derive(Default, Debug)]
-            struct ContractMethods {
-                pub address: revm::primitives::Address,
-            };
+    let items = quote! {
+        #[allow(unused_imports)]
+        use rustry_test::provider::db::Frontend as _;
 
-            impl ContractMethods {
-                fn new(address: revm::primitives::Address) -> Self {
-                    Self {
-                        address
-                    }
-                }
+        #bindings
 
-                #(
-                    #impl_fns
-                 )*
-            }
+        #[derive(Default, Debug)]
+        pub struct ContractMethods {
+            pub address: revm::primitives::Address,
+        }
 
-            #[derive(Default, Debug)]
-            struct ContractInstance {
-                pub code: revm::primitives::Bytes,
+        impl ContractMethods {
+            pub fn new(address: revm::primitives::Address) -> Self {
+                Self {
+                    address
+                }
             }
 
-            impl ContractInstance {
-                fn new(code: revm::primitives::Bytes) -> Self {
-                    Self {
-                        code,
-                    }
-                }
+            #(
+                #impl_fns
+             )*
+        }
 
-                fn deploy<'a>(self, provider: &'a mut rustry_test::provider::Provider) -> DeployedContract {
-                    let address = provider.deploy(self.code).unwrap();
-                    DeployedContract {
-                        address,
-                        methods: ContractMethods::new(address)
-                    }
+        #[derive(Default, Debug)]
+        pub struct ContractInstance {
+            pub code: revm::primitives::Bytes,
+        }
+
+        impl ContractInstance {
+            pub fn new(code: revm::primitives::Bytes) -> Self {
+                Self {
+                    code,
                 }
             }
 
-            struct DeployedContract {
-                pub address: revm::primitives::Address,
-                pub methods: ContractMethods
+            pub fn deploy<'a>(self, provider: &'a mut rustry_test::provider::Provider) -> DeployedContract {
+                let address = provider
+                    .deploy(self.code)
+                    .unwrap_or_else(|err| panic!("{err}"));
+                provider.register_contract(address, #info);
+                DeployedContract {
+                    address,
+                    methods: ContractMethods::new(address)
+                }
             }
+        }
 
-            impl rustry_test::common::contract::Contract for DeployedContract {
-                fn call(&mut self, provider: &mut rustry_test::provider::Provider, data: Vec<u8>) -> rustry_test::provider::db::ExecRes {
-                    provider.call(self.address, data.into())
-                }
+        pub struct DeployedContract {
+            pub address: revm::primitives::Address,
+            pub methods: ContractMethods
+        }
 
-                fn staticcall(&mut self, provider: &mut rustry_test::provider::Provider, data: Vec<u8>) -> rustry_test::provider::db::ExecRes {
-                    provider.staticcall(self.address, data.into())
-                }
+        impl DeployedContract {
+            /// Decode the events emitted by this contract, skipping the unknown ones.
+            pub fn decode_logs(&self, logs: &[revm::primitives::Log]) -> Vec<ContractEvent> {
+                logs.iter()
+                    .filter(|log| log.address == self.address)
+                    .filter_map(ContractEvent::decode)
+                    .collect()
+            }
 
-                fn send(&mut self, provider: &mut rustry_test::provider::Provider, value: revm::primitives::alloy_primitives::Uint<256, 4>) -> rustry_test::provider::db::ExecRes {
-                    provider.send(self.address, value)
+            /// Decode why a call reverted, `None` if it did not.
+            pub fn decode_error(&self, res: &rustry_test::provider::db::ExecRes) -> Option<ContractError> {
+                match res {
+                    rustry_test::provider::db::ExecRes::Revert(data, _) => Some(ContractError::decode(data)),
+                    _ => None,
                 }
             }
+        }
 
-            let as_bytes = hex::decode(#bytecode).unwrap();
+        #storage
 
-            let _bytecode: revm::primitives::Bytes = as_bytes.into();
+        impl rustry_test::common::contract::Contract for DeployedContract {
+            fn call(&mut self, provider: &mut rustry_test::provider::Provider, data: Vec<u8>) -> rustry_test::provider::db::ExecRes {
+                provider.call(self.address, data.into())
+            }
+
+            fn staticcall(&mut self, provider: &mut rustry_test::provider::Provider, data: Vec<u8>) -> rustry_test::provider::db::ExecRes {
+                provider.staticcall(self.address, data.into())
+            }
 
-            ContractInstance::new(_bytecode)
+            fn send(&mut self, provider: &mut rustry_test::provider::Provider, value: revm::primitives::alloy_primitives::Uint<256, 4>) -> rustry_test::provider::db::ExecRes {
+                provider.send(self.address, value)
+            }
         }
+
+    };
+
+    match binding {
+        Some(ContractBinding { mutability, name }) => quote! {
+            #[allow(dead_code, non_snake_case)]
+            mod #name {
+                #items
+            }
+
+            let #mutability #name = #name::ContractInstance::new(hex::decode(#bytecode).unwrap().into());
+        },
+        None => quote! {
+            {
+                #items
+
+                let as_bytes = hex::decode(#bytecode).unwrap();
+
+                let _bytecode: revm::primitives::Bytes = as_bytes.into();
+
+                ContractInstance::new(_bytecode)
+            }
+        },
     }
 }
@@ -0,0 +1,550 @@
+pub use super::revert::{ERROR_SELECTOR, PANIC_SELECTOR};
+use super::{labels::Labeled, revert::RevertReason};
+use crate::{
+    provider::{db::ExecRes, trace::CallTrace},
+    utils::{
+        abi::{abi_encode_string, get_event_topic, get_sig},
+        codec::{encode, AbiValue},
+    },
+};
+use revm::primitives::{Address, Bytes, Log, B256, U256};
+use std::fmt;
+
+/// What a reverting call is expected to return.
+#[derive(Debug, Clone)]
+pub enum ExpectedRevert {
+    /// Any revert, whatever the data.
+    Any,
+    /// The revert data should match exactly.
+    Data(Bytes),
+    /// `Error(string)` with this message, as thrown by `require` or `revert("...")`.
+    Message(String),
+    /// `Panic(uint256)` with this code, as thrown by `assert` or the checked arithmetic.
+    Panic(u64),
+    /// Only the 4 bytes selector of a custom error.
+    Selector([u8; 4]),
+    /// A custom error and its arguments.
+    Error {
+        signature: String,
+        args: Vec<AbiValue>,
+    },
+}
+
+impl ExpectedRevert {
+    fn matches(&self, data: &[u8]) -> bool {
+        match self {
+            ExpectedRevert::Any => true,
+            ExpectedRevert::Selector(selector) => data.starts_with(selector),
+            expected => expected.encode().as_deref() == Some(data),
+        }
+    }
+
+    /// The exact revert data expected, if it can be known.
+    fn encode(&self) -> Option<Vec<u8>> {
+        match self {
+            ExpectedRevert::Any | ExpectedRevert::Selector(_) => None,
+            ExpectedRevert::Data(data) => Some(data.to_vec()),
+            ExpectedRevert::Message(message) => {
+                Some([ERROR_SELECTOR.to_vec(), abi_encode_string(message)].concat())
+            }
+            ExpectedRevert::Panic(code) => Some(
+                [
+                    PANIC_SELECTOR.to_vec(),
+                    U256::from(*code).to_be_bytes::<32>().to_vec(),
+                ]
+                .concat(),
+            ),
+            ExpectedRevert::Error { signature, args } => {
+                Some([get_sig(signature).to_vec(), encode(args)].concat())
+            }
+        }
+    }
+}
+
+impl fmt::Display for ExpectedRevert {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExpectedRevert::Any => write!(f, "any revert"),
+            ExpectedRevert::Data(data) => write!(f, "revert data {data}"),
+            ExpectedRevert::Message(message) => write!(f, "Error({message:?})"),
+            ExpectedRevert::Panic(code) => write!(f, "Panic({code:#04x})"),
+            ExpectedRevert::Selector(selector) => {
+                write!(
+                    f,
+                    "custom error with selector {}",
+                    Bytes::from(selector.to_vec())
+                )
+            }
+            ExpectedRevert::Error { signature, .. } => write!(f, "{signature}"),
+        }
+    }
+}
+
+/// Human readable version of some revert data, decoding the standard errors.
+pub fn describe_revert_data(data: &[u8]) -> String {
+    RevertReason::decode(data).to_string()
+}
+
+/// Check that `res` reverted as `expected`, returns the failure message otherwise.
+pub fn check_revert(res: &ExecRes, expected: &ExpectedRevert) -> Result<(), String> {
+    let data = match res {
+        ExecRes::Revert(data, _) => data,
+        ExecRes::Success(data, _) => {
+            return Err(format!(
+                "expected {expected}\n  but the call succeeded and returned {data}"
+            ))
+        }
+        ExecRes::Halt(reason, _) => {
+            return Err(format!(
+                "expected {expected}\n  but the call halted with {reason:?}"
+            ))
+        }
+    };
+
+    if expected.matches(data) {
+        return Ok(());
+    }
+
+    let mut message = format!(
+        "revert mismatch\n  expected: {expected}\n    actual: {}",
+        describe_revert_data(data)
+    );
+    if let Some(expected_data) = expected.encode() {
+        message.push_str(&format!(
+            "\n  expected data: {}\n    actual data: {data}",
+            Bytes::from(expected_data)
+        ));
+    } else {
+        message.push_str(&format!("\n  actual data: {data}"));
+    }
+    Err(message)
+}
+
+#[track_caller]
+fn assert_revert(res: &ExecRes, expected: ExpectedRevert) {
+    if let Err(message) = check_revert(res, &expected) {
+        panic!("{message}");
+    }
+}
+
+/// Assert that the call reverted, with any data.
+/// Unlike `!res.is_success()`, a halt will not pass this assertion.
+#[track_caller]
+pub fn expect_revert(res: &ExecRes) {
+    assert_revert(res, ExpectedRevert::Any)
+}
+
+/// Assert that the call reverted with exactly `data`.
+#[track_caller]
+pub fn expect_revert_data(res: &ExecRes, data: impl Into<Bytes>) {
+    assert_revert(res, ExpectedRevert::Data(data.into()))
+}
+
+/// Assert that the call reverted with `Error(message)`.
+#[track_caller]
+pub fn expect_revert_message(res: &ExecRes, message: &str) {
+    assert_revert(res, ExpectedRevert::Message(message.to_string()))
+}
+
+/// Assert that the call reverted with `Panic(code)`.
+#[track_caller]
+pub fn expect_panic(res: &ExecRes, code: u64) {
+    assert_revert(res, ExpectedRevert::Panic(code))
+}
+
+/// Assert that the call reverted with a custom error having this selector.
+#[track_caller]
+pub fn expect_revert_selector(res: &ExecRes, selector: [u8; 4]) {
+    assert_revert(res, ExpectedRevert::Selector(selector))
+}
+
+/// Assert that the call reverted with the custom error `signature`,
+/// e.g. `Unauthorized(uint256)` and its arguments.
+/// If no argument is passed, only the selector is checked.
+#[track_caller]
+pub fn expect_revert_error(res: &ExecRes, signature: &str, args: Vec<AbiValue>) {
+    let expected = if args.is_empty() && !signature.ends_with("()") {
+        ExpectedRevert::Selector(get_sig(signature))
+    } else {
+        ExpectedRevert::Error {
+            signature: signature.to_string(),
+            args,
+        }
+    };
+    assert_revert(res, expected)
+}
+
+/// An event that should have been emitted.
+#[derive(Debug, Clone, Default)]
+pub struct ExpectedLog {
+    /// The emitter, any if `None`
+    pub address: Option<Address>,
+    pub topics: Vec<B256>,
+    /// The non-indexed data, any if `None`
+    pub data: Option<Bytes>,
+}
+
+impl ExpectedLog {
+    /// Expect the event `signature`, e.g. `Transfer(address,address,uint256)`.
+    pub fn new(signature: &str) -> Self {
+        Self {
+            topics: vec![get_event_topic(signature)],
+            ..Default::default()
+        }
+    }
+
+    /// Expect an anonymous event, with no topic for the signature.
+    pub fn anonymous() -> Self {
+        Self::default()
+    }
+
+    pub fn emitter(mut self, address: Address) -> Self {
+        self.address = Some(address);
+        self
+    }
+
+    /// Add an indexed parameter, as a 32 bytes word.
+    pub fn topic(mut self, topic: impl Into<B256>) -> Self {
+        self.topics.push(topic.into());
+        self
+    }
+
+    pub fn data(mut self, data: impl Into<Bytes>) -> Self {
+        self.data = Some(data.into());
+        self
+    }
+
+    pub fn matches(&self, log: &Log) -> bool {
+        self.address.map_or(true, |address| address == log.address)
+            && self.topics == log.topics
+            && self.data.as_ref().map_or(true, |data| *data == log.data)
+    }
+}
+
+impl fmt::Display for ExpectedLog {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "address: ")?;
+        match self.address {
+            Some(address) => write!(f, "{}", Labeled(address))?,
+            None => write!(f, "any")?,
+        }
+        write!(f, ", topics: {:?}, data: ", self.topics)?;
+        match &self.data {
+            Some(data) => write!(f, "{data}"),
+            None => write!(f, "any"),
+        }
+    }
+}
+
+/// Check that one of the `logs` is the `expected` one, returns the failure message otherwise.
+pub fn check_emit(logs: &[Log], expected: &ExpectedLog) -> Result<(), String> {
+    if logs.iter().any(|log| expected.matches(log)) {
+        return Ok(());
+    }
+
+    let mut message = format!("expected log not emitted\n  expected: {expected}");
+    if logs.is_empty() {
+        message.push_str("\n  no log was emitted");
+    }
+    for (i, log) in logs.iter().enumerate() {
+        message.push_str(&format!(
+            "\n  log #{i}: address: {}, topics: {:?}, data: {}",
+            Labeled(log.address),
+            log.topics,
+            log.data
+        ));
+    }
+    Err(message)
+}
+
+/// Assert that the execution emitted the `expected` log.
+#[track_caller]
+pub fn expect_emit(res: &ExecRes, expected: &ExpectedLog) {
+    if let Err(message) = check_emit(res.logs(), expected) {
+        panic!("{message}");
+    }
+}
+
+/// A call that should be made a number of times.
+#[derive(Debug, Clone)]
+pub struct ExpectedCall {
+    /// The address called, the one of the code for the delegate calls
+    pub callee: Address,
+    /// Prefix of the calldata, e.g. only a selector
+    pub calldata: Bytes,
+    /// Any if `None`
+    pub value: Option<U256>,
+    /// The gas passed to the call, any if `None`
+    pub gas: Option<u64>,
+    pub count: usize,
+}
+
+impl ExpectedCall {
+    /// Expect a single call.
+    pub fn new(callee: Address, calldata: impl Into<Bytes>) -> Self {
+        Self {
+            callee,
+            calldata: calldata.into(),
+            value: None,
+            gas: None,
+            count: 1,
+        }
+    }
+
+    pub fn value(mut self, value: U256) -> Self {
+        self.value = Some(value);
+        self
+    }
+
+    pub fn gas(mut self, gas: u64) -> Self {
+        self.gas = Some(gas);
+        self
+    }
+
+    pub fn count(mut self, count: usize) -> Self {
+        self.count = count;
+        self
+    }
+
+    pub fn matches(&self, call: &CallTrace) -> bool {
+        !call.kind.is_create()
+            && call.code_address == self.callee
+            && call.input.starts_with(&self.calldata)
+            && self.value.map_or(true, |value| value == call.value)
+            && self.gas.map_or(true, |gas| gas == call.gas_limit)
+    }
+}
+
+impl fmt::Display for ExpectedCall {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "to: {}, calldata: {}",
+            Labeled(self.callee),
+            self.calldata
+        )?;
+        match self.value {
+            Some(value) => write!(f, ", value: {value}")?,
+            None => write!(f, ", value: any")?,
+        }
+        match self.gas {
+            Some(gas) => write!(f, ", gas: {gas}"),
+            None => write!(f, ", gas: any"),
+        }
+    }
+}
+
+fn flatten_calls<'a>(trace: &'a CallTrace, calls: &mut Vec<&'a CallTrace>) {
+    if !trace.kind.is_create() {
+        calls.push(trace);
+    }
+    for child in &trace.children {
+        flatten_calls(child, calls);
+    }
+}
+
+/// Check that the `expected` call was made as many times as expected in the call trees,
+/// returns the failure message with the calls made otherwise.
+pub fn check_calls(traces: &[CallTrace], expected: &ExpectedCall) -> Result<(), String> {
+    let mut calls = Vec::new();
+    for trace in traces {
+        flatten_calls(trace, &mut calls);
+    }
+    let count = calls.iter().filter(|call| expected.matches(call)).count();
+    if count == expected.count {
+        return Ok(());
+    }
+
+    let mut message = format!(
+        "expected call made {count} times instead of {}\n  expected: {expected}",
+        expected.count
+    );
+    if calls.is_empty() {
+        message.push_str("\n  no call was made");
+    }
+    for (i, call) in calls.iter().enumerate() {
+        message.push_str(&format!(
+            "\n  call #{i}: {:?} from {} to {}, calldata: {}, value: {}, gas: {}",
+            call.kind,
+            Labeled(call.from),
+            Labeled(call.code_address),
+            call.input,
+            call.value,
+            call.gas_limit
+        ));
+    }
+    Err(message)
+}
+
+/// Assert that an `ExecRes` reverted.
+///
+/// # Examples
+///
+/// ```ignore
+/// assert_reverts!(res);
+/// assert_reverts!(res, "deadline has passed");
+/// assert_reverts!(res, panic = 0x11);
+/// assert_reverts!(res, selector = [0x82, 0xb4, 0x29, 0x00]);
+/// assert_reverts!(res, error = "Unauthorized(address)", alice);
+/// assert_reverts!(res, data = vec![0xde, 0xad]);
+/// ```
+#[macro_export]
+macro_rules! assert_reverts {
+    ($res:expr $(,)?) => {
+        $crate::common::assertions::expect_revert(&$res)
+    };
+    ($res:expr, panic = $code:expr $(,)?) => {
+        $crate::common::assertions::expect_panic(&$res, $code)
+    };
+    ($res:expr, selector = $selector:expr $(,)?) => {
+        $crate::common::assertions::expect_revert_selector(&$res, $selector)
+    };
+    ($res:expr, data = $data:expr $(,)?) => {
+        $crate::common::assertions::expect_revert_data(&$res, $data)
+    };
+    ($res:expr, error = $signature:expr $(, $arg:expr)* $(,)?) => {
+        $crate::common::assertions::expect_revert_error(
+            &$res,
+            $signature,
+            vec![$($crate::utils::codec::ToAbi::to_abi(&$arg)),*],
+        )
+    };
+    ($res:expr, $message:expr $(,)?) => {
+        $crate::common::assertions::expect_revert_message(&$res, $message)
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        check_emit, check_revert, ExpectedLog, ExpectedRevert, ERROR_SELECTOR, PANIC_SELECTOR,
+    };
+    use crate::{
+        provider::db::{ExecRes, Receipt},
+        utils::{
+            abi::{abi_encode_string, get_event_topic, get_sig},
+            codec::AbiValue,
+        },
+    };
+    use revm::primitives::{Address, Bytes, Halt, Log, B256, U256};
+
+    fn revert(data: Vec<u8>) -> ExecRes {
+        ExecRes::Revert(data.into(), Receipt::default())
+    }
+
+    fn error(message: &str) -> ExecRes {
+        revert([ERROR_SELECTOR.to_vec(), abi_encode_string(message)].concat())
+    }
+
+    #[test]
+    fn revert_message() {
+        let res = error("deadline has passed");
+        assert_reverts!(res);
+        assert_reverts!(res, "deadline has passed");
+
+        let err = check_revert(&res, &ExpectedRevert::Message("nope".into())).unwrap_err();
+        assert!(err.contains("expected: Error(\"nope\")"));
+        assert!(err.contains("actual: Error(\"deadline has passed\")"));
+    }
+
+    #[test]
+    fn revert_panic() {
+        let res = revert(
+            [
+                PANIC_SELECTOR.to_vec(),
+                U256::from(0x11).to_be_bytes::<32>().to_vec(),
+            ]
+            .concat(),
+        );
+        assert_reverts!(res, panic = 0x11);
+        assert!(check_revert(&res, &ExpectedRevert::Panic(0x12)).is_err());
+    }
+
+    #[test]
+    fn revert_custom_error() {
+        let selector = get_sig("Unauthorized(uint256)");
+        let res = revert(
+            [
+                selector.to_vec(),
+                U256::from(1).to_be_bytes::<32>().to_vec(),
+            ]
+            .concat(),
+        );
+        assert_reverts!(res, selector = selector);
+        assert_reverts!(res, error = "Unauthorized(uint256)");
+        assert_reverts!(res, error = "Unauthorized(uint256)", U256::from(1));
+        assert!(check_revert(&res, &ExpectedRevert::Selector([0; 4])).is_err());
+    }
+
+    #[test]
+    fn revert_custom_error_args() {
+        let alice = Address::repeat_byte(0xa1);
+        let res = revert(
+            [
+                get_sig("Unauthorized(address)").to_vec(),
+                alice.into_word().to_vec(),
+            ]
+            .concat(),
+        );
+        assert_reverts!(res, error = "Unauthorized(address)", alice);
+        let bob = ExpectedRevert::Error {
+            signature: "Unauthorized(address)".to_string(),
+            args: vec![AbiValue::Address(Address::repeat_byte(0xb0))],
+        };
+        assert!(check_revert(&res, &bob).is_err());
+
+        let res = revert(
+            [
+                get_sig("InvalidName(string)").to_vec(),
+                abi_encode_string("rustry"),
+            ]
+            .concat(),
+        );
+        assert_reverts!(res, error = "InvalidName(string)", "rustry");
+        assert_reverts!(res, error = "InvalidName(string)", String::from("rustry"));
+    }
+
+    #[test]
+    fn not_a_revert() {
+        let success = ExecRes::Success(Bytes::new(), Receipt::default());
+        let err = check_revert(&success, &ExpectedRevert::Any).unwrap_err();
+        assert!(err.contains("succeeded"));
+
+        let halt = ExecRes::Halt(
+            Halt::OutOfGas(revm::primitives::OutOfGasError::BasicOutOfGas),
+            Receipt::default(),
+        );
+        let err = check_revert(&halt, &ExpectedRevert::Any).unwrap_err();
+        assert!(err.contains("halted"));
+    }
+
+    #[test]
+    fn emit() {
+        let emitter = Address::repeat_byte(0x42);
+        let to = B256::repeat_byte(0xaa);
+        let logs = vec![Log {
+            address: emitter,
+            topics: vec![
+                get_event_topic("Transfer(address,address,uint256)"),
+                B256::ZERO,
+                to,
+            ],
+            data: U256::from(5).to_be_bytes::<32>().to_vec().into(),
+        }];
+
+        let expected = ExpectedLog::new("Transfer(address,address,uint256)")
+            .emitter(emitter)
+            .topic(B256::ZERO)
+            .topic(to);
+        assert!(check_emit(&logs, &expected).is_ok());
+
+        let with_data = expected
+            .clone()
+            .data(U256::from(5).to_be_bytes::<32>().to_vec());
+        assert!(check_emit(&logs, &with_data).is_ok());
+
+        let err = check_emit(&logs, &expected.data(vec![0])).unwrap_err();
+        assert!(err.contains("log #0"));
+        assert!(check_emit(&[], &ExpectedLog::anonymous()).is_err());
+    }
+}
@@ -1,4 +1,9 @@
-use crate::provider::{db::ExecRes, Provider};
+use crate::{
+    common::storage::StorageLayout,
+    compilers::solidity::source_map::ContractSources,
+    provider::{db::ExecRes, Provider},
+    utils::abi::get_sig,
+};
 
 pub trait Contract {
     fn call(&mut self, provider: &mut Provider, data: Vec<u8>) -> ExecRes;
@@ -9,3 +14,49 @@ pub trait Contract {
         value: revm::primitives::alloy_primitives::Uint<256, 4>,
     ) -> ExecRes;
 }
+
+/// What a deployed contract exposes, registered in the provider by its bindings.
+#[derive(Debug, Clone, Default)]
+pub struct ContractInfo {
+    pub name: String,
+    pub functions: Vec<FunctionInfo>,
+    /// Signatures of the custom errors
+    pub errors: Vec<String>,
+    /// To locate the reverts in the Solidity sources
+    pub sources: Option<ContractSources>,
+    /// To read the variables of the contract
+    pub storage_layout: Option<StorageLayout>,
+}
+
+impl ContractInfo {
+    /// The function called by this calldata.
+    pub fn function(&self, data: &[u8]) -> Option<&FunctionInfo> {
+        let selector = data.get(..4)?;
+        self.functions
+            .iter()
+            .find(|function| get_sig(&function.signature()) == selector)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct FunctionInfo {
+    pub name: String,
+    /// Canonical ABI types of the inputs
+    pub inputs: Vec<String>,
+    /// Canonical ABI types of the outputs
+    pub outputs: Vec<String>,
+    /// `pure`, `view`, `nonpayable` or `payable`
+    pub state_mutability: String,
+}
+
+impl FunctionInfo {
+    /// e.g. `transfer(address,uint256)`
+    pub fn signature(&self) -> String {
+        format!("{}({})", self.name, self.inputs.join(","))
+    }
+
+    /// If it can change the state of the contract.
+    pub fn is_mutating(&self) -> bool {
+        !matches!(self.state_mutability.as_str(), "pure" | "view")
+    }
+}
@@ -0,0 +1,88 @@
+//! Names of the addresses, shown along with them in the traces and the failure messages.
+//! The labels of the providers of a thread are shared by all the messages formatted on it, the
+//! last one set for an address being shown until its provider removes it.
+
+use revm::primitives::Address;
+use std::{cell::RefCell, collections::HashMap, fmt};
+
+thread_local! {
+    /// The labels set for each address, in the order they were set
+    static LABELS: RefCell<HashMap<Address, Vec<String>>> = RefCell::new(HashMap::new());
+}
+
+pub fn set_label(address: Address, label: impl Into<String>) {
+    LABELS.with(|labels| {
+        labels
+            .borrow_mut()
+            .entry(address)
+            .or_default()
+            .push(label.into())
+    });
+}
+
+pub fn get_label(address: &Address) -> Option<String> {
+    LABELS.with(|labels| labels.borrow().get(address)?.last().cloned())
+}
+
+/// Remove a `label` set for `address`, the previous one being shown again.
+pub fn remove_label(address: &Address, label: &str) {
+    LABELS.with(|labels| {
+        let mut labels = labels.borrow_mut();
+        let Some(stack) = labels.get_mut(address) else {
+            return;
+        };
+        if let Some(i) = stack.iter().rposition(|set| set == label) {
+            stack.remove(i);
+        }
+        if stack.is_empty() {
+            labels.remove(address);
+        }
+    });
+}
+
+/// Formats as `alice (0x..)` when labeled, or as the address otherwise.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Labeled(pub Address);
+
+impl fmt::Display for Labeled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match get_label(&self.0) {
+            Some(label) => write!(f, "{label} ({})", self.0),
+            None => write!(f, "{}", self.0),
+        }
+    }
+}
+
+impl fmt::Debug for Labeled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{remove_label, set_label, Labeled};
+    use revm::primitives::Address;
+
+    #[test]
+    fn labeled() {
+        let alice = Address::repeat_byte(0xa1);
+        assert_eq!(Labeled(alice).to_string(), alice.to_string());
+        set_label(alice, "alice");
+        assert_eq!(Labeled(alice).to_string(), format!("alice ({alice})"));
+        assert_eq!(
+            format!("{:?}", Some(Labeled(alice))),
+            format!("Some(alice ({alice}))")
+        );
+        remove_label(&alice, "alice");
+        assert_eq!(Labeled(alice).to_string(), alice.to_string());
+
+        // set by two providers, the first one dropped
+        set_label(alice, "alice");
+        set_label(alice, "bob");
+        remove_label(&alice, "alice");
+        assert_eq!(Labeled(alice).to_string(), format!("bob ({alice})"));
+        remove_label(&alice, "bob");
+        assert_eq!(Labeled(alice).to_string(), alice.to_string());
+    }
+}
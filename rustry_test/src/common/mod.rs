@@ -1,2 +1,6 @@
+pub mod assertions;
 pub mod contract;
+pub mod labels;
+pub mod revert;
+pub mod storage;
 pub mod utils;
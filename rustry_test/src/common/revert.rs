@@ -0,0 +1,198 @@
+use crate::utils::abi::abi_decode_string;
+use revm::primitives::{Bytes, U256};
+use std::fmt;
+
+/// `Error(string)`
+pub const ERROR_SELECTOR: [u8; 4] = [0x08, 0xc3, 0x79, 0xa0];
+/// `Panic(uint256)`
+pub const PANIC_SELECTOR: [u8; 4] = [0x4e, 0x48, 0x7b, 0x71];
+
+/// Codes of the `Panic(uint256)` error thrown by the compiler checks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PanicCode {
+    Generic,
+    AssertFailed,
+    ArithmeticOverflow,
+    DivisionByZero,
+    InvalidEnumValue,
+    InvalidStorageEncoding,
+    EmptyArrayPop,
+    IndexOutOfBounds,
+    OutOfMemory,
+    UninitializedFunction,
+    Unknown(U256),
+}
+
+impl PanicCode {
+    pub fn code(&self) -> U256 {
+        let code = match self {
+            PanicCode::Generic => 0x00,
+            PanicCode::AssertFailed => 0x01,
+            PanicCode::ArithmeticOverflow => 0x11,
+            PanicCode::DivisionByZero => 0x12,
+            PanicCode::InvalidEnumValue => 0x21,
+            PanicCode::InvalidStorageEncoding => 0x22,
+            PanicCode::EmptyArrayPop => 0x31,
+            PanicCode::IndexOutOfBounds => 0x32,
+            PanicCode::OutOfMemory => 0x41,
+            PanicCode::UninitializedFunction => 0x51,
+            PanicCode::Unknown(code) => return *code,
+        };
+        U256::from(code)
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            PanicCode::Generic => "generic compiler panic",
+            PanicCode::AssertFailed => "assertion failed",
+            PanicCode::ArithmeticOverflow => "arithmetic overflow or underflow",
+            PanicCode::DivisionByZero => "division or modulo by zero",
+            PanicCode::InvalidEnumValue => "conversion to an invalid enum value",
+            PanicCode::InvalidStorageEncoding => "incorrectly encoded storage byte array",
+            PanicCode::EmptyArrayPop => "pop on an empty array",
+            PanicCode::IndexOutOfBounds => "array index out of bounds",
+            PanicCode::OutOfMemory => "too much memory allocated",
+            PanicCode::UninitializedFunction => "call to an uninitialized function",
+            PanicCode::Unknown(_) => "unknown panic code",
+        }
+    }
+}
+
+impl From<U256> for PanicCode {
+    fn from(code: U256) -> Self {
+        let known = [
+            PanicCode::Generic,
+            PanicCode::AssertFailed,
+            PanicCode::ArithmeticOverflow,
+            PanicCode::DivisionByZero,
+            PanicCode::InvalidEnumValue,
+            PanicCode::InvalidStorageEncoding,
+            PanicCode::EmptyArrayPop,
+            PanicCode::IndexOutOfBounds,
+            PanicCode::OutOfMemory,
+            PanicCode::UninitializedFunction,
+        ];
+        known
+            .into_iter()
+            .find(|panic| panic.code() == code)
+            .unwrap_or(PanicCode::Unknown(code))
+    }
+}
+
+impl fmt::Display for PanicCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match u64::try_from(self.code()) {
+            Ok(code) => write!(f, "Panic({code:#04x}): {}", self.description()),
+            Err(_) => write!(f, "Panic({}): {}", self.code(), self.description()),
+        }
+    }
+}
+
+/// Why a call reverted, decoded from its revert data.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RevertReason {
+    /// Reverted without any data, like `revert()` or a failed `require` without message.
+    Empty,
+    /// `Error(string)`, as thrown by `require` or `revert("...")`.
+    Error(String),
+    /// `Panic(uint256)`, as thrown by `assert` or the checked arithmetic.
+    Panic(PanicCode),
+    /// A custom error, decode it with the `ContractError` of the contract.
+    Custom { selector: [u8; 4], data: Bytes },
+    /// Anything else.
+    Raw(Bytes),
+}
+
+impl RevertReason {
+    pub fn decode(data: &[u8]) -> Self {
+        if data.is_empty() {
+            return RevertReason::Empty;
+        }
+        if let Some(encoded) = data.strip_prefix(&ERROR_SELECTOR) {
+            if let Some(message) = abi_decode_string(encoded) {
+                return RevertReason::Error(message);
+            }
+        }
+        if let Some(encoded) = data.strip_prefix(&PANIC_SELECTOR) {
+            if let Some(code) = U256::try_from_be_slice(encoded).filter(|_| encoded.len() == 32) {
+                return RevertReason::Panic(code.into());
+            }
+        }
+        match <[u8; 4]>::try_from(&data[..data.len().min(4)]) {
+            Ok(selector) if (data.len() - 4) % 32 == 0 => RevertReason::Custom {
+                selector,
+                data: data.to_vec().into(),
+            },
+            _ => RevertReason::Raw(data.to_vec().into()),
+        }
+    }
+}
+
+impl fmt::Display for RevertReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RevertReason::Empty => write!(f, "empty revert data"),
+            RevertReason::Error(message) => write!(f, "Error({message:?})"),
+            RevertReason::Panic(code) => write!(f, "{code}"),
+            RevertReason::Custom { selector, data } => write!(
+                f,
+                "custom error {} with data {data}",
+                Bytes::from(selector.to_vec())
+            ),
+            RevertReason::Raw(data) => write!(f, "revert data {data}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PanicCode, RevertReason, ERROR_SELECTOR, PANIC_SELECTOR};
+    use crate::utils::abi::{abi_encode_string, get_sig};
+    use revm::primitives::U256;
+
+    #[test]
+    fn decode_standard() {
+        assert_eq!(RevertReason::decode(&[]), RevertReason::Empty);
+
+        let data = [ERROR_SELECTOR.to_vec(), abi_encode_string("nope")].concat();
+        assert_eq!(
+            RevertReason::decode(&data),
+            RevertReason::Error(String::from("nope"))
+        );
+
+        let data = [
+            PANIC_SELECTOR.to_vec(),
+            U256::from(0x11).to_be_bytes::<32>().to_vec(),
+        ]
+        .concat();
+        let reason = RevertReason::decode(&data);
+        assert_eq!(reason, RevertReason::Panic(PanicCode::ArithmeticOverflow));
+        assert_eq!(
+            reason.to_string(),
+            "Panic(0x11): arithmetic overflow or underflow"
+        );
+
+        let data = [
+            PANIC_SELECTOR.to_vec(),
+            U256::from(0x99).to_be_bytes::<32>().to_vec(),
+        ]
+        .concat();
+        assert_eq!(
+            RevertReason::decode(&data),
+            RevertReason::Panic(PanicCode::Unknown(U256::from(0x99)))
+        );
+    }
+
+    #[test]
+    fn decode_custom() {
+        let selector = get_sig("Unauthorized()");
+        assert!(matches!(
+            RevertReason::decode(&selector),
+            RevertReason::Custom { selector: s, .. } if s == selector
+        ));
+        assert!(matches!(
+            RevertReason::decode(&[0xde, 0xad]),
+            RevertReason::Raw(_)
+        ));
+    }
+}
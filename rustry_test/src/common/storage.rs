@@ -0,0 +1,696 @@
+//! Storage of the contracts read by variable name, from the layouts output by the compilers.
+//!
+//! https://docs.soliditylang.org/en/latest/internals/layout_in_storage.html
+
+use crate::{
+    compilers::{solidity::solc, vyper::vyperc},
+    provider::{db::Cheats, Provider},
+    utils::slots::{array_data_slot, element_slot, mapping_slot, read_packed},
+};
+use revm::primitives::{keccak256, Address, Bytes, B256, U256};
+use std::collections::HashMap;
+
+/// How the compiler lays out the dynamic values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    Solidity,
+    Vyper,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StorageLayout {
+    pub encoding: Encoding,
+    pub variables: Vec<Variable>,
+    /// By type id
+    pub types: HashMap<String, StorageType>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Variable {
+    pub label: String,
+    /// Relative to the parent for the members of a struct
+    pub slot: U256,
+    /// In bytes, from the right of the slot
+    pub offset: usize,
+    /// Id in the `types`
+    pub ty: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StorageType {
+    /// e.g. `mapping(address => uint256)` or `HashMap[address, uint256]`
+    pub label: String,
+    /// In bytes, the whole slots of the dynamic types
+    pub size: usize,
+    pub kind: TypeKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TypeKind {
+    Value,
+    Mapping {
+        key: String,
+        value: String,
+    },
+    DynamicArray {
+        base: String,
+    },
+    StaticArray {
+        base: String,
+        length: usize,
+    },
+    /// `bytes` and `string`
+    Bytes,
+    Struct {
+        members: Vec<Variable>,
+    },
+}
+
+impl StorageType {
+    fn slots(&self) -> usize {
+        self.size.div_ceil(32).max(1)
+    }
+}
+
+impl From<&solc::StorageVariable> for Variable {
+    fn from(variable: &solc::StorageVariable) -> Self {
+        Variable {
+            label: variable.label.clone(),
+            slot: variable.slot.parse().unwrap_or_default(),
+            offset: variable.offset,
+            ty: variable.type_id.clone(),
+        }
+    }
+}
+
+impl From<&solc::StorageLayout> for StorageLayout {
+    fn from(layout: &solc::StorageLayout) -> Self {
+        let types = layout
+            .types
+            .iter()
+            .map(|(id, ty)| {
+                let kind = match (ty.encoding.as_str(), &ty.key, &ty.value, &ty.base) {
+                    ("mapping", Some(key), Some(value), _) => TypeKind::Mapping {
+                        key: key.clone(),
+                        value: value.clone(),
+                    },
+                    ("dynamic_array", _, _, Some(base)) => {
+                        TypeKind::DynamicArray { base: base.clone() }
+                    }
+                    ("bytes", ..) => TypeKind::Bytes,
+                    // the length is the last one of the label, e.g. `uint256[2][3]`
+                    (_, _, _, Some(base)) => TypeKind::StaticArray {
+                        base: base.clone(),
+                        length: array_length(&ty.label).unwrap_or_default(),
+                    },
+                    _ => match &ty.members {
+                        Some(members) => TypeKind::Struct {
+                            members: members.iter().map(Variable::from).collect(),
+                        },
+                        None => TypeKind::Value,
+                    },
+                };
+                let storage_type = StorageType {
+                    label: ty.label.clone(),
+                    size: ty.number_of_bytes.parse().unwrap_or(32),
+                    kind,
+                };
+                (id.clone(), storage_type)
+            })
+            .collect();
+        StorageLayout {
+            encoding: Encoding::Solidity,
+            variables: layout.storage.iter().map(Variable::from).collect(),
+            types,
+        }
+    }
+}
+
+impl From<&vyperc::Layout> for StorageLayout {
+    fn from(layout: &vyperc::Layout) -> Self {
+        let mut types = HashMap::new();
+        let mut variables: Vec<Variable> = layout
+            .storage_layout
+            .iter()
+            // skips the reentrancy locks
+            .filter(|(_, entry)| !entry.type_name.starts_with("nonreentrant"))
+            .filter_map(|(label, entry)| {
+                vyper_type(&mut types, &entry.type_name);
+                Some(Variable {
+                    label: label.clone(),
+                    slot: U256::from(entry.slot?),
+                    offset: 0,
+                    ty: entry.type_name.clone(),
+                })
+            })
+            .collect();
+        variables.sort_by_key(|variable| variable.slot);
+        StorageLayout {
+            encoding: Encoding::Vyper,
+            variables,
+            types,
+        }
+    }
+}
+
+/// `N` of `T[N]`.
+fn array_length(label: &str) -> Option<usize> {
+    let (_, length) = label.strip_suffix(']')?.rsplit_once('[')?;
+    length.trim().parse().ok()
+}
+
+/// The arguments of `name[..]`, split at the top level commas.
+fn type_args<'a>(ty: &'a str, name: &str) -> Option<Vec<&'a str>> {
+    let args = ty
+        .strip_prefix(name)?
+        .strip_prefix('[')?
+        .strip_suffix(']')?;
+    let mut split = Vec::new();
+    let (mut depth, mut start) = (0, 0);
+    for (i, c) in args.char_indices() {
+        match c {
+            '[' => depth += 1,
+            ']' => depth -= 1,
+            ',' if depth == 0 => {
+                split.push(args[start..i].trim());
+                start = i + 1;
+            }
+            _ => (),
+        }
+    }
+    split.push(args[start..].trim());
+    Some(split)
+}
+
+/// Registers the Vyper type named `ty` and the ones it contains, returning its size.
+fn vyper_type(types: &mut HashMap<String, StorageType>, ty: &str) -> usize {
+    if let Some(known) = types.get(ty) {
+        return known.size;
+    }
+    let (size, kind) = if let Some([key, value]) = type_args(ty, "HashMap").as_deref() {
+        vyper_type(types, key);
+        vyper_type(types, value);
+        let kind = TypeKind::Mapping {
+            key: key.to_string(),
+            value: value.to_string(),
+        };
+        (32, kind)
+    } else if let Some([base, length]) = type_args(ty, "DynArray").as_deref() {
+        let slots = vyper_type(types, base).div_ceil(32);
+        let length: usize = length.parse().unwrap_or_default();
+        // the length comes first
+        let kind = TypeKind::DynamicArray {
+            base: base.to_string(),
+        };
+        (32 * (1 + length * slots), kind)
+    } else if let Some([length]) = type_args(ty, "String")
+        .or_else(|| type_args(ty, "Bytes"))
+        .as_deref()
+    {
+        let length: usize = length.parse().unwrap_or_default();
+        (32 * (1 + length.div_ceil(32)), TypeKind::Bytes)
+    } else if let Some((base, length)) = ty.strip_suffix(']').and_then(|ty| ty.rsplit_once('[')) {
+        let (base, length) = (base.trim(), length.trim().parse().unwrap_or_default());
+        let slots = vyper_type(types, base).div_ceil(32);
+        let kind = TypeKind::StaticArray {
+            base: base.to_string(),
+            length,
+        };
+        (32 * slots * length, kind)
+    } else {
+        (32, TypeKind::Value)
+    };
+    types.insert(
+        ty.to_string(),
+        StorageType {
+            label: ty.to_string(),
+            size,
+            kind,
+        },
+    );
+    size
+}
+
+impl StorageLayout {
+    pub fn variable(&self, label: &str) -> Option<&Variable> {
+        self.variables
+            .iter()
+            .find(|variable| variable.label == label)
+    }
+
+    #[track_caller]
+    fn storage_type(&self, ty: &str) -> &StorageType {
+        self.types
+            .get(ty)
+            .unwrap_or_else(|| panic!("unknown storage type `{ty}`"))
+    }
+
+    /// The variables starting at this slot, e.g. to name the slots of a state diff.
+    pub fn slot_name(&self, slot: &U256) -> Option<String> {
+        let labels: Vec<&str> = self
+            .variables
+            .iter()
+            .filter(|variable| variable.slot == *slot)
+            .map(|variable| variable.label.as_str())
+            .collect();
+        (!labels.is_empty()).then(|| labels.join(", "))
+    }
+}
+
+/// A key of a mapping, the value types being a word and the others their bytes.
+pub trait StorageKey {
+    fn key_bytes(&self) -> Vec<u8>;
+}
+
+impl<T: StorageKey + ?Sized> StorageKey for &T {
+    fn key_bytes(&self) -> Vec<u8> {
+        (**self).key_bytes()
+    }
+}
+
+impl StorageKey for Address {
+    fn key_bytes(&self) -> Vec<u8> {
+        self.into_word().to_vec()
+    }
+}
+
+impl StorageKey for U256 {
+    fn key_bytes(&self) -> Vec<u8> {
+        self.to_be_bytes::<32>().to_vec()
+    }
+}
+
+impl StorageKey for B256 {
+    fn key_bytes(&self) -> Vec<u8> {
+        self.to_vec()
+    }
+}
+
+impl StorageKey for bool {
+    fn key_bytes(&self) -> Vec<u8> {
+        U256::from(*self as u8).key_bytes()
+    }
+}
+
+macro_rules! uint_key {
+    ($($int:ty),*) => {
+        $(
+            impl StorageKey for $int {
+                fn key_bytes(&self) -> Vec<u8> {
+                    U256::from(*self).key_bytes()
+                }
+            }
+        )*
+    };
+}
+
+uint_key!(u8, u16, u32, u64, u128, usize);
+
+impl StorageKey for str {
+    fn key_bytes(&self) -> Vec<u8> {
+        self.as_bytes().to_vec()
+    }
+}
+
+impl StorageKey for String {
+    fn key_bytes(&self) -> Vec<u8> {
+        self.as_bytes().to_vec()
+    }
+}
+
+impl StorageKey for [u8] {
+    fn key_bytes(&self) -> Vec<u8> {
+        self.to_vec()
+    }
+}
+
+impl StorageKey for Bytes {
+    fn key_bytes(&self) -> Vec<u8> {
+        self.to_vec()
+    }
+}
+
+/// Where a value of a contract is stored, to read it or to go down to its elements.
+#[derive(Clone, Copy)]
+pub struct StorageRef<'a> {
+    provider: &'a Provider,
+    address: Address,
+    layout: &'a StorageLayout,
+    slot: U256,
+    offset: usize,
+    ty: &'a StorageType,
+}
+
+impl<'a> StorageRef<'a> {
+    pub fn slot(&self) -> U256 {
+        self.slot
+    }
+
+    /// In bytes, from the right of the slot
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    pub fn storage_type(&self) -> &'a StorageType {
+        self.ty
+    }
+
+    fn load(&self, slot: U256) -> U256 {
+        self.provider.load(self.address, slot)
+    }
+
+    fn at(&self, slot: U256, offset: usize, ty: &str) -> Self {
+        StorageRef {
+            slot,
+            offset,
+            ty: self.layout.storage_type(ty),
+            ..*self
+        }
+    }
+
+    /// The whole slot.
+    pub fn word(&self) -> U256 {
+        self.load(self.slot)
+    }
+
+    /// The value, shifted and masked if it is packed with others.
+    pub fn value(&self) -> U256 {
+        read_packed(self.word(), self.offset, self.ty.size)
+    }
+
+    pub fn address(&self) -> Address {
+        Address::from_word(self.value().into())
+    }
+
+    pub fn bool(&self) -> bool {
+        self.value() != U256::ZERO
+    }
+
+    /// Of a dynamic array or of `bytes` and `string`.
+    #[track_caller]
+    pub fn len(&self) -> usize {
+        let word = self.word();
+        match (&self.ty.kind, self.layout.encoding) {
+            (TypeKind::DynamicArray { .. }, _) | (TypeKind::Bytes, Encoding::Vyper) => {
+                word.saturating_to()
+            }
+            // the short ones are stored with their length times two in the last byte
+            (TypeKind::Bytes, Encoding::Solidity) if !word.bit(0) => (word.byte(0) / 2) as usize,
+            (TypeKind::Bytes, Encoding::Solidity) => (word >> 1).saturating_to(),
+            (TypeKind::StaticArray { length, .. }, _) => *length,
+            _ => panic!("`{}` has no length", self.ty.label),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The content of `bytes` or `string`.
+    #[track_caller]
+    pub fn bytes(&self) -> Bytes {
+        assert!(
+            matches!(self.ty.kind, TypeKind::Bytes),
+            "`{}` is not `bytes` or `string`",
+            self.ty.label
+        );
+        let len = self.len();
+        let start = match self.layout.encoding {
+            Encoding::Solidity if len < 32 => {
+                return self.word().to_be_bytes::<32>()[..len].to_vec().into()
+            }
+            Encoding::Solidity => array_data_slot(self.slot),
+            Encoding::Vyper => self.slot + U256::from(1),
+        };
+        let mut bytes: Vec<u8> = (0..len.div_ceil(32))
+            .flat_map(|i| self.load(start + U256::from(i)).to_be_bytes::<32>())
+            .collect();
+        bytes.truncate(len);
+        bytes.into()
+    }
+
+    #[track_caller]
+    pub fn string(&self) -> String {
+        String::from_utf8_lossy(&self.bytes()).into_owned()
+    }
+
+    /// The element `i` of an array, not checked against the length of the dynamic ones.
+    #[track_caller]
+    pub fn index(&self, i: usize) -> Self {
+        let (base, start) = match (&self.ty.kind, self.layout.encoding) {
+            (TypeKind::StaticArray { base, length }, _) => {
+                assert!(i < *length, "index {i} out of `{}`", self.ty.label);
+                (base, self.slot)
+            }
+            (TypeKind::DynamicArray { base }, Encoding::Solidity) => {
+                (base, array_data_slot(self.slot))
+            }
+            (TypeKind::DynamicArray { base }, Encoding::Vyper) => (base, self.slot + U256::from(1)),
+            _ => panic!("`{}` is not an array", self.ty.label),
+        };
+        let element = self.layout.storage_type(base);
+        // Vyper does not pack the elements smaller than a slot
+        let size = match self.layout.encoding {
+            Encoding::Solidity => element.size,
+            Encoding::Vyper => element.slots() * 32,
+        };
+        let (slot, offset) = element_slot(start, i, size);
+        self.at(slot, offset, base)
+    }
+
+    /// The value of a mapping at this key.
+    #[track_caller]
+    pub fn key(&self, key: impl StorageKey) -> Self {
+        let TypeKind::Mapping {
+            key: key_type,
+            value,
+        } = &self.ty.kind
+        else {
+            panic!("`{}` is not a mapping", self.ty.label);
+        };
+        let slot = match self.layout.encoding {
+            Encoding::Solidity => mapping_slot(self.slot, key),
+            // `keccak256(slot . key)`, the byte strings being hashed first
+            Encoding::Vyper => {
+                let key = match self.layout.storage_type(key_type).kind {
+                    TypeKind::Bytes => keccak256(key.key_bytes()).to_vec(),
+                    _ => key.key_bytes(),
+                };
+                let preimage = [self.slot.to_be_bytes::<32>().as_slice(), &key].concat();
+                U256::from_be_bytes(keccak256(preimage).0)
+            }
+        };
+        self.at(slot, 0, value)
+    }
+
+    /// A member of a struct.
+    #[track_caller]
+    pub fn member(&self, name: &str) -> Self {
+        let TypeKind::Struct { members } = &self.ty.kind else {
+            panic!("`{}` is not a struct", self.ty.label);
+        };
+        let member = members
+            .iter()
+            .find(|member| member.label == name)
+            .unwrap_or_else(|| panic!("`{}` has no member `{name}`", self.ty.label));
+        self.at(self.slot + member.slot, member.offset, &member.ty)
+    }
+}
+
+/// The variables of a contract deployed through its bindings.
+#[derive(Clone, Copy)]
+pub struct ContractStorage<'a> {
+    provider: &'a Provider,
+    address: Address,
+    layout: &'a StorageLayout,
+}
+
+impl<'a> ContractStorage<'a> {
+    /// `None` if the contract has no registered layout.
+    pub fn new(provider: &'a Provider, address: Address) -> Option<Self> {
+        let layout = provider.contract_info(&address)?.storage_layout.as_ref()?;
+        Some(ContractStorage {
+            provider,
+            address,
+            layout,
+        })
+    }
+
+    pub fn layout(&self) -> &'a StorageLayout {
+        self.layout
+    }
+
+    pub fn variable(&self, label: &str) -> Option<StorageRef<'a>> {
+        let variable = self.layout.variable(label)?;
+        Some(StorageRef {
+            provider: self.provider,
+            address: self.address,
+            layout: self.layout,
+            slot: variable.slot,
+            offset: variable.offset,
+            ty: self.layout.storage_type(&variable.ty),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ContractStorage, StorageLayout, TypeKind};
+    use crate::{
+        common::contract::ContractInfo,
+        compilers::{solidity::solc::SolcOut, vyper::vyperc::VypercOut},
+        provider::{db::Cheats, Provider},
+    };
+    use revm::primitives::{keccak256, Address, U256};
+
+    fn word(value: impl Into<U256>) -> [u8; 32] {
+        value.into().to_be_bytes()
+    }
+
+    fn hash_slot(preimage: &[&[u8]]) -> U256 {
+        U256::from_be_bytes(keccak256(preimage.concat()).0)
+    }
+
+    fn deployed(layout: StorageLayout) -> (Provider, Address) {
+        let mut provider = Provider::default();
+        let address = Address::repeat_byte(0x5e);
+        provider.register_contract(
+            address,
+            ContractInfo {
+                name: "Storage".to_string(),
+                storage_layout: Some(layout),
+                ..Default::default()
+            },
+        );
+        (provider, address)
+    }
+
+    #[test]
+    fn solidity_storage() {
+        let out: SolcOut =
+            serde_json::from_str(include_str!("../test-data/solc/output/storage.json")).unwrap();
+        let contract = &out.contracts.unwrap()["source_code.sol"]["Storage"];
+        let layout = StorageLayout::from(contract.storage_layout.as_ref().unwrap());
+        assert_eq!(
+            layout.slot_name(&U256::from(1)).as_deref(),
+            Some("owner, paused, decimals")
+        );
+        let (mut provider, address) = deployed(layout);
+        let alice = Address::repeat_byte(0xa1);
+        let bob = Address::repeat_byte(0xb0);
+
+        provider.store(address, U256::from(0), U256::from(42));
+        // owner, then paused and decimals packed after it
+        let packed = U256::from_be_bytes(alice.into_word().0)
+            | (U256::from(1) << 160)
+            | (U256::from(18) << 168);
+        provider.store(address, U256::from(1), packed);
+        let balance = hash_slot(&[alice.into_word().as_slice(), &word(U256::from(2))]);
+        provider.store(address, balance, U256::from(100));
+        let inner = hash_slot(&[alice.into_word().as_slice(), &word(U256::from(3))]);
+        let allowance = hash_slot(&[bob.into_word().as_slice(), &word(inner)]);
+        provider.store(address, allowance, U256::from(7));
+        provider.store(address, U256::from(4), U256::from(2));
+        let values = hash_slot(&[&word(U256::from(4))]);
+        provider.store(address, values + U256::from(1), U256::from(9));
+        provider.store(address, U256::from(5), U256::from(3) << 128);
+        let mut name = [0u8; 32];
+        name[..6].copy_from_slice(b"rustry");
+        name[31] = 12;
+        provider.store(address, U256::from(6), U256::from_be_bytes(name));
+        let data = [0xda; 40];
+        provider.store(address, U256::from(7), U256::from(81));
+        let data_slot = hash_slot(&[&word(U256::from(7))]);
+        provider.store(address, data_slot, U256::from_be_bytes([0xda; 32]));
+        let mut tail = [0u8; 32];
+        tail[..8].copy_from_slice(&data[32..]);
+        provider.store(
+            address,
+            data_slot + U256::from(1),
+            U256::from_be_bytes(tail),
+        );
+        provider.store(address, U256::from(8), U256::from(5) << 128);
+        provider.store(
+            address,
+            U256::from(9),
+            U256::from_be_bytes(bob.into_word().0),
+        );
+        let position = hash_slot(&[&word(U256::from(3)), &word(U256::from(10))]);
+        provider.store(address, position, U256::from(1000));
+        let id = hash_slot(&[b"a", &word(U256::from(11))]);
+        provider.store(address, id, U256::from(1));
+
+        let storage = ContractStorage::new(&provider, address).unwrap();
+        let variable = |label| storage.variable(label).unwrap();
+        assert_eq!(variable("number").value(), U256::from(42));
+        assert_eq!(variable("owner").address(), alice);
+        assert!(variable("paused").bool());
+        assert_eq!(variable("decimals").value(), U256::from(18));
+        assert_eq!(variable("balances").key(alice).value(), U256::from(100));
+        assert_eq!(variable("balances").key(bob).value(), U256::ZERO);
+        assert_eq!(
+            variable("allowance").key(alice).key(bob).value(),
+            U256::from(7)
+        );
+        assert_eq!(variable("values").len(), 2);
+        assert_eq!(variable("values").index(1).value(), U256::from(9));
+        assert_eq!(variable("limits").index(2).value(), U256::from(3));
+        assert_eq!(variable("limits").index(2).offset(), 16);
+        assert_eq!(variable("name").string(), "rustry");
+        assert_eq!(variable("data").bytes().as_ref(), data.as_slice());
+        assert_eq!(variable("position").member("start").value(), U256::from(5));
+        assert_eq!(variable("position").member("owner").address(), bob);
+        assert_eq!(
+            variable("positions").key(3u64).member("amount").value(),
+            U256::from(1000)
+        );
+        assert_eq!(variable("ids").key("a").value(), U256::from(1));
+        assert!(storage.variable("missing").is_none());
+    }
+
+    #[test]
+    fn vyper_storage() {
+        let out: VypercOut =
+            serde_json::from_str(include_str!("../test-data/vyperc/output/storage.json")).unwrap();
+        let contract = &out.contracts.unwrap()["source_code.vy"]["source_code"];
+        let layout = StorageLayout::from(contract.layout.as_ref().unwrap());
+        let values = &layout.types["DynArray[uint256, 10]"];
+        assert_eq!(values.size, 32 * 11);
+        assert!(matches!(&values.kind, TypeKind::DynamicArray { base } if base == "uint256"));
+        assert!(matches!(
+            &layout.types["HashMap[address, HashMap[address, uint256]]"].kind,
+            TypeKind::Mapping { key, value } if key == "address" && value == "HashMap[address, uint256]"
+        ));
+        let (mut provider, address) = deployed(layout);
+        let alice = Address::repeat_byte(0xa1);
+        let bob = Address::repeat_byte(0xb0);
+
+        let balance = hash_slot(&[&word(U256::from(2)), alice.into_word().as_slice()]);
+        provider.store(address, balance, U256::from(100));
+        let inner = hash_slot(&[&word(U256::from(3)), alice.into_word().as_slice()]);
+        let allowance = hash_slot(&[&word(inner), bob.into_word().as_slice()]);
+        provider.store(address, allowance, U256::from(7));
+        provider.store(address, U256::from(4), U256::from(2));
+        provider.store(address, U256::from(6), U256::from(9));
+        provider.store(address, U256::from(17), U256::from(3));
+        let mut name = [0u8; 32];
+        name[..6].copy_from_slice(b"rustry");
+        provider.store(address, U256::from(18), U256::from(6));
+        provider.store(address, U256::from(19), U256::from_be_bytes(name));
+        let id = hash_slot(&[&word(U256::from(21)), keccak256(b"a").as_slice()]);
+        provider.store(address, id, U256::from(1));
+
+        let storage = ContractStorage::new(&provider, address).unwrap();
+        let variable = |label| storage.variable(label).unwrap();
+        assert_eq!(variable("balances").key(alice).value(), U256::from(100));
+        assert_eq!(
+            variable("allowance").key(alice).key(bob).value(),
+            U256::from(7)
+        );
+        assert_eq!(variable("values").len(), 2);
+        assert_eq!(variable("values").index(1).value(), U256::from(9));
+        assert_eq!(variable("limits").index(2).value(), U256::from(3));
+        assert_eq!(variable("name").string(), "rustry");
+        assert_eq!(variable("ids").key("a").value(), U256::from(1));
+    }
+}
@@ -106,8 +106,10 @@ impl Compiler {
                                 String::from("*"),
                                 vec![
                                     OutputOption::EvmBytecode,
+                                    OutputOption::EvmDeployedBytecode,
                                     OutputOption::Metadata,
                                     OutputOption::Abi,
+                                    OutputOption::StorageLayout,
                                 ],
                             )]),
                         )]),
@@ -152,7 +154,12 @@ impl Compiler {
                             String::from("*"),
                             HashMap::from([(
                                 String::from("*"),
-                                vec![vyperc::OutputOption::EvmBytecode, vyperc::OutputOption::Abi],
+                                vec![
+                                    vyperc::OutputOption::EvmBytecode,
+                                    vyperc::OutputOption::EvmDeployedBytecode,
+                                    vyperc::OutputOption::Abi,
+                                    vyperc::OutputOption::Layout,
+                                ],
                             )]),
                         )]),
                     }))
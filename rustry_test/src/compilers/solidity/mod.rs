@@ -1,2 +1,3 @@
 pub mod solc;
+pub mod source_map;
 pub mod types;
@@ -25,8 +25,12 @@ pub enum OutputOption {
     Metadata,
     #[serde(rename = "evm.bytecode")]
     EvmBytecode,
+    #[serde(rename = "evm.deployedBytecode")]
+    EvmDeployedBytecode,
     #[serde(rename = "abi")]
     Abi,
+    #[serde(rename = "storageLayout")]
+    StorageLayout,
 }
 
 #[derive(Clone, Serialize)]
@@ -103,8 +107,9 @@ pub struct OutError<S, E> {
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct DebugData {
+    /// Only for the internal functions
     #[serde(rename = "entryPoint")]
-    pub entry_point: i32,
+    pub entry_point: Option<i32>,
     pub id: Option<i32>,
     #[serde(rename = "parameterSlots")]
     pub parameter_slots: Option<i32>,
@@ -113,7 +118,7 @@ pub struct DebugData {
 }
 
 // TODO
-type Ast = HashMap<String, String>;
+type Ast = serde_json::Value;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct GeneratedSource {
@@ -142,8 +147,15 @@ pub struct BytecodeData {
 pub struct DeployedBytecodeData {
     #[serde(flatten)]
     pub bytecode_data: BytecodeData,
-    #[serde(rename = "immutableReferences")]
-    pub immutable_references: HashMap<String, String>, // TODO
+    /// By AST id of the immutable, where its value is in the code
+    #[serde(rename = "immutableReferences", default)]
+    pub immutable_references: HashMap<String, Vec<ImmutableReference>>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ImmutableReference {
+    pub start: usize,
+    pub length: usize,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -160,8 +172,61 @@ pub struct EvmOutput {
     pub gas_estimates: Option<HashMap<String, HashMap<String, String>>>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct StorageLayout {}
+// https://docs.soliditylang.org/en/latest/internals/layout_in_storage.html#json-output
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StorageLayout {
+    pub storage: Vec<StorageVariable>,
+    /// By type id, e.g. `t_mapping(t_address,t_uint256)`, `null` without storage
+    #[serde(default, deserialize_with = "null_as_default")]
+    pub types: HashMap<String, StorageType>,
+}
+
+fn null_as_default<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: Default + Deserialize<'de>,
+{
+    Ok(Option::deserialize(deserializer)?.unwrap_or_default())
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StorageVariable {
+    #[serde(rename = "astId")]
+    pub ast_id: i64,
+    /// e.g. `source_code.sol:Token`
+    pub contract: String,
+    pub label: String,
+    /// In bytes, from the right of the slot
+    pub offset: usize,
+    /// A decimal number
+    pub slot: String,
+    /// Id in the `types`
+    #[serde(rename = "type")]
+    pub type_id: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StorageType {
+    /// `inplace`, `mapping`, `dynamic_array` or `bytes`
+    pub encoding: String,
+    /// e.g. `mapping(address => uint256)`
+    pub label: String,
+    /// A decimal number
+    #[serde(rename = "numberOfBytes")]
+    pub number_of_bytes: String,
+    /// Only for the mappings
+    #[serde(skip_serializing_if = "opt_none")]
+    pub key: Option<String>,
+    /// Only for the mappings
+    #[serde(skip_serializing_if = "opt_none")]
+    pub value: Option<String>,
+    /// Only for the arrays
+    #[serde(skip_serializing_if = "opt_none")]
+    pub base: Option<String>,
+    /// Only for the structs
+    #[serde(skip_serializing_if = "opt_none")]
+    pub members: Option<Vec<StorageVariable>>,
+}
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct TypeType {
@@ -170,6 +235,26 @@ pub struct TypeType {
     pub name: String,
     #[serde(rename = "type")]
     pub type_type: String,
+    /// Only for event parameters
+    #[serde(skip_serializing_if = "opt_none")]
+    pub indexed: Option<bool>,
+    /// The fields of a `tuple`
+    #[serde(skip_serializing_if = "opt_none")]
+    pub components: Option<Vec<TypeType>>,
+}
+
+impl TypeType {
+    /// The type as used in signatures, with the tuples unpacked.
+    /// e.g. `tuple[]` with 2 `uint256` components is `(uint256,uint256)[]`
+    pub fn canonical_type(&self) -> String {
+        match (self.type_type.strip_prefix("tuple"), &self.components) {
+            (Some(suffix), Some(components)) => {
+                let inner: Vec<String> = components.iter().map(|c| c.canonical_type()).collect();
+                format!("({}){suffix}", inner.join(","))
+            }
+            _ => self.type_type.clone(),
+        }
+    }
 }
 
 // TODO move elsewhere
@@ -179,24 +264,27 @@ pub trait EntryUtils {
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct AbiEntry {
+    #[serde(default)]
     pub inputs: Vec<TypeType>,
+    /// Empty for the constructor, fallback and receive
+    #[serde(default)]
     pub name: String,
+    #[serde(default)]
     pub outputs: Vec<TypeType>,
-    #[serde(rename = "stateMutability")]
+    /// Empty for the events and errors
+    #[serde(rename = "stateMutability", default)]
     pub state_mutability: String,
     #[serde(rename = "type")]
     pub entry_type: String,
+    /// Only for events
+    #[serde(skip_serializing_if = "opt_none")]
+    pub anonymous: Option<bool>,
 }
 
 impl EntryUtils for AbiEntry {
     fn signature(&self) -> String {
-        let mut inner_types: String = self
-            .inputs
-            .iter()
-            .map(|tt| format!("{},", tt.type_type.clone()))
-            .collect();
-        inner_types.pop();
-        format!("{0}({inner_types})", self.name)
+        let inner_types: Vec<String> = self.inputs.iter().map(|tt| tt.canonical_type()).collect();
+        format!("{0}({1})", self.name, inner_types.join(","))
     }
 }
 
@@ -0,0 +1,291 @@
+//! Mapping of the program counters to the Solidity sources, with the source maps of solc.
+//! https://docs.soliditylang.org/en/latest/internals/source_mappings.html
+
+use std::{collections::HashMap, fmt};
+
+/// Where the content of a source file is written, e.g. the Rust file of a `solidity!` literal.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Origin {
+    pub path: String,
+    /// 1-based line of the first line of the content
+    pub line: usize,
+    /// 1-based column of the start of the content
+    pub column: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceFile {
+    /// Index of the file in the source maps
+    pub id: i32,
+    pub name: String,
+    pub content: String,
+    pub origin: Option<Origin>,
+}
+
+/// An internal function of the deployed code, from the function debug data of solc.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InternalFunction {
+    pub name: String,
+    /// Program counter of its `JUMPDEST`
+    pub entry_point: usize,
+    pub parameter_slots: usize,
+    pub return_slots: usize,
+}
+
+/// The source map of the deployed code of a contract, and the files it refers to.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ContractSources {
+    /// Compressed, as output by solc
+    pub source_map: String,
+    /// The compiled files and the ones generated by solc
+    pub files: Vec<SourceFile>,
+    pub functions: Vec<InternalFunction>,
+}
+
+/// The name of a function in the debug data, without the `@` and the id of the Solidity ones,
+/// e.g. `_transfer` for `@_transfer_123`.
+pub fn function_name(debug_name: &str) -> &str {
+    match debug_name.strip_prefix('@') {
+        Some(name) => name
+            .rsplit_once('_')
+            .filter(|(_, id)| id.chars().all(|c| c.is_ascii_digit()))
+            .map_or(name, |(name, _)| name),
+        None => debug_name,
+    }
+}
+
+/// The source range of an instruction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SourceElement {
+    /// Byte offset in the file
+    pub offset: usize,
+    pub length: usize,
+    /// -1 if the instruction is not mapped to a file
+    pub file: i32,
+}
+
+/// One element per instruction, the empty fields being the ones of the previous element.
+pub fn parse_source_map(source_map: &str) -> Vec<SourceElement> {
+    let mut element = SourceElement {
+        offset: 0,
+        length: 0,
+        file: -1,
+    };
+    source_map
+        .split(';')
+        .map(|entry| {
+            let mut fields = entry.split(':');
+            if let Some(offset) = fields.next().and_then(|field| field.parse().ok()) {
+                element.offset = offset;
+            }
+            if let Some(length) = fields.next().and_then(|field| field.parse().ok()) {
+                element.length = length;
+            }
+            if let Some(file) = fields.next().and_then(|field| field.parse().ok()) {
+                element.file = file;
+            }
+            element
+        })
+        .collect()
+}
+
+/// Program counter and opcode of each instruction of the code.
+pub fn instructions(code: &[u8]) -> Vec<(usize, u8)> {
+    let mut instructions = Vec::new();
+    let mut pc = 0;
+    while let Some(&op) = code.get(pc) {
+        instructions.push((pc, op));
+        // PUSH1 to PUSH32
+        if (0x60..=0x7f).contains(&op) {
+            pc += (op - 0x5f) as usize;
+        }
+        pc += 1;
+    }
+    instructions
+}
+
+/// Index of the instruction at `pc`, `None` if `pc` is in the data of a push.
+pub fn instruction_index(code: &[u8], pc: usize) -> Option<usize> {
+    instructions(code)
+        .iter()
+        .position(|(instruction_pc, _)| *instruction_pc == pc)
+}
+
+/// A position in a source file, with the line it is on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CodeLocation {
+    /// The origin of the file if any, or its name
+    pub file: String,
+    /// 1-based
+    pub line: usize,
+    /// 1-based, in bytes
+    pub column: usize,
+    /// Text of the line
+    pub text: String,
+    /// Offset and length of the source range within `text`
+    pub highlight: (usize, usize),
+}
+
+impl fmt::Display for CodeLocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.file, self.line, self.column)
+    }
+}
+
+impl CodeLocation {
+    /// The line and the range underlined, as by rustc.
+    pub fn snippet(&self) -> String {
+        let number = self.line.to_string();
+        let margin = " ".repeat(number.len());
+        let (start, length) = self.highlight;
+        format!(
+            "{margin} |\n{number} | {}\n{margin} | {}{}",
+            self.text,
+            " ".repeat(start),
+            "^".repeat(length.max(1))
+        )
+    }
+}
+
+impl SourceFile {
+    /// Generated by the compiler, e.g. `#utility.yul`.
+    pub fn is_generated(&self) -> bool {
+        self.name.starts_with('#')
+    }
+
+    /// The file, line and column of a position in the content, moved to the origin if any.
+    pub fn translate(&self, line: usize, column: usize) -> (String, usize, usize) {
+        match &self.origin {
+            Some(origin) => {
+                let column = if line == 1 {
+                    column + origin.column - 1
+                } else {
+                    column
+                };
+                (origin.path.clone(), line + origin.line - 1, column)
+            }
+            None => (self.name.clone(), line, column),
+        }
+    }
+
+    fn locate(&self, offset: usize, length: usize) -> Option<CodeLocation> {
+        let before = self.content.get(..offset)?;
+        let line_start = before.rfind('\n').map_or(0, |newline| newline + 1);
+        let line_end = self.content[offset..]
+            .find('\n')
+            .map_or(self.content.len(), |newline| offset + newline);
+        let (file, line, column) =
+            self.translate(before.matches('\n').count() + 1, offset - line_start + 1);
+        Some(CodeLocation {
+            file,
+            line,
+            column,
+            text: self.content[line_start..line_end].to_string(),
+            highlight: (offset - line_start, length.min(line_end - offset)),
+        })
+    }
+}
+
+impl ContractSources {
+    /// Where the instruction at `pc` of the deployed `code` comes from.
+    pub fn locate(&self, code: &[u8], pc: usize) -> Option<CodeLocation> {
+        let index = instruction_index(code, pc)?;
+        self.locate_element(parse_source_map(&self.source_map).get(index)?)
+    }
+
+    /// Where each instruction of the deployed `code` comes from, by program counter.
+    pub fn locate_all(&self, code: &[u8]) -> HashMap<usize, CodeLocation> {
+        instructions(code)
+            .iter()
+            .zip(parse_source_map(&self.source_map))
+            .filter_map(|((pc, _), element)| Some((*pc, self.locate_element(&element)?)))
+            .collect()
+    }
+
+    fn locate_element(&self, element: &SourceElement) -> Option<CodeLocation> {
+        self.files
+            .iter()
+            .find(|file| file.id == element.file)?
+            .locate(element.offset, element.length)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        function_name, instruction_index, parse_source_map, ContractSources, Origin, SourceElement,
+        SourceFile,
+    };
+
+    #[test]
+    fn function_names() {
+        assert_eq!(function_name("@_transfer_123"), "_transfer");
+        assert_eq!(function_name("@fun_a_1"), "fun_a");
+        assert_eq!(
+            function_name("abi_decode_tuple_t_uint256"),
+            "abi_decode_tuple_t_uint256"
+        );
+    }
+
+    #[test]
+    fn parse() {
+        let elements = parse_source_map("1:2:0;:9;;4::-1:i");
+        let element = |offset, length, file| SourceElement {
+            offset,
+            length,
+            file,
+        };
+        assert_eq!(
+            elements,
+            vec![
+                element(1, 2, 0),
+                element(1, 9, 0),
+                element(1, 9, 0),
+                element(4, 9, -1),
+            ]
+        );
+    }
+
+    #[test]
+    fn locate() {
+        // PUSH1 0x00 PUSH2 0x0000 DUP1 REVERT
+        let code = [0x60, 0x00, 0x61, 0x00, 0x00, 0x80, 0xfd];
+        assert_eq!(instruction_index(&code, 5), Some(2));
+        assert_eq!(instruction_index(&code, 3), None);
+
+        let content = "contract A {\n    function f() public { revert(); }\n}";
+        let offset = content.find("revert").unwrap();
+        let mut sources = ContractSources {
+            source_map: format!("0:52:0;;;{offset}:8"),
+            files: vec![SourceFile {
+                id: 0,
+                name: "A.sol".to_string(),
+                content: content.to_string(),
+                origin: None,
+            }],
+            functions: Vec::new(),
+        };
+        let location = sources.locate(&code, 6).unwrap();
+        assert_eq!(location.to_string(), "A.sol:2:27");
+        assert_eq!(
+            location.snippet(),
+            "  |\n2 |     function f() public { revert(); }\n  |                           ^^^^^^^^"
+        );
+        // the first line spans the whole contract
+        assert_eq!(sources.locate(&code, 0).unwrap().highlight, (0, 12));
+
+        sources.files[0].origin = Some(Origin {
+            path: "tests/a.rs".to_string(),
+            line: 10,
+            column: 5,
+        });
+        assert_eq!(
+            sources.locate(&code, 6).unwrap().to_string(),
+            "tests/a.rs:11:27"
+        );
+        assert_eq!(
+            sources.locate(&code, 0).unwrap().to_string(),
+            "tests/a.rs:10:5"
+        );
+    }
+}
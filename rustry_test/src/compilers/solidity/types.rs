@@ -10,6 +10,30 @@ pub fn internal_to_type(_type: &str) -> String {
         // String::from("U256")
         String::from("u128")
     } else {
-        todo!("{_type} missing !");
+        abi_to_rust_type(_type)
+    }
+}
+
+/// Path of the Rust type used in the bindings for a canonical ABI type.
+pub fn abi_to_rust_type(_type: &str) -> String {
+    if let Some(elem) = _type.strip_suffix(']') {
+        let elem = &elem[..elem.rfind('[').unwrap()];
+        return format!("Vec<{}>", abi_to_rust_type(elem));
+    }
+    if _type.starts_with('(') {
+        return String::from("rustry_test::utils::codec::AbiValue");
+    }
+    match _type {
+        "address" => String::from("revm::primitives::Address"),
+        "bool" => String::from("bool"),
+        "string" => String::from("String"),
+        "bytes" => String::from("revm::primitives::Bytes"),
+        "function" => String::from("revm::primitives::FixedBytes<24>"),
+        _ if _type.starts_with("uint") => String::from("revm::primitives::U256"),
+        _ if _type.starts_with("int") => String::from("revm::primitives::alloy_primitives::I256"),
+        _ if _type.starts_with("bytes") => {
+            format!("revm::primitives::FixedBytes<{}>", &_type["bytes".len()..])
+        }
+        _ => todo!("{_type} missing !"),
     }
 }
@@ -1,4 +1,13 @@
-use super::{huff::huffc::HuffcOut, solidity::solc::SolcOut, vyper::vyperc::VypercOut};
+use super::{
+    huff::huffc::HuffcOut,
+    solidity::solc::{EntryUtils, SolcOut},
+    vyper::vyperc::VypercOut,
+};
+use crate::utils::{
+    abi::get_event_topic,
+    codec::{decode_log, encode, AbiValue},
+};
+use revm::primitives::{Address, Log, U256};
 use serde::Deserialize;
 use std::{fs, path::PathBuf};
 
@@ -17,6 +26,161 @@ fn deserialize_vyper_output() {
     test_compiler_output::<VypercOut>("vyperc");
 }
 
+/// The events of an ABI: the signature, if anonymous, then the canonical types and if indexed.
+type Events = Vec<(String, bool, Vec<(String, bool)>)>;
+
+fn params(types: &[(&str, bool)]) -> Vec<(String, bool)> {
+    types
+        .iter()
+        .map(|(ty, indexed)| (ty.to_string(), *indexed))
+        .collect()
+}
+
+fn decode(log: &Log, signature: Option<&str>, params: &[(String, bool)]) -> Option<Vec<AbiValue>> {
+    let params: Vec<(&str, bool)> = params
+        .iter()
+        .map(|(ty, indexed)| (ty.as_str(), *indexed))
+        .collect();
+    decode_log(log, signature, &params)
+}
+
+#[test]
+fn solc_events() {
+    let out: SolcOut =
+        serde_json::from_str(include_str!("../test-data/solc/output/events.json")).unwrap();
+    let contracts = out.contracts.unwrap();
+    let abi = contracts["source_code.sol"]["Token"].abi.as_ref().unwrap();
+    let events: Events = abi
+        .iter()
+        .filter(|entry| entry.entry_type == "event")
+        .map(|entry| {
+            let params = entry
+                .inputs
+                .iter()
+                .map(|input| (input.canonical_type(), input.indexed.unwrap_or_default()))
+                .collect();
+            (
+                entry.signature(),
+                entry.anonymous.unwrap_or_default(),
+                params,
+            )
+        })
+        .collect();
+    assert_eq!(
+        events,
+        vec![
+            (
+                "Transfer(address,address,uint256)".to_string(),
+                false,
+                params(&[("address", true), ("address", true), ("uint256", false)])
+            ),
+            (
+                "Memo(uint256,string)".to_string(),
+                true,
+                params(&[("uint256", true), ("string", false)])
+            ),
+            (
+                "Filled((address,uint256[]))".to_string(),
+                false,
+                params(&[("(address,uint256[])", false)])
+            ),
+        ]
+    );
+
+    let order = AbiValue::Tuple(vec![
+        AbiValue::Address(Address::repeat_byte(0xa1)),
+        AbiValue::Array(vec![
+            AbiValue::Uint(U256::from(1)),
+            AbiValue::Uint(U256::from(2)),
+        ]),
+    ]);
+    let log = Log {
+        address: Address::ZERO,
+        topics: vec![get_event_topic("Filled((address,uint256[]))")],
+        data: encode(&[order.clone()]).into(),
+    };
+    assert_eq!(
+        decode(&log, Some(&events[2].0), &events[2].2),
+        Some(vec![order])
+    );
+
+    // anonymous, without the topic of the signature
+    let log = Log {
+        address: Address::ZERO,
+        topics: vec![U256::from(7).into()],
+        data: encode(&[AbiValue::String("gm".to_string())]).into(),
+    };
+    assert_eq!(
+        decode(&log, None, &events[1].2),
+        Some(vec![
+            AbiValue::Uint(U256::from(7)),
+            AbiValue::String("gm".to_string())
+        ])
+    );
+}
+
+#[test]
+fn vyper_events() {
+    let out: VypercOut =
+        serde_json::from_str(include_str!("../test-data/vyperc/output/events.json")).unwrap();
+    let contracts = out.contracts.unwrap();
+    let abi = contracts["source_code.vy"]["source_code"]
+        .abi
+        .as_ref()
+        .unwrap();
+    let events: Events = abi
+        .iter()
+        .filter(|entry| entry.entry_type == "event")
+        .map(|entry| {
+            let params = entry
+                .inputs
+                .iter()
+                .flatten()
+                .map(|input| (input.canonical_type(), input.indexed.unwrap_or_default()))
+                .collect();
+            (
+                entry.name.clone(),
+                entry.anonymous.unwrap_or_default(),
+                params,
+            )
+        })
+        .collect();
+    assert_eq!(
+        events,
+        vec![
+            (
+                "Transfer".to_string(),
+                false,
+                params(&[("address", true), ("address", true), ("uint256", false)])
+            ),
+            ("Message".to_string(), false, params(&[("string", false)])),
+        ]
+    );
+
+    let (sender, receiver) = (Address::repeat_byte(0xa1), Address::repeat_byte(0xb0));
+    let log = Log {
+        address: Address::ZERO,
+        topics: vec![
+            get_event_topic("Transfer(address,address,uint256)"),
+            sender.into_word(),
+            receiver.into_word(),
+        ],
+        data: encode(&[AbiValue::Uint(U256::from(5))]).into(),
+    };
+    assert_eq!(
+        decode(
+            &log,
+            Some("Transfer(address,address,uint256)"),
+            &events[0].2
+        ),
+        Some(vec![
+            AbiValue::Address(sender),
+            AbiValue::Address(receiver),
+            AbiValue::Uint(U256::from(5))
+        ])
+    );
+}
+
 fn test_compiler_output<T: for<'a> Deserialize<'a>>(compiler: &str) {
     let mut d = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
     d.push(format!("src/test-data/{compiler}/output/"));
@@ -1,6 +1,6 @@
 use crate::compilers::{
     builder::{CompilerError, CompilerOutput, RunCompiler},
-    solidity::solc::{JsonError, OutError, Source, StorageLayout},
+    solidity::solc::{JsonError, OutError, Source},
 };
 use derive_builder::Builder;
 use serde::{Deserialize, Serialize};
@@ -23,8 +23,12 @@ pub struct Settings {
 pub enum OutputOption {
     #[serde(rename = "evm.bytecode")]
     EvmBytecode,
+    #[serde(rename = "evm.deployedBytecode")]
+    EvmDeployedBytecode,
     #[serde(rename = "abi")]
     Abi,
+    #[serde(rename = "layout")]
+    Layout,
 }
 
 // https://docs.vyperlang.org/en/stable/compiling-a-contract.html?highlight=--standard-json#input-json-description
@@ -61,16 +65,34 @@ fn constructor() -> String {
 pub struct BytecodeData {
     pub object: String,
     pub opcodes: String,
+    /// In the format of solc
+    #[serde(rename = "sourceMap")]
+    pub source_map: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct DeployedBytecodeData {
     #[serde(flatten)]
     pub bytecode_data: BytecodeData,
-    #[serde(rename = "immutableReferences")]
+    #[serde(rename = "immutableReferences", default)]
     pub immutable_references: HashMap<String, String>, // TODO
 }
 
+#[derive(Debug, Deserialize)]
+pub struct Layout {
+    /// By variable name
+    #[serde(default)]
+    pub storage_layout: HashMap<String, LayoutEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LayoutEntry {
+    /// e.g. `HashMap[address, uint256]`
+    #[serde(rename = "type")]
+    pub type_name: String,
+    pub slot: Option<u64>,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct EvmOutput {
     pub assembly: Option<String>,
@@ -95,6 +117,25 @@ pub struct AbiEntry {
     pub outputs: Option<Vec<AbiEntry>>,
     #[serde(rename = "stateMutability")]
     pub state_mutability: Option<String>,
+    /// Only for event parameters
+    pub indexed: Option<bool>,
+    /// Only for events
+    pub anonymous: Option<bool>,
+    /// The fields of a `tuple`
+    pub components: Option<Vec<AbiEntry>>,
+}
+
+impl AbiEntry {
+    /// The type of a parameter as used in signatures, with the tuples unpacked.
+    pub fn canonical_type(&self) -> String {
+        match (self.entry_type.strip_prefix("tuple"), &self.components) {
+            (Some(suffix), Some(components)) => {
+                let inner: Vec<String> = components.iter().map(|c| c.canonical_type()).collect();
+                format!("({}){suffix}", inner.join(","))
+            }
+            _ => self.entry_type.clone(),
+        }
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -111,8 +152,7 @@ pub struct VycContract {
     pub ir_optimized: Option<String>,
     #[serde(rename = "irOptimizedAst")]
     pub ir_optimized_ast: Option<HashMap<String, String>>,
-    #[serde(rename = "storageLayout")]
-    pub storage_layout: Option<StorageLayout>,
+    pub layout: Option<Layout>,
     pub evm: Option<EvmOutput>,
 }
 
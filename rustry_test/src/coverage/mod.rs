@@ -0,0 +1,116 @@
+//! Lines and branches of the sources executed by the tests, recorded when `RUSTRY_COVERAGE` is set.
+//! The instructions are mapped to the sources with the source maps of the compilers, and the
+//! coverage of the tests of the binary is written to `target/rustry/coverage/<binary>`.
+
+mod report;
+
+pub use report::{BranchCoverage, CoverageReport, FileCoverage};
+
+use crate::{
+    compilers::solidity::source_map::ContractSources,
+    utils::{binary_name, rustry_dir},
+};
+use revm::{
+    interpreter::{opcode, Interpreter},
+    primitives::{Bytes, B256, U256},
+};
+use std::{
+    collections::{BTreeMap, HashMap},
+    env, fs, io,
+    path::PathBuf,
+    sync::Mutex,
+};
+
+/// The code executed by the providers dropped so far, by code hash.
+static COVERAGE: Mutex<BTreeMap<B256, CoveredCode>> = Mutex::new(BTreeMap::new());
+
+/// If `RUSTRY_COVERAGE` is set to anything but 0.
+pub fn is_enabled() -> bool {
+    env::var("RUSTRY_COVERAGE").is_ok_and(|value| !value.is_empty() && value != "0")
+}
+
+/// How many times each instruction of a code was executed.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CodeHits {
+    pub instructions: HashMap<usize, u64>,
+    /// By program counter of the `JUMPI`, the times it jumped and the times it did not
+    pub jumps: HashMap<usize, [u64; 2]>,
+}
+
+impl CodeHits {
+    fn merge(&mut self, other: &CodeHits) {
+        for (pc, count) in &other.instructions {
+            *self.instructions.entry(*pc).or_default() += count;
+        }
+        for (pc, [jumped, not_jumped]) in &other.jumps {
+            let counts = self.jumps.entry(*pc).or_default();
+            counts[0] += jumped;
+            counts[1] += not_jumped;
+        }
+    }
+}
+
+/// Counts the executed instructions through the inspector hooks.
+#[derive(Debug, Default)]
+pub struct HitRecorder {
+    pub hits: HashMap<B256, CodeHits>,
+}
+
+impl HitRecorder {
+    pub fn step(&mut self, interp: &Interpreter) {
+        let hits = self.hits.entry(interp.contract.hash).or_default();
+        let pc = interp.program_counter();
+        *hits.instructions.entry(pc).or_default() += 1;
+        if interp.current_opcode() == opcode::JUMPI {
+            if let Ok(condition) = interp.stack.peek(1) {
+                hits.jumps.entry(pc).or_default()[usize::from(condition == U256::ZERO)] += 1;
+            }
+        }
+    }
+}
+
+/// A deployed code with a source map, and its executions.
+#[derive(Debug, Clone)]
+pub struct CoveredCode {
+    pub code: Bytes,
+    pub sources: ContractSources,
+    pub hits: CodeHits,
+}
+
+/// Add the executions of a provider to the ones of the binary.
+pub(crate) fn submit(covered: Vec<(B256, CoveredCode)>) {
+    let mut coverage = COVERAGE.lock().unwrap_or_else(|err| err.into_inner());
+    for (hash, code) in covered {
+        match coverage.get_mut(&hash) {
+            Some(existing) => existing.hits.merge(&code.hits),
+            None => {
+                coverage.insert(hash, code);
+            }
+        }
+    }
+}
+
+/// Write the coverage of the binary so far to `lcov.info` and `summary.md`.
+pub fn write_report() -> io::Result<PathBuf> {
+    // held while writing, an older report written last would miss the hits of the other tests
+    let coverage = COVERAGE.lock().unwrap_or_else(|err| err.into_inner());
+    let report = CoverageReport::new(coverage.values());
+    let dir = rustry_dir().join("coverage").join(binary_name());
+    fs::create_dir_all(&dir)?;
+    fs::write(dir.join("lcov.info"), report.to_lcov())?;
+    fs::write(dir.join("summary.md"), report.to_string())?;
+    Ok(dir)
+}
+
+/// Writes the coverage when a test ends, as each test may be the last one of the binary.
+pub struct CoverageGuard;
+
+impl Drop for CoverageGuard {
+    fn drop(&mut self) {
+        if is_enabled() {
+            if let Err(err) = write_report() {
+                eprintln!("failed to write the coverage: {err}");
+            }
+        }
+    }
+}
@@ -0,0 +1,248 @@
+use super::CoveredCode;
+use crate::compilers::solidity::source_map::{instructions, parse_source_map};
+use revm::interpreter::opcode;
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::{self, Write},
+};
+
+/// A `JUMPI`, the branches being the jump and the next instruction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BranchCoverage {
+    pub line: usize,
+    /// The times it jumped and the times it did not, `None` if it was never reached
+    pub taken: Option<[u64; 2]>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FileCoverage {
+    /// Executions of the most executed instruction of each line with instructions
+    pub lines: BTreeMap<usize, u64>,
+    pub branches: Vec<BranchCoverage>,
+}
+
+impl FileCoverage {
+    pub fn lines_hit(&self) -> usize {
+        self.lines.values().filter(|count| **count > 0).count()
+    }
+
+    /// Counting the 2 branches of each `JUMPI`.
+    pub fn branches_found(&self) -> usize {
+        2 * self.branches.len()
+    }
+
+    pub fn branches_hit(&self) -> usize {
+        self.branches
+            .iter()
+            .filter_map(|branch| branch.taken)
+            .flatten()
+            .filter(|count| *count > 0)
+            .count()
+    }
+}
+
+/// Coverage by source file, the `solidity!` and `vyper!` literals being in their Rust file.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CoverageReport {
+    pub files: BTreeMap<String, FileCoverage>,
+}
+
+impl CoverageReport {
+    pub fn new<'a>(covered: impl IntoIterator<Item = &'a CoveredCode>) -> Self {
+        let mut report = CoverageReport::default();
+        for code in covered {
+            report.add(code);
+        }
+        report
+    }
+
+    fn add(&mut self, covered: &CoveredCode) {
+        let elements = parse_source_map(&covered.sources.source_map);
+        // start of each line, by file id
+        let line_starts: HashMap<i32, Vec<usize>> = covered
+            .sources
+            .files
+            .iter()
+            .filter(|file| !file.is_generated())
+            .map(|file| {
+                let starts = std::iter::once(0)
+                    .chain(
+                        file.content
+                            .match_indices('\n')
+                            .map(|(newline, _)| newline + 1),
+                    )
+                    .collect();
+                (file.id, starts)
+            })
+            .collect();
+
+        for ((pc, op), element) in instructions(&covered.code).into_iter().zip(elements) {
+            let Some(starts) = line_starts.get(&element.file) else {
+                continue;
+            };
+            let file = covered
+                .sources
+                .files
+                .iter()
+                .find(|file| file.id == element.file)
+                .expect("the lines are of a known file");
+            let line = starts.partition_point(|start| *start <= element.offset);
+            let (path, line, _) = file.translate(line, 1);
+
+            let coverage = self.files.entry(path).or_default();
+            let count = covered.hits.instructions.get(&pc).copied().unwrap_or(0);
+            let line_count = coverage.lines.entry(line).or_default();
+            *line_count = (*line_count).max(count);
+            if op == opcode::JUMPI {
+                coverage.branches.push(BranchCoverage {
+                    line,
+                    taken: (count > 0)
+                        .then(|| covered.hits.jumps.get(&pc).copied().unwrap_or_default()),
+                });
+            }
+        }
+    }
+
+    /// The `lcov.info` format, read by the editors and the CI tools.
+    pub fn to_lcov(&self) -> String {
+        let mut lcov = String::new();
+        for (path, file) in &self.files {
+            writeln!(lcov, "TN:\nSF:{path}").unwrap();
+            for (line, count) in &file.lines {
+                writeln!(lcov, "DA:{line},{count}").unwrap();
+            }
+            for (block, branch) in file.branches.iter().enumerate() {
+                for index in 0..2 {
+                    let taken = branch
+                        .taken
+                        .map_or("-".to_string(), |taken| taken[index].to_string());
+                    writeln!(lcov, "BRDA:{},{block},{index},{taken}", branch.line).unwrap();
+                }
+            }
+            writeln!(
+                lcov,
+                "BRF:{}\nBRH:{}\nLF:{}\nLH:{}\nend_of_record",
+                file.branches_found(),
+                file.branches_hit(),
+                file.lines.len(),
+                file.lines_hit()
+            )
+            .unwrap();
+        }
+        lcov
+    }
+}
+
+fn ratio(hit: usize, found: usize) -> String {
+    if found == 0 {
+        return "-".to_string();
+    }
+    format!("{:.2}% ({hit}/{found})", hit as f64 * 100.0 / found as f64)
+}
+
+/// A Markdown table of the lines and branches hit per file.
+impl fmt::Display for CoverageReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "| File | Lines | Branches |\n| ---- | ----- | -------- |"
+        )?;
+        let (mut lines, mut branches) = ((0, 0), (0, 0));
+        for (path, file) in &self.files {
+            writeln!(
+                f,
+                "| {path} | {} | {} |",
+                ratio(file.lines_hit(), file.lines.len()),
+                ratio(file.branches_hit(), file.branches_found())
+            )?;
+            lines = (lines.0 + file.lines_hit(), lines.1 + file.lines.len());
+            branches = (
+                branches.0 + file.branches_hit(),
+                branches.1 + file.branches_found(),
+            );
+        }
+        writeln!(
+            f,
+            "| Total | {} | {} |",
+            ratio(lines.0, lines.1),
+            ratio(branches.0, branches.1)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CoverageReport;
+    use crate::{
+        compilers::solidity::source_map::{ContractSources, SourceFile},
+        coverage::{CodeHits, CoveredCode},
+    };
+    use std::collections::HashMap;
+
+    #[test]
+    fn lcov() {
+        let content = "contract A {\n    function f() {\n        if (x) {\n            y();\n        }\n    }\n}";
+        let line = |text: &str| content.find(text).unwrap();
+        // PUSH1 0x06 CALLDATASIZE JUMPI STOP JUMPDEST STOP
+        let code = vec![0x60, 0x06, 0x36, 0x57, 0x00, 0x5b, 0x00];
+        let source_map = format!(
+            "{}:1:0;;{}:1:0;;{}:1:0;:1:1",
+            line("contract"),
+            line("if"),
+            line("y()")
+        );
+        let covered = CoveredCode {
+            code: code.into(),
+            sources: ContractSources {
+                source_map,
+                files: vec![
+                    SourceFile {
+                        id: 0,
+                        name: "A.sol".to_string(),
+                        content: content.to_string(),
+                        origin: None,
+                    },
+                    SourceFile {
+                        id: 1,
+                        name: "#utility.yul".to_string(),
+                        content: String::new(),
+                        origin: None,
+                    },
+                ],
+                functions: Vec::new(),
+            },
+            hits: CodeHits {
+                instructions: HashMap::from([(0, 2), (2, 2), (3, 2), (4, 2)]),
+                jumps: HashMap::from([(3, [0, 2])]),
+            },
+        };
+
+        let report = CoverageReport::new([&covered]);
+        assert_eq!(
+            report.to_lcov(),
+            "\
+TN:
+SF:A.sol
+DA:1,2
+DA:3,2
+DA:4,0
+BRDA:3,0,0,0
+BRDA:3,0,1,2
+BRF:2
+BRH:1
+LF:3
+LH:2
+end_of_record
+"
+        );
+        assert_eq!(
+            report.to_string(),
+            "\
+| File | Lines | Branches |
+| ---- | ----- | -------- |
+| A.sol | 66.67% (2/3) | 50.00% (1/2) |
+| Total | 66.67% (2/3) | 50.00% (1/2) |
+"
+        );
+    }
+}
@@ -0,0 +1,56 @@
+use super::Fuzz;
+use crate::utils::rustry_dir;
+use revm::primitives::hex;
+use std::{fs, io, path::PathBuf};
+
+/// Failing inputs of a fuzz test, kept in `target/rustry/fuzz-failures/<test>`.
+/// Each input is a hex encoded line, preceded by a readable comment.
+pub struct Corpus {
+    path: PathBuf,
+}
+
+impl Corpus {
+    pub fn new(test: &str) -> Self {
+        let file_name = test.replace("::", "-");
+        Corpus {
+            path: rustry_dir().join("fuzz-failures").join(file_name),
+        }
+    }
+
+    pub fn path(&self) -> &PathBuf {
+        &self.path
+    }
+
+    /// The saved inputs, skipping the ones which don't match the parameters anymore.
+    pub fn load<T: Fuzz>(&self) -> Vec<T> {
+        let Ok(content) = fs::read_to_string(&self.path) else {
+            return Vec::new();
+        };
+        content
+            .lines()
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .filter_map(|line| hex::decode(line).ok())
+            .filter_map(|bytes| {
+                let mut data = bytes.as_slice();
+                T::deserialize(&mut data).filter(|_| data.is_empty())
+            })
+            .collect()
+    }
+
+    pub fn save<T: Fuzz>(&self, input: &T) -> io::Result<()> {
+        let mut bytes = Vec::new();
+        input.serialize(&mut bytes);
+        let line = hex::encode(bytes);
+
+        let mut content = fs::read_to_string(&self.path).unwrap_or_default();
+        if content.lines().any(|saved| saved == line) {
+            return Ok(());
+        }
+        content.push_str(&format!("# {}\n{line}\n", input.describe()));
+
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(&self.path, content)
+    }
+}
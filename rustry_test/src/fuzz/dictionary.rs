@@ -0,0 +1,151 @@
+use super::Rng;
+use revm::{
+    interpreter::opcode::{PUSH1, PUSH20, PUSH32},
+    primitives::{Address, U256},
+};
+use std::{cell::RefCell, collections::HashSet};
+
+/// Past this size, the new entries are dropped.
+const MAX_ENTRIES: usize = 4096;
+
+thread_local! {
+    /// Only set while a fuzz test is running on this thread.
+    static DICTIONARY: RefCell<Option<Dictionary>> = RefCell::new(None);
+}
+
+/// Interesting values met during the executions, mixed into the generated inputs.
+#[derive(Debug, Default)]
+pub struct Dictionary {
+    values: Vec<U256>,
+    seen_values: HashSet<U256>,
+    addresses: Vec<Address>,
+    seen_addresses: HashSet<Address>,
+}
+
+impl Dictionary {
+    fn insert_value(&mut self, value: U256) {
+        if self.values.len() < MAX_ENTRIES && self.seen_values.insert(value) {
+            self.values.push(value);
+        }
+    }
+
+    fn insert_address(&mut self, address: Address) {
+        if self.addresses.len() < MAX_ENTRIES && self.seen_addresses.insert(address) {
+            self.addresses.push(address);
+        }
+    }
+}
+
+/// Collect into the dictionary until dropped.
+pub(super) struct DictionaryGuard;
+
+impl DictionaryGuard {
+    pub fn activate() -> Self {
+        DICTIONARY.with(|dictionary| *dictionary.borrow_mut() = Some(Dictionary::default()));
+        DictionaryGuard
+    }
+}
+
+impl Drop for DictionaryGuard {
+    fn drop(&mut self) {
+        DICTIONARY.with(|dictionary| *dictionary.borrow_mut() = None);
+    }
+}
+
+/// `bytes` must be at most 32 bytes long.
+fn word(bytes: &[u8]) -> U256 {
+    U256::try_from_be_slice(bytes).unwrap_or_default()
+}
+
+fn with_dictionary(f: impl FnOnce(&mut Dictionary)) {
+    DICTIONARY.with(|dictionary| {
+        if let Some(dictionary) = dictionary.borrow_mut().as_mut() {
+            f(dictionary)
+        }
+    })
+}
+
+pub fn collect_value(value: U256) {
+    with_dictionary(|dictionary| dictionary.insert_value(value))
+}
+
+pub fn collect_address(address: Address) {
+    with_dictionary(|dictionary| {
+        dictionary.insert_address(address);
+        dictionary.insert_value(word(address.as_slice()));
+    })
+}
+
+/// The operands of the PUSH instructions, e.g. the constants of a contract.
+pub fn collect_code(code: &[u8]) {
+    with_dictionary(|dictionary| {
+        let mut pc = 0;
+        while pc < code.len() {
+            let opcode = code[pc];
+            pc += 1;
+            if (PUSH1..=PUSH32).contains(&opcode) {
+                let len = usize::from(opcode - PUSH1 + 1);
+                let operand = &code[pc..code.len().min(pc + len)];
+                pc += len;
+                if operand.len() != len {
+                    break;
+                }
+                if opcode == PUSH20 {
+                    dictionary.insert_address(Address::from_slice(operand));
+                }
+                dictionary.insert_value(word(operand));
+            }
+        }
+    })
+}
+
+/// The 32 bytes words of some data, e.g. returned by a call.
+pub fn collect_words(data: &[u8]) {
+    with_dictionary(|dictionary| {
+        data.chunks_exact(32)
+            .for_each(|bytes| dictionary.insert_value(word(bytes)));
+    })
+}
+
+fn pick<T: Copy>(rng: &mut Rng, entries: &[T]) -> Option<T> {
+    if entries.is_empty() {
+        None
+    } else {
+        Some(entries[rng.below(entries.len() as u64) as usize])
+    }
+}
+
+pub(super) fn pick_value(rng: &mut Rng) -> Option<U256> {
+    DICTIONARY.with(|dictionary| pick(rng, &dictionary.borrow().as_ref()?.values))
+}
+
+pub(super) fn pick_address(rng: &mut Rng) -> Option<Address> {
+    DICTIONARY.with(|dictionary| pick(rng, &dictionary.borrow().as_ref()?.addresses))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{collect_code, collect_words, pick_address, pick_value, DictionaryGuard};
+    use crate::fuzz::Rng;
+    use revm::primitives::{Address, U256};
+
+    #[test]
+    fn collect() {
+        let mut rng = Rng::new(0);
+        // inactive outside of the fuzz tests
+        collect_words(&[1; 32]);
+        assert_eq!(pick_value(&mut rng), None);
+
+        let _guard = DictionaryGuard::activate();
+        // PUSH2 0x1234 PUSH20 0xaa..aa PUSH2 0x12 (truncated)
+        let mut code = vec![0x61, 0x12, 0x34, 0x73];
+        code.extend([0xaa; 20]);
+        code.extend([0x61, 0x12]);
+        collect_code(&code);
+
+        assert_eq!(pick_address(&mut rng), Some(Address::repeat_byte(0xaa)));
+        let values: Vec<_> = (0..32).filter_map(|_| pick_value(&mut rng)).collect();
+        assert!(values.contains(&U256::from(0x1234)));
+        assert!(!values.contains(&U256::from(0x12)));
+    }
+}
@@ -0,0 +1,566 @@
+use super::dictionary;
+use crate::utils::codec::{AbiValue, ParamType};
+use revm::primitives::{alloy_primitives::I256, Address, Bytes, FixedBytes, U256};
+use std::fmt::Debug;
+
+/// Small and fast pseudo random generator (splitmix64), reproducible from its seed.
+#[derive(Debug, Clone)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Rng { state: seed }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform in `0..bound`, `bound` must not be 0.
+    pub fn below(&mut self, bound: u64) -> u64 {
+        self.next_u64() % bound
+    }
+
+    /// True once every `n` times on average.
+    pub fn one_in(&mut self, n: u64) -> bool {
+        self.below(n) == 0
+    }
+
+    pub fn fill(&mut self, bytes: &mut [u8]) {
+        for chunk in bytes.chunks_mut(8) {
+            let random = self.next_u64().to_le_bytes();
+            chunk.copy_from_slice(&random[..chunk.len()]);
+        }
+    }
+}
+
+/// Types which can be generated as inputs of a fuzz test.
+pub trait Fuzz: Sized + Clone + Debug {
+    fn generate(rng: &mut Rng) -> Self;
+
+    /// Simpler values to try when the test failed with this one, the simplest first.
+    fn shrink(&self) -> Vec<Self> {
+        Vec::new()
+    }
+
+    /// Binary form of the value, to persist the failures.
+    fn serialize(&self, out: &mut Vec<u8>);
+
+    fn deserialize(data: &mut &[u8]) -> Option<Self>;
+
+    /// How the input is shown when the test fails.
+    fn describe(&self) -> String {
+        format!("{self:?}")
+    }
+}
+
+fn take<'a>(data: &mut &'a [u8], len: usize) -> Option<&'a [u8]> {
+    if data.len() < len {
+        None
+    } else {
+        let (head, tail) = data.split_at(len);
+        *data = tail;
+        Some(head)
+    }
+}
+
+fn serialize_len(len: usize, out: &mut Vec<u8>) {
+    out.extend_from_slice(&(len as u32).to_le_bytes());
+}
+
+fn deserialize_len(data: &mut &[u8]) -> Option<usize> {
+    let len = take(data, 4)?.try_into().ok()?;
+    Some(u32::from_le_bytes(len) as usize)
+}
+
+/// Values breaking the contracts most often.
+fn edge_u256(rng: &mut Rng) -> U256 {
+    let bit = rng.below(256) as usize;
+    match rng.below(6) {
+        0 => U256::ZERO,
+        1 => U256::from(1),
+        2 => U256::MAX,
+        3 => U256::MAX - U256::from(1),
+        4 => U256::from(1) << bit,
+        _ => (U256::from(1) << bit) - U256::from(1),
+    }
+}
+
+/// Moves towards zero, by half of the distance first, and then by smaller and smaller steps.
+fn shrink_u256(value: U256) -> Vec<U256> {
+    if value == U256::ZERO {
+        return Vec::new();
+    }
+    let mut candidates = vec![U256::ZERO];
+    candidates.extend(
+        (1..256)
+            .map(|shift| value >> shift)
+            .take_while(|step| *step != U256::ZERO)
+            .map(|step| value - step),
+    );
+    candidates.push(value - U256::from(1));
+    candidates.dedup();
+    candidates
+}
+
+impl Fuzz for U256 {
+    fn generate(rng: &mut Rng) -> Self {
+        if rng.one_in(4) {
+            if let Some(value) = dictionary::pick_value(rng) {
+                // the off by ones are as interesting as the values themselves
+                return match rng.below(4) {
+                    0 => value.wrapping_add(U256::from(1)),
+                    1 => value.wrapping_sub(U256::from(1)),
+                    _ => value,
+                };
+            }
+        }
+        if rng.one_in(8) {
+            return edge_u256(rng);
+        }
+        let mut bytes = [0u8; 32];
+        rng.fill(&mut bytes);
+        // favor the small values, as amounts and indexes usually are
+        let bits = rng.below(257) as usize;
+        if bits == 0 {
+            U256::ZERO
+        } else {
+            U256::from_be_bytes(bytes) >> (256 - bits)
+        }
+    }
+
+    fn shrink(&self) -> Vec<Self> {
+        shrink_u256(*self)
+    }
+
+    fn serialize(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.to_be_bytes::<32>());
+    }
+
+    fn deserialize(data: &mut &[u8]) -> Option<Self> {
+        U256::try_from_be_slice(take(data, 32)?)
+    }
+
+    fn describe(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl Fuzz for I256 {
+    fn generate(rng: &mut Rng) -> Self {
+        I256::from_raw(U256::generate(rng))
+    }
+
+    fn shrink(&self) -> Vec<Self> {
+        let abs = self.unsigned_abs();
+        let mut candidates: Vec<I256> = shrink_u256(abs)
+            .into_iter()
+            .filter_map(|abs| I256::try_from(abs).ok())
+            .map(|abs| {
+                if self.is_negative() {
+                    abs.wrapping_neg()
+                } else {
+                    abs
+                }
+            })
+            .collect();
+        if let Some(positive) = self
+            .is_negative()
+            .then(|| I256::try_from(abs).ok())
+            .flatten()
+        {
+            candidates.insert(1.min(candidates.len()), positive);
+        }
+        candidates
+    }
+
+    fn serialize(&self, out: &mut Vec<u8>) {
+        self.into_raw().serialize(out)
+    }
+
+    fn deserialize(data: &mut &[u8]) -> Option<Self> {
+        U256::deserialize(data).map(I256::from_raw)
+    }
+
+    fn describe(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl Fuzz for Address {
+    fn generate(rng: &mut Rng) -> Self {
+        if rng.one_in(3) {
+            if let Some(address) = dictionary::pick_address(rng) {
+                return address;
+            }
+        }
+        match rng.below(16) {
+            0 => Address::ZERO,
+            1 => Address::with_last_byte(rng.below(256) as u8),
+            _ => {
+                let mut address = Address::ZERO;
+                rng.fill(address.as_mut_slice());
+                address
+            }
+        }
+    }
+
+    fn shrink(&self) -> Vec<Self> {
+        if self.is_zero() {
+            Vec::new()
+        } else {
+            vec![Address::ZERO]
+        }
+    }
+
+    fn serialize(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(self.as_slice());
+    }
+
+    fn deserialize(data: &mut &[u8]) -> Option<Self> {
+        Some(Address::from_slice(take(data, 20)?))
+    }
+}
+
+impl Fuzz for bool {
+    fn generate(rng: &mut Rng) -> Self {
+        rng.one_in(2)
+    }
+
+    fn shrink(&self) -> Vec<Self> {
+        if *self {
+            vec![false]
+        } else {
+            Vec::new()
+        }
+    }
+
+    fn serialize(&self, out: &mut Vec<u8>) {
+        out.push(u8::from(*self));
+    }
+
+    fn deserialize(data: &mut &[u8]) -> Option<Self> {
+        match take(data, 1)? {
+            [0] => Some(false),
+            [1] => Some(true),
+            _ => None,
+        }
+    }
+}
+
+impl<const N: usize> Fuzz for FixedBytes<N> {
+    fn generate(rng: &mut Rng) -> Self {
+        let mut bytes = FixedBytes::ZERO;
+        if !rng.one_in(16) {
+            rng.fill(bytes.as_mut_slice());
+        }
+        bytes
+    }
+
+    fn shrink(&self) -> Vec<Self> {
+        if self.is_zero() {
+            Vec::new()
+        } else {
+            vec![FixedBytes::ZERO]
+        }
+    }
+
+    fn serialize(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(self.as_slice());
+    }
+
+    fn deserialize(data: &mut &[u8]) -> Option<Self> {
+        Some(FixedBytes::from_slice(take(data, N)?))
+    }
+}
+
+/// Shorter versions of the list first, then the list with each of its elements shrunk.
+fn shrink_list<T: Clone>(values: &[T], shrink: impl Fn(&T) -> Vec<T>) -> Vec<Vec<T>> {
+    if values.is_empty() {
+        return Vec::new();
+    }
+    let mut candidates = vec![Vec::new()];
+    if values.len() > 2 {
+        candidates.push(values[..values.len() / 2].to_vec());
+    }
+    if values.len() > 1 {
+        candidates.extend((0..values.len()).map(|i| [&values[..i], &values[i + 1..]].concat()));
+    }
+    for (i, value) in values.iter().enumerate() {
+        candidates.extend(shrink(value).into_iter().map(|shrunk| {
+            let mut values = values.to_vec();
+            values[i] = shrunk;
+            values
+        }));
+    }
+    candidates
+}
+
+impl Fuzz for Bytes {
+    fn generate(rng: &mut Rng) -> Self {
+        let mut bytes = vec![0u8; rng.below(65) as usize];
+        rng.fill(&mut bytes);
+        bytes.into()
+    }
+
+    fn shrink(&self) -> Vec<Self> {
+        shrink_list(self, |byte| (*byte != 0).then_some(0).into_iter().collect())
+            .into_iter()
+            .map(Bytes::from)
+            .collect()
+    }
+
+    fn serialize(&self, out: &mut Vec<u8>) {
+        serialize_len(self.len(), out);
+        out.extend_from_slice(self);
+    }
+
+    fn deserialize(data: &mut &[u8]) -> Option<Self> {
+        let len = deserialize_len(data)?;
+        Some(take(data, len)?.to_vec().into())
+    }
+}
+
+impl Fuzz for String {
+    fn generate(rng: &mut Rng) -> Self {
+        let len = rng.below(33);
+        (0..len)
+            .map(|_| char::from(b' ' + rng.below(95) as u8))
+            .collect()
+    }
+
+    fn shrink(&self) -> Vec<Self> {
+        let chars: Vec<char> = self.chars().collect();
+        shrink_list(&chars, |c| (*c != 'a').then_some('a').into_iter().collect())
+            .into_iter()
+            .map(String::from_iter)
+            .collect()
+    }
+
+    fn serialize(&self, out: &mut Vec<u8>) {
+        serialize_len(self.len(), out);
+        out.extend_from_slice(self.as_bytes());
+    }
+
+    fn deserialize(data: &mut &[u8]) -> Option<Self> {
+        let len = deserialize_len(data)?;
+        String::from_utf8(take(data, len)?.to_vec()).ok()
+    }
+}
+
+macro_rules! impl_fuzz_int {
+    ($($ty:ty),*) => {
+        $(
+            impl Fuzz for $ty {
+                fn generate(rng: &mut Rng) -> Self {
+                    match rng.below(8) {
+                        0 => <$ty>::MIN,
+                        1 => <$ty>::MAX,
+                        2 => 0,
+                        _ => rng.next_u64() as $ty,
+                    }
+                }
+
+                fn shrink(&self) -> Vec<Self> {
+                    if *self == 0 {
+                        return Vec::new();
+                    }
+                    let mut candidates = vec![0];
+                    candidates.extend(
+                        (1..<$ty>::BITS)
+                            .map(|shift| self >> shift)
+                            .take_while(|step| *step != 0)
+                            .map(|step| self - step),
+                    );
+                    candidates.push(if *self > 0 { self - 1 } else { self + 1 });
+                    candidates.dedup();
+                    candidates
+                }
+
+                fn serialize(&self, out: &mut Vec<u8>) {
+                    out.extend_from_slice(&self.to_le_bytes());
+                }
+
+                fn deserialize(data: &mut &[u8]) -> Option<Self> {
+                    let bytes = take(data, std::mem::size_of::<$ty>())?.try_into().ok()?;
+                    Some(<$ty>::from_le_bytes(bytes))
+                }
+            }
+        )*
+    };
+}
+
+impl_fuzz_int!(u8, u16, u32, u64, usize, i8, i16, i32, i64);
+
+impl Fuzz for u128 {
+    fn generate(rng: &mut Rng) -> Self {
+        (u128::from(rng.next_u64()) << 64) | u128::from(rng.next_u64())
+    }
+
+    fn shrink(&self) -> Vec<Self> {
+        shrink_u256(U256::from(*self))
+            .into_iter()
+            .map(|value| value.to())
+            .collect()
+    }
+
+    fn serialize(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.to_le_bytes());
+    }
+
+    fn deserialize(data: &mut &[u8]) -> Option<Self> {
+        Some(u128::from_le_bytes(take(data, 16)?.try_into().ok()?))
+    }
+}
+
+impl<T: Fuzz> Fuzz for Vec<T> {
+    fn generate(rng: &mut Rng) -> Self {
+        (0..rng.below(17)).map(|_| T::generate(rng)).collect()
+    }
+
+    fn shrink(&self) -> Vec<Self> {
+        shrink_list(self, T::shrink)
+    }
+
+    fn serialize(&self, out: &mut Vec<u8>) {
+        serialize_len(self.len(), out);
+        self.iter().for_each(|value| value.serialize(out));
+    }
+
+    fn deserialize(data: &mut &[u8]) -> Option<Self> {
+        let len = deserialize_len(data)?;
+        (0..len).map(|_| T::deserialize(data)).collect()
+    }
+
+    fn describe(&self) -> String {
+        describe_list(self)
+    }
+}
+
+impl<T: Fuzz, const N: usize> Fuzz for [T; N] {
+    fn generate(rng: &mut Rng) -> Self {
+        std::array::from_fn(|_| T::generate(rng))
+    }
+
+    fn shrink(&self) -> Vec<Self> {
+        let mut candidates = Vec::new();
+        for (i, value) in self.iter().enumerate() {
+            candidates.extend(value.shrink().into_iter().map(|shrunk| {
+                let mut values = self.clone();
+                values[i] = shrunk;
+                values
+            }));
+        }
+        candidates
+    }
+
+    fn serialize(&self, out: &mut Vec<u8>) {
+        self.iter().for_each(|value| value.serialize(out));
+    }
+
+    fn deserialize(data: &mut &[u8]) -> Option<Self> {
+        let values: Vec<T> = (0..N)
+            .map(|_| T::deserialize(data))
+            .collect::<Option<_>>()?;
+        values.try_into().ok()
+    }
+
+    fn describe(&self) -> String {
+        describe_list(self)
+    }
+}
+
+fn describe_list<T: Fuzz>(values: &[T]) -> String {
+    let values: Vec<_> = values.iter().map(T::describe).collect();
+    format!("[{}]", values.join(", "))
+}
+
+macro_rules! impl_fuzz_tuple {
+    ($($name:ident $index:tt),+) => {
+        impl<$($name: Fuzz),+> Fuzz for ($($name,)+) {
+            fn generate(rng: &mut Rng) -> Self {
+                ($($name::generate(rng),)+)
+            }
+
+            fn shrink(&self) -> Vec<Self> {
+                let mut candidates = Vec::new();
+                $(
+                    candidates.extend(self.$index.shrink().into_iter().map(|shrunk| {
+                        let mut values = self.clone();
+                        values.$index = shrunk;
+                        values
+                    }));
+                )+
+                candidates
+            }
+
+            fn serialize(&self, out: &mut Vec<u8>) {
+                $(self.$index.serialize(out);)+
+            }
+
+            fn deserialize(data: &mut &[u8]) -> Option<Self> {
+                Some(($($name::deserialize(data)?,)+))
+            }
+
+            fn describe(&self) -> String {
+                let values = [$(self.$index.describe()),+];
+                format!("({})", values.join(", "))
+            }
+        }
+    };
+}
+
+impl_fuzz_tuple!(A 0);
+impl_fuzz_tuple!(A 0, B 1);
+impl_fuzz_tuple!(A 0, B 1, C 2);
+impl_fuzz_tuple!(A 0, B 1, C 2, D 3);
+impl_fuzz_tuple!(A 0, B 1, C 2, D 3, E 4);
+impl_fuzz_tuple!(A 0, B 1, C 2, D 3, E 4, F 5);
+impl_fuzz_tuple!(A 0, B 1, C 2, D 3, E 4, F 5, G 6);
+impl_fuzz_tuple!(A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7);
+
+/// Value of an ABI type, used to call the contracts with generated arguments.
+pub fn generate_abi(ty: &ParamType, rng: &mut Rng) -> AbiValue {
+    // the bits above the size of the type are dropped, and the sign extended
+    let mask = |bits: usize| {
+        if bits >= 256 {
+            U256::MAX
+        } else {
+            (U256::from(1) << bits) - U256::from(1)
+        }
+    };
+    match ty {
+        ParamType::Uint(bits) => AbiValue::Uint(U256::generate(rng) & mask(*bits)),
+        ParamType::Int(bits) => {
+            let mut raw = U256::generate(rng) & mask(*bits);
+            if *bits < 256 && raw.bit(bits - 1) {
+                raw |= !mask(*bits);
+            }
+            AbiValue::Int(I256::from_raw(raw))
+        }
+        ParamType::Address => AbiValue::Address(Address::generate(rng)),
+        ParamType::Bool => AbiValue::Bool(bool::generate(rng)),
+        ParamType::FixedBytes(len) => {
+            let mut bytes = vec![0; *len];
+            rng.fill(&mut bytes);
+            AbiValue::FixedBytes(bytes)
+        }
+        ParamType::Bytes => AbiValue::Bytes(Bytes::generate(rng)),
+        ParamType::String => AbiValue::String(String::generate(rng)),
+        ParamType::Array(elem) => {
+            AbiValue::Array((0..rng.below(5)).map(|_| generate_abi(elem, rng)).collect())
+        }
+        ParamType::FixedArray(elem, len) => {
+            AbiValue::FixedArray((0..*len).map(|_| generate_abi(elem, rng)).collect())
+        }
+        ParamType::Tuple(types) => {
+            AbiValue::Tuple(types.iter().map(|ty| generate_abi(ty, rng)).collect())
+        }
+    }
+}
@@ -0,0 +1,274 @@
+//! Stateful fuzzing: sequences of random calls to the deployed contracts, with the invariants
+//! checked after each of them.
+
+use super::{default_seed, env_var, gen::generate_abi, quietly, DictionaryGuard, Rng};
+use crate::{
+    common::labels::Labeled,
+    provider::{
+        db::{ExecRes, Frontend},
+        Provider,
+    },
+    utils::{
+        abi::get_sig,
+        codec::{encode, AbiValue, ParamType},
+    },
+};
+use revm::primitives::{hex, Address, Bytes};
+use std::{
+    fmt,
+    panic::{self, AssertUnwindSafe},
+};
+
+/// Upper bound of the executions spent shrinking a failing sequence.
+const MAX_SHRINK_RUNS: u32 = 256;
+
+/// How an invariant test is run, `RUSTRY_FUZZ_RUNS`, `RUSTRY_INVARIANT_DEPTH` and
+/// `RUSTRY_FUZZ_SEED` override the defaults.
+#[derive(Debug, Clone)]
+pub struct InvariantConfig {
+    /// Number of sequences, each one starting from the set up state
+    pub runs: u32,
+    /// Number of calls per sequence
+    pub depth: u32,
+    /// Seed of the generator, printed on failure to replay it
+    pub seed: u64,
+    /// Callers of the target functions
+    pub senders: Vec<Address>,
+    /// If a reverting call breaks the invariants
+    pub fail_on_revert: bool,
+}
+
+impl Default for InvariantConfig {
+    fn default() -> Self {
+        InvariantConfig {
+            runs: env_var("RUSTRY_FUZZ_RUNS").unwrap_or(64),
+            depth: env_var("RUSTRY_INVARIANT_DEPTH").unwrap_or(32),
+            seed: default_seed(),
+            senders: [0x10, 0x20, 0x30].map(Address::repeat_byte).to_vec(),
+            fail_on_revert: false,
+        }
+    }
+}
+
+impl InvariantConfig {
+    /// Apply the environment variables again, over the settings of the test.
+    pub fn override_from_env(&mut self) {
+        if let Some(runs) = env_var("RUSTRY_FUZZ_RUNS") {
+            self.runs = runs;
+        }
+        if let Some(depth) = env_var("RUSTRY_INVARIANT_DEPTH") {
+            self.depth = depth;
+        }
+        if let Some(seed) = env_var("RUSTRY_FUZZ_SEED") {
+            self.seed = seed;
+        }
+    }
+}
+
+/// A call of the sequence, with everything needed to replay it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Call {
+    pub sender: Address,
+    pub target: Address,
+    /// Label of the target contract
+    pub contract: String,
+    /// Signature of the called function
+    pub function: String,
+    pub args: Vec<AbiValue>,
+}
+
+impl Call {
+    pub fn calldata(&self) -> Bytes {
+        let mut data = get_sig(&self.function).to_vec();
+        data.extend(encode(&self.args));
+        data.into()
+    }
+
+    /// Send the call from its sender, without changing the one of the provider.
+    pub fn execute(&self, provider: &mut Provider) -> ExecRes {
+        let sender = std::mem::replace(&mut provider.sender, self.sender);
+        let res = provider.call(self.target, self.calldata());
+        provider.sender = sender;
+        res
+    }
+}
+
+impl fmt::Display for Call {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = self.function.split('(').next().unwrap_or_default();
+        let args: Vec<String> = self.args.iter().map(ToString::to_string).collect();
+        write!(
+            f,
+            "{} -> {}({}).{name}({})\n     calldata: 0x{}",
+            Labeled(self.sender),
+            self.contract,
+            self.target,
+            args.join(", "),
+            hex::encode(self.calldata())
+        )
+    }
+}
+
+enum Plan {
+    Generate { rng: Rng, depth: u32 },
+    Replay(Vec<Call>),
+}
+
+/// One sequence of calls, either generated or replayed.
+pub struct InvariantRun {
+    plan: Plan,
+    senders: Vec<Address>,
+    fail_on_revert: bool,
+    /// Only these contracts are called if not empty
+    targets: Vec<Address>,
+    executed: Vec<Call>,
+}
+
+impl InvariantRun {
+    fn new(config: &InvariantConfig, plan: Plan) -> Self {
+        InvariantRun {
+            plan,
+            senders: config.senders.clone(),
+            fail_on_revert: config.fail_on_revert,
+            targets: Vec::new(),
+            executed: Vec::new(),
+        }
+    }
+
+    /// Only call this contract and the other targets, instead of all the deployed ones.
+    /// This is how the calls go through handler contracts.
+    pub fn target(&mut self, address: Address) {
+        self.targets.push(address);
+    }
+
+    /// Run the sequence on `provider`, checking `invariant` on the initial state and after each call.
+    pub fn drive(&mut self, provider: &mut Provider, invariant: impl Fn(&mut Provider)) {
+        invariant(provider);
+        match &mut self.plan {
+            Plan::Generate { rng, depth } => {
+                let functions = target_functions(provider, &self.targets);
+                assert!(
+                    !functions.is_empty(),
+                    "no function to call, deploy a contract through its bindings first"
+                );
+                for _ in 0..*depth {
+                    let (target, contract, function, types) =
+                        &functions[rng.below(functions.len() as u64) as usize];
+                    let call = Call {
+                        sender: self.senders[rng.below(self.senders.len() as u64) as usize],
+                        target: *target,
+                        contract: contract.clone(),
+                        function: function.clone(),
+                        args: types.iter().map(|ty| generate_abi(ty, rng)).collect(),
+                    };
+                    self.executed.push(call.clone());
+                    execute(&call, provider, self.fail_on_revert);
+                    invariant(provider);
+                }
+            }
+            Plan::Replay(calls) => {
+                for call in calls.iter() {
+                    self.executed.push(call.clone());
+                    execute(call, provider, self.fail_on_revert);
+                    invariant(provider);
+                }
+            }
+        }
+    }
+}
+
+fn execute(call: &Call, provider: &mut Provider, fail_on_revert: bool) {
+    let res = call.execute(provider);
+    if fail_on_revert {
+        res.success();
+    }
+}
+
+/// The mutating functions of the targets, or of all the registered contracts.
+fn target_functions(
+    provider: &Provider,
+    targets: &[Address],
+) -> Vec<(Address, String, String, Vec<ParamType>)> {
+    provider
+        .contracts()
+        .iter()
+        .filter(|(address, _)| targets.is_empty() || targets.contains(address))
+        .flat_map(|(address, info)| {
+            info.functions
+                .iter()
+                .filter(|function| function.is_mutating())
+                .filter_map(|function| {
+                    let types = function
+                        .inputs
+                        .iter()
+                        .map(|ty| ParamType::parse(ty))
+                        .collect::<Option<_>>()?;
+                    let name = provider.get_label(address).unwrap_or(&info.name);
+                    Some((*address, name.to_string(), function.signature(), types))
+                })
+        })
+        .collect()
+}
+
+/// Run `config.runs` sequences of `config.depth` calls. `campaign` sets up the state
+/// and then drives the run it is given.
+/// A failing sequence is shrunk by dropping its calls, and printed before being reported.
+pub fn run(config: &InvariantConfig, campaign: impl Fn(&mut InvariantRun)) {
+    let _dictionary = DictionaryGuard::activate();
+    // the calls executed until the failure, if any
+    let fails = |plan: Plan| {
+        let mut run = InvariantRun::new(config, plan);
+        let res = quietly(|| panic::catch_unwind(AssertUnwindSafe(|| campaign(&mut run))));
+        res.err().map(|_| run.executed)
+    };
+
+    let mut rng = Rng::new(config.seed);
+    for run in 0..config.runs {
+        let plan = Plan::Generate {
+            rng: Rng::new(rng.next_u64()),
+            depth: config.depth,
+        };
+        if let Some(calls) = fails(plan) {
+            let original = calls.len();
+            let calls = shrink(calls, |calls| fails(Plan::Replay(calls.to_vec())));
+            let listed: Vec<String> = calls
+                .iter()
+                .enumerate()
+                .map(|(i, call)| format!("  {}. {call}", i + 1))
+                .collect();
+            eprintln!(
+                "invariant broken in run {} (seed: {}) after {} calls, shrunk from {original}:\n{}",
+                run + 1,
+                config.seed,
+                calls.len(),
+                listed.join("\n")
+            );
+
+            let mut replay = InvariantRun::new(config, Plan::Replay(calls));
+            match panic::catch_unwind(AssertUnwindSafe(|| campaign(&mut replay))) {
+                Err(err) => panic::resume_unwind(err),
+                Ok(()) => {
+                    panic!("invariant held when the calls were replayed, is it deterministic ?")
+                }
+            }
+        }
+    }
+}
+
+/// Drop the calls which are not needed to break the invariants.
+/// `fails` returns the calls executed until the failure, if the sequence still fails.
+pub fn shrink(mut calls: Vec<Call>, fails: impl Fn(&[Call]) -> Option<Vec<Call>>) -> Vec<Call> {
+    let mut budget = MAX_SHRINK_RUNS;
+    let mut i = 0;
+    while i < calls.len() && budget > 0 {
+        budget -= 1;
+        let mut candidate = calls.clone();
+        candidate.remove(i);
+        match fails(&candidate) {
+            // the calls after the failure are dropped as well
+            Some(executed) => calls = executed,
+            None => i += 1,
+        }
+    }
+    calls
+}
@@ -0,0 +1,275 @@
+mod corpus;
+pub mod dictionary;
+mod gen;
+pub mod invariant;
+#[cfg(feature = "proptest")]
+pub mod strategy;
+
+pub use corpus::Corpus;
+pub use gen::{generate_abi, Fuzz, Rng};
+
+use dictionary::DictionaryGuard;
+
+use std::{
+    cell::Cell,
+    env,
+    panic::{self, AssertUnwindSafe},
+    str::FromStr,
+    sync::Once,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Upper bound of the executions spent shrinking a failing input.
+const MAX_SHRINK_RUNS: u32 = 4096;
+
+/// How a fuzz test is run, `RUSTRY_FUZZ_RUNS` and `RUSTRY_FUZZ_SEED` override the defaults.
+#[derive(Debug, Clone)]
+pub struct FuzzConfig {
+    /// Number of generated inputs
+    pub runs: u32,
+    /// Seed of the generator, printed on failure to replay it
+    pub seed: u64,
+    /// Name of the test, under which the failures are saved and replayed
+    pub name: Option<String>,
+}
+
+impl Default for FuzzConfig {
+    fn default() -> Self {
+        FuzzConfig {
+            runs: env_var("RUSTRY_FUZZ_RUNS").unwrap_or(256),
+            seed: default_seed(),
+            name: None,
+        }
+    }
+}
+
+impl FuzzConfig {
+    /// Apply `RUSTRY_FUZZ_RUNS` and `RUSTRY_FUZZ_SEED` again, over the settings of the test.
+    pub fn override_from_env(&mut self) {
+        if let Some(runs) = env_var("RUSTRY_FUZZ_RUNS") {
+            self.runs = runs;
+        }
+        if let Some(seed) = env_var("RUSTRY_FUZZ_SEED") {
+            self.seed = seed;
+        }
+    }
+}
+
+fn env_var<T: FromStr>(name: &str) -> Option<T> {
+    env::var(name).ok().and_then(|value| value.parse().ok())
+}
+
+/// `RUSTRY_FUZZ_SEED`, or a different one each time.
+fn default_seed() -> u64 {
+    env_var("RUSTRY_FUZZ_SEED").unwrap_or_else(|| {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|time| time.as_nanos() as u64)
+            .unwrap_or_default()
+    })
+}
+
+/// Run `test` with `config.runs` generated inputs, mixing in the values met during the executions.
+/// The saved failures are replayed first, and a new failure is shrunk and saved before being reported.
+pub fn run<T: Fuzz>(config: &FuzzConfig, test: impl Fn(T)) {
+    let _dictionary = DictionaryGuard::activate();
+    let fails = |input: &T| {
+        quietly(|| panic::catch_unwind(AssertUnwindSafe(|| test(input.clone())))).is_err()
+    };
+    let corpus = config.name.as_deref().map(Corpus::new);
+
+    if let Some(corpus) = &corpus {
+        for input in corpus.load::<T>() {
+            if fails(&input) {
+                let context = format!("failed on {}", corpus.path().display());
+                report(&test, input, &context);
+            }
+        }
+    }
+
+    let mut rng = Rng::new(config.seed);
+    for run in 0..config.runs {
+        let input = T::generate(&mut rng);
+        if fails(&input) {
+            let original = input.describe();
+            let input = shrink(input, fails);
+            if let Some(corpus) = &corpus {
+                if let Err(err) = corpus.save(&input) {
+                    eprintln!("could not save the failure: {err}");
+                }
+            }
+            let context = format!(
+                "failed after {} runs (seed: {})\n  shrunk from: {original}",
+                run + 1,
+                config.seed
+            );
+            report(&test, input, &context);
+        }
+    }
+}
+
+/// Simplest input still making the test fail.
+pub fn shrink<T: Fuzz>(mut input: T, fails: impl Fn(&T) -> bool) -> T {
+    let mut budget = MAX_SHRINK_RUNS;
+    'shrink: while budget > 0 {
+        for candidate in input.shrink() {
+            if budget == 0 {
+                break 'shrink;
+            }
+            budget -= 1;
+            if fails(&candidate) {
+                input = candidate;
+                continue 'shrink;
+            }
+        }
+        break;
+    }
+    input
+}
+
+/// Run the failing input again, with the panic message this time.
+fn report<T: Fuzz>(test: &impl Fn(T), input: T, context: &str) -> ! {
+    let description = input.describe();
+    let res = panic::catch_unwind(AssertUnwindSafe(|| test(input)));
+    eprintln!("fuzz test {context}\n  input: {description}");
+    match res {
+        Err(err) => panic::resume_unwind(err),
+        Ok(()) => {
+            panic!("fuzz test passed when run again with {description}, is it deterministic ?")
+        }
+    }
+}
+
+thread_local! {
+    static QUIET: Cell<bool> = Cell::new(false);
+}
+
+/// If the panics are not printed, e.g. while looking for a failing input.
+pub(crate) fn is_quiet() -> bool {
+    QUIET.with(Cell::get)
+}
+
+/// Run `f` without printing its panics, the other threads keep printing theirs.
+fn quietly<R>(f: impl FnOnce() -> R) -> R {
+    static HOOK: Once = Once::new();
+    HOOK.call_once(|| {
+        let default = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            if !is_quiet() {
+                default(info)
+            }
+        }));
+    });
+
+    QUIET.with(|quiet| quiet.set(true));
+    let res = f();
+    QUIET.with(|quiet| quiet.set(false));
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{run, shrink, Corpus, Fuzz, FuzzConfig, Rng};
+    use revm::primitives::{Address, Bytes, U256};
+    use std::{cell::Cell, fs};
+
+    #[test]
+    fn reproducible() {
+        let inputs = |seed| {
+            let mut rng = Rng::new(seed);
+            <Vec<(U256, Address, bool)>>::generate(&mut rng)
+        };
+        assert_eq!(inputs(42), inputs(42));
+        assert_ne!(inputs(42), inputs(43));
+    }
+
+    #[test]
+    fn runs() {
+        let count = Cell::new(0);
+        let config = FuzzConfig {
+            runs: 100,
+            seed: 1,
+            name: None,
+        };
+        run(&config, |(_, _): (U256, [bool; 3])| {
+            count.set(count.get() + 1)
+        });
+        assert_eq!(count.get(), 100);
+    }
+
+    #[test]
+    #[should_panic(expected = "too big")]
+    fn finds_failure() {
+        let config = FuzzConfig {
+            runs: 1000,
+            seed: 1,
+            name: None,
+        };
+        run(&config, |(x,): (U256,)| {
+            assert!(x < U256::from(1u128 << 100), "too big");
+        });
+    }
+
+    #[test]
+    fn shrinks() {
+        let input = (
+            U256::MAX,
+            Address::repeat_byte(0xff),
+            Bytes::from(vec![0xff; 40]),
+            true,
+        );
+        let shrunk = shrink(input, |(x, _, bytes, _)| {
+            *x >= U256::from(1000) && bytes.len() >= 3
+        });
+        assert_eq!(
+            shrunk,
+            (
+                U256::from(1000),
+                Address::ZERO,
+                Bytes::from(vec![0; 3]),
+                false
+            )
+        );
+
+        let shrunk = shrink(vec![-50i64, 7, 300], |values| {
+            values.iter().any(|value| *value <= -10)
+        });
+        assert_eq!(shrunk, vec![-10]);
+    }
+
+    #[test]
+    fn serialize() {
+        let mut rng = Rng::new(3);
+        for _ in 0..100 {
+            let input = <(U256, Vec<Address>, [bool; 2], Bytes, String, i32)>::generate(&mut rng);
+            let mut bytes = Vec::new();
+            input.serialize(&mut bytes);
+            let mut data = bytes.as_slice();
+            assert_eq!(Fuzz::deserialize(&mut data), Some(input));
+            assert!(data.is_empty());
+        }
+    }
+
+    #[test]
+    fn replays_corpus() {
+        let name = "rustry_test::fuzz::tests::replays_corpus";
+        let corpus = Corpus::new(name);
+        let _ = fs::remove_file(corpus.path());
+        let config = FuzzConfig {
+            runs: 1000,
+            seed: 1,
+            name: Some(name.to_string()),
+        };
+        let test = |(x,): (U256,)| assert!(x < U256::from(1u128 << 100));
+
+        let res = std::panic::catch_unwind(|| run(&config, test));
+        assert!(res.is_err());
+        let saved = corpus.load::<(U256,)>();
+        assert_eq!(saved, vec![(U256::from(1u128 << 100),)]);
+
+        // found without generating anything
+        let replay = FuzzConfig { runs: 0, ..config };
+        assert!(std::panic::catch_unwind(|| run(&replay, test)).is_err());
+        fs::remove_file(corpus.path()).unwrap();
+    }
+}
@@ -0,0 +1,165 @@
+//! proptest strategies of the EVM types, to compose with the existing ones.
+
+use crate::{
+    compilers::solidity::solc::{AbiEntry, EntryUtils},
+    utils::{
+        abi::get_sig,
+        codec::{encode, AbiValue, ParamType},
+    },
+};
+use proptest::{collection, prelude::*, sample};
+use revm::primitives::{alloy_primitives::I256, Address, Bytes, U256};
+
+/// Keeps the `bits` lowest bits.
+fn mask(bits: usize) -> U256 {
+    if bits >= 256 {
+        U256::MAX
+    } else {
+        (U256::from(1) << bits) - U256::from(1)
+    }
+}
+
+/// Any `U256`, the edge values being as likely as the others.
+pub fn u256() -> impl Strategy<Value = U256> {
+    prop_oneof![
+        any::<U256>(),
+        Just(U256::ZERO),
+        Just(U256::MAX),
+        (0..256usize).prop_map(|bit| U256::from(1) << bit),
+        (1..256usize).prop_map(|bit| (U256::from(1) << bit) - U256::from(1)),
+    ]
+}
+
+/// `uint<bits>`, e.g. 8 for a `uint8`.
+pub fn uint(bits: usize) -> impl Strategy<Value = U256> {
+    assert!((1..=256).contains(&bits), "invalid size: {bits} bits");
+    u256().prop_map(move |value| value & mask(bits))
+}
+
+/// In `low..=high`, shrinking towards `low`.
+pub fn u256_in(low: U256, high: U256) -> impl Strategy<Value = U256> {
+    assert!(low <= high, "empty range");
+    let span = high - low;
+    u256().prop_map(move |value| {
+        if span == U256::MAX {
+            value
+        } else {
+            low + value % (span + U256::from(1))
+        }
+    })
+}
+
+/// `int<bits>`, e.g. 8 for an `int8`.
+pub fn int(bits: usize) -> impl Strategy<Value = I256> {
+    assert!((1..=256).contains(&bits), "invalid size: {bits} bits");
+    u256().prop_map(move |value| {
+        let mut raw = value & mask(bits);
+        if bits < 256 && raw.bit(bits - 1) {
+            raw |= !mask(bits);
+        }
+        I256::from_raw(raw)
+    })
+}
+
+/// One of the addresses of `pool`, e.g. the senders of a test.
+pub fn address_in(pool: Vec<Address>) -> impl Strategy<Value = Address> {
+    sample::select(pool)
+}
+
+/// At most `max_len` bytes long.
+pub fn bytes(max_len: usize) -> impl Strategy<Value = Bytes> {
+    collection::vec(any::<u8>(), 0..=max_len).prop_map(Bytes::from)
+}
+
+/// Values of an ABI type.
+pub fn abi_value(ty: &ParamType) -> BoxedStrategy<AbiValue> {
+    match ty {
+        ParamType::Uint(bits) => uint(*bits).prop_map(AbiValue::Uint).boxed(),
+        ParamType::Int(bits) => int(*bits).prop_map(AbiValue::Int).boxed(),
+        ParamType::Address => any::<Address>().prop_map(AbiValue::Address).boxed(),
+        ParamType::Bool => any::<bool>().prop_map(AbiValue::Bool).boxed(),
+        ParamType::FixedBytes(len) => collection::vec(any::<u8>(), *len)
+            .prop_map(AbiValue::FixedBytes)
+            .boxed(),
+        ParamType::Bytes => bytes(128).prop_map(AbiValue::Bytes).boxed(),
+        ParamType::String => any::<String>().prop_map(AbiValue::String).boxed(),
+        ParamType::Array(elem) => collection::vec(abi_value(elem), 0..8)
+            .prop_map(AbiValue::Array)
+            .boxed(),
+        ParamType::FixedArray(elem, len) => collection::vec(abi_value(elem), *len)
+            .prop_map(AbiValue::FixedArray)
+            .boxed(),
+        ParamType::Tuple(types) => abi_values(types).prop_map(AbiValue::Tuple).boxed(),
+    }
+}
+
+/// Values of a list of ABI types, e.g. the inputs of a function.
+pub fn abi_values(types: &[ParamType]) -> BoxedStrategy<Vec<AbiValue>> {
+    types.iter().map(abi_value).collect::<Vec<_>>().boxed()
+}
+
+/// Calldata of the function, with generated arguments.
+pub fn strategy_for(entry: &AbiEntry) -> BoxedStrategy<Bytes> {
+    let types: Vec<ParamType> = entry
+        .inputs
+        .iter()
+        .map(|input| {
+            let ty = input.canonical_type();
+            ParamType::parse(&ty).unwrap_or_else(|| panic!("unsupported ABI type {ty}"))
+        })
+        .collect();
+    let selector = get_sig(&entry.signature());
+    abi_values(&types)
+        .prop_map(move |values| [selector.as_slice(), &encode(&values)].concat().into())
+        .boxed()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{int, strategy_for, u256_in};
+    use crate::{
+        compilers::solidity::solc::AbiEntry,
+        utils::{
+            abi::get_sig,
+            codec::{decode, ParamType},
+        },
+    };
+    use proptest::prelude::*;
+    use revm::primitives::{Bytes, U256};
+
+    fn transfer() -> BoxedStrategy<Bytes> {
+        let entry: AbiEntry = serde_json::from_str(
+            r#"{
+                "type": "function",
+                "name": "transfer",
+                "inputs": [
+                    { "name": "to", "type": "address", "internalType": "address" },
+                    { "name": "amounts", "type": "uint8[]", "internalType": "uint8[]" }
+                ],
+                "outputs": [],
+                "stateMutability": "nonpayable"
+            }"#,
+        )
+        .unwrap();
+        strategy_for(&entry)
+    }
+
+    proptest! {
+        #[test]
+        fn calldata(data in transfer()) {
+            let selector = get_sig("transfer(address,uint8[])");
+            prop_assert_eq!(&data[..4], selector.as_slice());
+            let types = [
+                ParamType::Address,
+                ParamType::Array(Box::new(ParamType::Uint(8))),
+            ];
+            prop_assert!(decode(&types, &data[4..]).is_some());
+        }
+
+        #[test]
+        fn bounded(x in u256_in(U256::from(10), U256::from(20)), y in int(8)) {
+            prop_assert!(x >= U256::from(10) && x <= U256::from(20));
+            prop_assert!(y.unsigned_abs() <= U256::from(128));
+        }
+    }
+}
@@ -0,0 +1,8 @@
+mod profile;
+mod report;
+mod snapshot;
+
+pub use profile::{GasProfile, GasProfiler, ProfileFrame};
+pub use report::{is_enabled, write_report, GasReport, GasStats, ReportGuard};
+pub(crate) use snapshot::record_gas;
+pub use snapshot::{check_snapshot, measure, snapshot, SnapshotMode, SNAPSHOT_FILE};
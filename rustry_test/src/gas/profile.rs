@@ -0,0 +1,203 @@
+//! Gas spent by the instructions, by stack of calls and internal functions, as folded stacks for
+//! the flamegraph tools, e.g. `inferno-flamegraph < profile.folded > profile.svg`.
+
+use crate::{common::contract::ContractInfo, compilers::solidity::source_map::InternalFunction};
+use revm::{
+    interpreter::{opcode, Interpreter},
+    primitives::{hex, Address, B256},
+};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Write as _,
+    fs, io,
+    path::Path,
+};
+
+/// A frame of the folded stacks, before the contracts are named.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ProfileFrame {
+    Call {
+        /// Where the code was loaded from
+        code_address: Address,
+        /// The first bytes of the input
+        selector: Vec<u8>,
+    },
+    Create,
+    Internal(String),
+    Op(u8),
+}
+
+#[derive(Debug)]
+struct InternalCall {
+    name: String,
+    /// Where it jumps back to
+    return_pc: usize,
+    /// Of the stack once returned
+    return_height: usize,
+}
+
+#[derive(Debug)]
+struct ActiveFrame {
+    frame: ProfileFrame,
+    /// The innermost last
+    internal_calls: Vec<InternalCall>,
+    /// The stack and the remaining gas of the step not ended yet
+    pending: Option<(Vec<ProfileFrame>, u64)>,
+    /// Spent by the call made by the pending step
+    child_gas: u64,
+}
+
+/// Attributes the gas of each step through the inspector hooks.
+#[derive(Debug, Default)]
+pub struct GasProfiler {
+    /// Of the registered contracts, by code hash and entry point
+    functions: HashMap<B256, HashMap<usize, InternalFunction>>,
+    /// The calls not ended yet, the innermost last
+    active: Vec<ActiveFrame>,
+    stacks: HashMap<Vec<ProfileFrame>, u64>,
+}
+
+impl GasProfiler {
+    /// Follow the internal functions of the code.
+    pub fn add_functions(&mut self, code_hash: B256, functions: &[InternalFunction]) {
+        self.functions.entry(code_hash).or_insert_with(|| {
+            functions
+                .iter()
+                .map(|function| (function.entry_point, function.clone()))
+                .collect()
+        });
+    }
+
+    pub fn start(&mut self, frame: ProfileFrame) {
+        self.active.push(ActiveFrame {
+            frame,
+            internal_calls: Vec::new(),
+            pending: None,
+            child_gas: 0,
+        });
+    }
+
+    /// `gas_spent` by the call, refunds excluded.
+    pub fn end(&mut self, gas_spent: u64) {
+        self.active.pop();
+        if let Some(parent) = self.active.last_mut() {
+            parent.child_gas += gas_spent;
+        }
+    }
+
+    pub fn step(&mut self, interp: &Interpreter) {
+        let Some(active) = self.active.last_mut() else {
+            return;
+        };
+        let pc = interp.program_counter();
+        let height = interp.stack.len();
+        while active
+            .internal_calls
+            .last()
+            .is_some_and(|call| call.return_pc == pc && call.return_height == height)
+        {
+            active.internal_calls.pop();
+        }
+        // the arguments are above the return address, the latter being consumed by the return
+        let entered = self
+            .functions
+            .get(&interp.contract.hash)
+            .and_then(|functions| functions.get(&pc))
+            .filter(|_| interp.current_opcode() == opcode::JUMPDEST);
+        if let Some(function) = entered {
+            if let Ok(return_pc) = interp.stack.peek(function.parameter_slots) {
+                active.internal_calls.push(InternalCall {
+                    name: function.name.clone(),
+                    return_pc: return_pc.saturating_to(),
+                    return_height: (height + function.return_slots)
+                        .saturating_sub(function.parameter_slots + 1),
+                });
+            }
+        }
+
+        let mut stack = Vec::new();
+        for frame in &self.active {
+            stack.push(frame.frame.clone());
+            stack.extend(
+                frame
+                    .internal_calls
+                    .iter()
+                    .map(|call| ProfileFrame::Internal(call.name.clone())),
+            );
+        }
+        stack.push(ProfileFrame::Op(interp.current_opcode()));
+        let active = self.active.last_mut().expect("no active frame");
+        active.pending = Some((stack, interp.gas.remaining()));
+        active.child_gas = 0;
+    }
+
+    pub fn step_end(&mut self, interp: &Interpreter) {
+        let Some(active) = self.active.last_mut() else {
+            return;
+        };
+        if let Some((stack, gas)) = active.pending.take() {
+            let cost = gas
+                .saturating_sub(interp.gas.remaining())
+                .saturating_sub(active.child_gas);
+            *self.stacks.entry(stack).or_default() += cost;
+        }
+    }
+
+    /// The gas per stack so far, with the contracts named after the registered ones.
+    pub fn profile(&self, contracts: &BTreeMap<Address, ContractInfo>) -> GasProfile {
+        let mut stacks = BTreeMap::new();
+        for (frames, gas) in &self.stacks {
+            let names: Vec<String> = frames
+                .iter()
+                .map(|frame| match frame {
+                    ProfileFrame::Call {
+                        code_address,
+                        selector,
+                    } => {
+                        let info = contracts.get(code_address);
+                        let contract =
+                            info.map_or_else(|| code_address.to_string(), |info| info.name.clone());
+                        let function = match info.and_then(|info| info.function(selector)) {
+                            Some(function) => function.name.clone(),
+                            None if selector.len() < 4 => "fallback".to_string(),
+                            None => format!("0x{}", hex::encode(&selector[..4])),
+                        };
+                        format!("{contract}::{function}")
+                    }
+                    ProfileFrame::Create => "create".to_string(),
+                    ProfileFrame::Internal(name) => name.clone(),
+                    ProfileFrame::Op(op) => opcode::OPCODE_JUMPMAP[*op as usize]
+                        .map_or_else(|| format!("opcode {op:#04x}"), ToString::to_string),
+                })
+                .collect();
+            *stacks.entry(names.join(";")).or_default() += gas;
+        }
+        GasProfile { stacks }
+    }
+}
+
+/// Gas spent by the instructions, by stack of frames from the transaction to the instruction.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GasProfile {
+    /// The frames joined with `;`
+    pub stacks: BTreeMap<String, u64>,
+}
+
+impl GasProfile {
+    pub fn total(&self) -> u64 {
+        self.stacks.values().sum()
+    }
+
+    /// One `frames gas` line per stack, as read by `inferno-flamegraph` or `flamegraph.pl`.
+    pub fn to_folded(&self) -> String {
+        let mut folded = String::new();
+        for (stack, gas) in self.stacks.iter().filter(|(_, gas)| **gas > 0) {
+            let _ = writeln!(folded, "{stack} {gas}");
+        }
+        folded
+    }
+
+    pub fn write_folded(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        fs::write(path, self.to_folded())
+    }
+}
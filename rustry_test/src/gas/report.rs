@@ -0,0 +1,261 @@
+//! Gas used per function of the registered contracts, over all the tests of the binary.
+//! Recorded when `RUSTRY_GAS_REPORT` is set, and written to `target/rustry/gas-report/<binary>`.
+
+use crate::{
+    common::contract::ContractInfo,
+    provider::trace::{CallKind, CallTrace},
+    utils::{binary_name, rustry_dir},
+};
+use revm::primitives::{hex, Address};
+use serde::Serialize;
+use std::{
+    collections::BTreeMap,
+    env,
+    fmt::{self, Write},
+    fs, io,
+    path::PathBuf,
+    sync::Mutex,
+};
+
+/// The reports of the providers dropped so far.
+static REPORT: Mutex<GasReport> = Mutex::new(GasReport {
+    contracts: BTreeMap::new(),
+});
+
+/// If `RUSTRY_GAS_REPORT` is set to anything but 0.
+pub fn is_enabled() -> bool {
+    env::var("RUSTRY_GAS_REPORT").is_ok_and(|value| !value.is_empty() && value != "0")
+}
+
+/// Gas used by each call, by contract name and function signature.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GasReport {
+    contracts: BTreeMap<String, BTreeMap<String, Vec<u64>>>,
+}
+
+/// Summary of the calls of a function.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct GasStats {
+    pub calls: usize,
+    pub min: u64,
+    pub avg: u64,
+    pub median: u64,
+    pub max: u64,
+}
+
+impl GasStats {
+    /// `None` if there is no sample.
+    pub fn new(samples: &[u64]) -> Option<Self> {
+        let mut sorted = samples.to_vec();
+        sorted.sort_unstable();
+        let (min, max) = (*sorted.first()?, *sorted.last()?);
+        let mid = sorted.len() / 2;
+        let median = if sorted.len() % 2 == 0 {
+            (sorted[mid - 1] + sorted[mid]) / 2
+        } else {
+            sorted[mid]
+        };
+        let sum: u128 = sorted.iter().map(|&gas| gas as u128).sum();
+        Some(GasStats {
+            calls: sorted.len(),
+            min,
+            avg: (sum / sorted.len() as u128) as u64,
+            median,
+            max,
+        })
+    }
+}
+
+impl GasReport {
+    /// The calls of the trace into the `contracts`, at any depth.
+    /// Their gas excludes the intrinsic cost of the transaction.
+    pub fn record(&mut self, trace: &CallTrace, contracts: &BTreeMap<Address, ContractInfo>) {
+        if !trace.kind.is_create() && trace.kind != CallKind::DelegateCall {
+            if let Some(info) = contracts.get(&trace.code_address) {
+                let function = match info.function(&trace.input) {
+                    Some(function) => function.signature(),
+                    None if trace.input.len() < 4 => "fallback()".to_string(),
+                    None => format!("0x{}", hex::encode(&trace.input[..4])),
+                };
+                self.add(&info.name, &function, trace.gas_used);
+            }
+        }
+        for child in &trace.children {
+            self.record(child, contracts);
+        }
+    }
+
+    pub fn add(&mut self, contract: &str, function: &str, gas_used: u64) {
+        self.contracts
+            .entry(contract.to_string())
+            .or_default()
+            .entry(function.to_string())
+            .or_default()
+            .push(gas_used);
+    }
+
+    pub fn merge(&mut self, other: GasReport) {
+        for (contract, functions) in other.contracts {
+            for (function, samples) in functions {
+                self.contracts
+                    .entry(contract.clone())
+                    .or_default()
+                    .entry(function)
+                    .or_default()
+                    .extend(samples);
+            }
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.contracts.is_empty()
+    }
+
+    /// The stats by contract name and function signature.
+    pub fn stats(&self) -> BTreeMap<&str, BTreeMap<&str, GasStats>> {
+        self.contracts
+            .iter()
+            .map(|(contract, functions)| {
+                let functions = functions
+                    .iter()
+                    .filter_map(|(function, samples)| {
+                        Some((function.as_str(), GasStats::new(samples)?))
+                    })
+                    .collect();
+                (contract.as_str(), functions)
+            })
+            .collect()
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(&self.stats()).expect("the stats are serializable")
+    }
+
+    /// Add the report of a provider to the one of the binary.
+    pub(crate) fn submit(self) {
+        REPORT
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .merge(self);
+    }
+}
+
+/// A Markdown table, also readable in a terminal.
+impl fmt::Display for GasReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let header = [
+            "Contract", "Function", "min", "avg", "median", "max", "calls",
+        ];
+        let mut rows = vec![header.map(String::from)];
+        for (contract, functions) in self.stats() {
+            for (function, stats) in functions {
+                rows.push([
+                    contract.to_string(),
+                    function.to_string(),
+                    stats.min.to_string(),
+                    stats.avg.to_string(),
+                    stats.median.to_string(),
+                    stats.max.to_string(),
+                    stats.calls.to_string(),
+                ]);
+            }
+        }
+
+        let widths: Vec<usize> = (0..header.len())
+            .map(|column| rows.iter().map(|row| row[column].len()).max().unwrap_or(0))
+            .collect();
+        let line = |row: &[String], f: &mut fmt::Formatter<'_>| {
+            let mut out = String::from("|");
+            for (column, (cell, width)) in row.iter().zip(&widths).enumerate() {
+                // the names on the left, the numbers on the right
+                if column < 2 {
+                    write!(out, " {cell:<width$} |").unwrap();
+                } else {
+                    write!(out, " {cell:>width$} |").unwrap();
+                }
+            }
+            writeln!(f, "{out}")
+        };
+        line(&rows[0], f)?;
+        let separator: Vec<String> = widths.iter().map(|width| "-".repeat(*width)).collect();
+        line(&separator, f)?;
+        for row in &rows[1..] {
+            line(row, f)?;
+        }
+        Ok(())
+    }
+}
+
+/// `target/rustry/gas-report/<binary>`
+fn report_path() -> PathBuf {
+    rustry_dir().join("gas-report").join(binary_name())
+}
+
+/// Write the report of the binary so far, as `.json` and `.md` files.
+pub fn write_report() -> io::Result<PathBuf> {
+    // held while writing, an older report written last would miss the calls of the other tests
+    let report = REPORT.lock().unwrap_or_else(|err| err.into_inner());
+    let path = report_path();
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(path.with_extension("json"), report.to_json())?;
+    fs::write(path.with_extension("md"), report.to_string())?;
+    Ok(path)
+}
+
+/// Writes the report when a test ends, as each test may be the last one of the binary.
+pub struct ReportGuard;
+
+impl Drop for ReportGuard {
+    fn drop(&mut self) {
+        if is_enabled() {
+            if let Err(err) = write_report() {
+                eprintln!("failed to write the gas report: {err}");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{GasReport, GasStats};
+
+    #[test]
+    fn stats() {
+        assert_eq!(GasStats::new(&[]), None);
+        let stats = GasStats::new(&[30, 10, 20, 100]).unwrap();
+        assert_eq!(
+            stats,
+            GasStats {
+                calls: 4,
+                min: 10,
+                avg: 40,
+                median: 25,
+                max: 100,
+            }
+        );
+        assert_eq!(GasStats::new(&[7, 1, 3]).unwrap().median, 3);
+    }
+
+    #[test]
+    fn table() {
+        let mut report = GasReport::default();
+        report.add("Counter", "setNumber(uint256)", 22_000);
+        let mut other = GasReport::default();
+        other.add("Counter", "setNumber(uint256)", 2_000);
+        other.add("Counter", "number()", 300);
+        report.merge(other);
+
+        assert_eq!(
+            report.to_string(),
+            "\
+| Contract | Function           |  min |   avg | median |   max | calls |
+| -------- | ------------------ | ---- | ----- | ------ | ----- | ----- |
+| Counter  | number()           |  300 |   300 |    300 |   300 |     1 |
+| Counter  | setNumber(uint256) | 2000 | 12000 |  12000 | 22000 |     2 |
+"
+        );
+        assert!(report.to_json().contains(r#""median": 12000"#));
+    }
+}
@@ -0,0 +1,198 @@
+//! Gas used by a block of a test, kept in a `.gas-snapshot` file next to the crate to review the changes.
+//! With `RUSTRY_GAS_CHECK` set, the snapshots are not updated and a test using more gas than its
+//! snapshot, plus `RUSTRY_GAS_TOLERANCE` percent, fails.
+
+use std::{
+    cell::RefCell,
+    collections::BTreeMap,
+    env,
+    fmt::Write,
+    fs, io,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+pub const SNAPSHOT_FILE: &str = ".gas-snapshot";
+
+thread_local! {
+    /// Gas used since each of the running measures started, the innermost last
+    static METERS: RefCell<Vec<u64>> = RefCell::new(Vec::new());
+}
+
+/// Serializes the updates of the snapshot files by the tests running in parallel.
+static FILES: Mutex<()> = Mutex::new(());
+
+/// Add the gas of a transaction to the running measures.
+pub(crate) fn record_gas(gas_used: u64) {
+    METERS.with(|meters| {
+        meters
+            .borrow_mut()
+            .iter_mut()
+            .for_each(|meter| *meter += gas_used)
+    });
+}
+
+struct MeterGuard;
+
+impl Drop for MeterGuard {
+    fn drop(&mut self) {
+        METERS.with(|meters| meters.borrow_mut().pop());
+    }
+}
+
+/// Run `f`, and the gas used by the transactions it made on this thread.
+pub fn measure<R>(f: impl FnOnce() -> R) -> (R, u64) {
+    METERS.with(|meters| meters.borrow_mut().push(0));
+    let guard = MeterGuard;
+    let res = f();
+    let gas_used = METERS.with(|meters| *meters.borrow().last().expect("the meter was pushed"));
+    drop(guard);
+    (res, gas_used)
+}
+
+/// What to do with a measure.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SnapshotMode {
+    /// Write it to the snapshot file
+    Record,
+    /// Fail if it is above the saved one by more than `tolerance` percent
+    Check { tolerance: f64 },
+}
+
+impl SnapshotMode {
+    /// `Check` if `RUSTRY_GAS_CHECK` is set to anything but 0, with `RUSTRY_GAS_TOLERANCE` or 0.
+    pub fn from_env() -> Self {
+        let check =
+            env::var("RUSTRY_GAS_CHECK").is_ok_and(|value| !value.is_empty() && value != "0");
+        if !check {
+            return SnapshotMode::Record;
+        }
+        let tolerance = env::var("RUSTRY_GAS_TOLERANCE")
+            .ok()
+            .and_then(|tolerance| tolerance.parse().ok())
+            .unwrap_or(0.0);
+        SnapshotMode::Check { tolerance }
+    }
+}
+
+/// Lines of `name (gas: 1234)`, sorted by name.
+fn load(path: &Path) -> BTreeMap<String, u64> {
+    let content = fs::read_to_string(path).unwrap_or_default();
+    content
+        .lines()
+        .filter_map(|line| {
+            let (name, gas) = line.strip_suffix(')')?.rsplit_once(" (gas: ")?;
+            Some((name.to_string(), gas.parse().ok()?))
+        })
+        .collect()
+}
+
+fn save(path: &Path, snapshots: &BTreeMap<String, u64>) -> io::Result<()> {
+    let mut content = String::new();
+    for (name, gas) in snapshots {
+        writeln!(content, "{name} (gas: {gas})").unwrap();
+    }
+    fs::write(path, content)
+}
+
+fn change(old: u64, new: u64) -> String {
+    let percent = (new as f64 - old as f64) / old.max(1) as f64 * 100.0;
+    format!("{old} -> {new} ({percent:+.2}%)")
+}
+
+/// Record or check the measure of `name` in the snapshot file at `path`.
+pub fn check_snapshot(path: &Path, name: &str, gas_used: u64, mode: SnapshotMode) {
+    let _lock = FILES.lock().unwrap_or_else(|err| err.into_inner());
+    let mut snapshots = load(path);
+    let saved = snapshots.get(name).copied();
+    match (mode, saved) {
+        (SnapshotMode::Check { .. }, None) => panic!(
+            "no gas snapshot of `{name}` in {}, record it without RUSTRY_GAS_CHECK",
+            path.display()
+        ),
+        (SnapshotMode::Check { tolerance }, Some(saved)) => {
+            let limit = saved as f64 * (1.0 + tolerance / 100.0);
+            if gas_used as f64 > limit {
+                panic!(
+                    "gas snapshot `{name}` regressed: {} (tolerance: {tolerance}%)",
+                    change(saved, gas_used)
+                );
+            }
+        }
+        (SnapshotMode::Record, _) => {
+            if let Some(saved) = saved.filter(|saved| *saved != gas_used) {
+                eprintln!("gas snapshot `{name}`: {}", change(saved, gas_used));
+            }
+            snapshots.insert(name.to_string(), gas_used);
+            if let Err(err) = save(path, &snapshots) {
+                eprintln!("failed to write {}: {err}", path.display());
+            }
+        }
+    }
+}
+
+/// Measure `f` and record or check it in the `.gas-snapshot` of `crate_dir`, see `gas_snapshot!`.
+pub fn snapshot<R>(crate_dir: &str, name: &str, f: impl FnOnce() -> R) -> R {
+    let (res, gas_used) = measure(f);
+    let path = PathBuf::from(crate_dir).join(SNAPSHOT_FILE);
+    check_snapshot(&path, name, gas_used, SnapshotMode::from_env());
+    res
+}
+
+/// Snapshot the gas used by the transactions of the closure, which is called right away.
+///
+/// # Examples
+///
+/// ```ignore
+/// let number = gas_snapshot!("increment", || {
+///     counter.methods.increment(&mut provider);
+///     counter.methods.number(&mut provider)
+/// });
+/// ```
+#[macro_export]
+macro_rules! gas_snapshot {
+    ($name:expr, $f:expr $(,)?) => {
+        $crate::gas::snapshot(env!("CARGO_MANIFEST_DIR"), $name, $f)
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check_snapshot, load, measure, record_gas, SnapshotMode};
+    use std::{fs, panic};
+
+    #[test]
+    fn nested_measures() {
+        record_gas(1);
+        let ((_, inner), outer) = measure(|| {
+            record_gas(10);
+            measure(|| record_gas(100))
+        });
+        assert_eq!((inner, outer), (100, 110));
+    }
+
+    #[test]
+    fn record_and_check() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(".gas-snapshot");
+        check_snapshot(&path, "transfer", 1000, SnapshotMode::Record);
+        check_snapshot(&path, "mint", 500, SnapshotMode::Record);
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "mint (gas: 500)\ntransfer (gas: 1000)\n"
+        );
+
+        let check = SnapshotMode::Check { tolerance: 5.0 };
+        check_snapshot(&path, "transfer", 1040, check);
+        check_snapshot(&path, "transfer", 900, check);
+        assert_eq!(load(&path)["transfer"], 1000);
+
+        let regressed = panic::catch_unwind(|| check_snapshot(&path, "transfer", 1060, check));
+        let message = *regressed.unwrap_err().downcast::<String>().unwrap();
+        assert_eq!(
+            message,
+            "gas snapshot `transfer` regressed: 1000 -> 1060 (+6.00%) (tolerance: 5%)"
+        );
+        assert!(panic::catch_unwind(|| check_snapshot(&path, "burn", 1, check)).is_err());
+    }
+}
@@ -2,6 +2,9 @@
 
 pub mod common;
 pub mod compilers;
+pub mod coverage;
+pub mod fuzz;
+pub mod gas;
 pub mod prelude;
 pub mod provider;
 pub mod utils;
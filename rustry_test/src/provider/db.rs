@@ -1,54 +1,310 @@
+use super::{
+    deal::{BalanceSlot, Packing, SlotProbe},
+    debugger::{DebugRecorder, Debugger, LastTx, PreState},
+    geth::{CallFrame, StructLogTrace},
+    inspector::ProviderInspector,
+    stack_trace::{RevertFrame, StackFrame, StackTrace},
+    state_diff::StateDiff,
+    trace::{CallTrace, TraceRenderer, Tracer, Verbosity},
+};
+use crate::{
+    common::{
+        assertions::{check_calls, ExpectedCall},
+        contract::ContractInfo,
+        labels::{self, Labeled},
+        revert::RevertReason,
+    },
+    coverage::{self, CoverageReport, CoveredCode, HitRecorder},
+    fuzz::{self, dictionary},
+    gas::{self, GasProfile, GasProfiler, GasReport},
+    utils::{
+        abi::get_sig,
+        codec::{encode, AbiValue},
+        slots::{load_packed, store_packed},
+    },
+};
 use revm::{
-    db::{CacheDB, EmptyDB},
+    db::{CacheDB, DatabaseRef, EmptyDB},
     primitives::{
-        alloy_primitives::Uint, AccountInfo, Address, Bytes, Env, ExecutionResult, Halt, Output,
-        TransactTo, U256,
+        alloy_primitives::Uint, hex, AccountInfo, Address, Bytecode, Bytes, Env, ExecutionResult,
+        Halt, Log, Output, ResultAndState, TransactTo, B256, KECCAK_EMPTY, U256,
     },
-    EVM,
+    DatabaseCommit, EVM,
+};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt, io,
 };
 
 pub struct Provider {
     evm: EVM<CacheDB<EmptyDB>>,
     /// The active sender
     pub sender: Address,
+    /// Logs of the committed transactions, if they are being recorded
+    recorded_logs: Option<Vec<Log>>,
+    inspector: ProviderInspector,
+    /// Contracts deployed through their bindings
+    contracts: BTreeMap<Address, ContractInfo>,
+    /// Tracing is on if set
+    verbosity: Option<Verbosity>,
+    traces: Vec<CallTrace>,
+    labels: HashMap<Address, String>,
+    /// Steps of the last transaction, if they are being recorded
+    struct_log_trace: Option<StructLogTrace>,
+    /// Gas used by the calls to the registered contracts, if `RUSTRY_GAS_REPORT` is set
+    gas_report: Option<GasReport>,
+    /// To execute the last transaction again in the debugger, if it is being recorded
+    last_tx: Option<LastTx>,
+    record_last_tx: bool,
+    /// Changes of the committed transactions, if they are being recorded
+    state_diffs: Option<Vec<StateDiff>>,
+    /// Where the tokens dealt keep the balances
+    balance_slots: HashMap<Address, (BalanceSlot, Packing)>,
+    /// The balances of the tokens not hashing the holder, by token and holder
+    holder_slots: HashMap<(Address, Address), (U256, Packing)>,
+    /// Where the tokens dealt keep the total supply
+    supply_slots: HashMap<Address, (U256, Packing)>,
+    /// The accounts without code given a `STOP` to be mocked
+    mocked_accounts: Vec<Address>,
+    /// Calls to verify, see `expect_call`
+    expected_calls: Vec<ExpectedCall>,
+    /// Call trees of the transactions since the calls are expected
+    observed_calls: Vec<CallTrace>,
 }
 
 impl Default for Provider {
     fn default() -> Self {
         let mut evm = EVM::new();
         evm.database(CacheDB::new(EmptyDB::default()));
+        let verbosity = Verbosity::from_env();
+        let gas_report = gas::is_enabled().then(GasReport::default);
         Provider {
             evm,
             sender: Address::ZERO,
+            recorded_logs: None,
+            inspector: ProviderInspector {
+                tracer: (verbosity.is_some() || gas_report.is_some()).then(Tracer::default),
+                coverage: coverage::is_enabled().then(HitRecorder::default),
+                ..Default::default()
+            },
+            contracts: BTreeMap::new(),
+            verbosity,
+            traces: Vec::new(),
+            labels: HashMap::new(),
+            struct_log_trace: None,
+            gas_report,
+            last_tx: None,
+            record_last_tx: false,
+            state_diffs: None,
+            balance_slots: HashMap::new(),
+            holder_slots: HashMap::new(),
+            supply_slots: HashMap::new(),
+            mocked_accounts: Vec::new(),
+            expected_calls: Vec::new(),
+            observed_calls: Vec::new(),
         }
     }
 }
 
+/// What happened during the execution, on top of the returned data.
+#[derive(Default, Clone)]
+pub struct Receipt {
+    /// Logs emitted, always empty if the execution was not successful
+    pub logs: Vec<Log>,
+    pub gas_used: u64,
+    pub gas_refunded: u64,
+    /// Address of the contract, if one was deployed
+    pub created_address: Option<Address>,
+    /// Where the calls were, if the execution failed
+    pub stack_trace: Option<StackTrace>,
+}
+
+impl fmt::Debug for Receipt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let logs: Vec<_> = self
+            .logs
+            .iter()
+            .map(|log| (Labeled(log.address), &log.topics, &log.data))
+            .collect();
+        f.debug_struct("Receipt")
+            .field("logs", &logs)
+            .field("gas_used", &self.gas_used)
+            .field("gas_refunded", &self.gas_refunded)
+            .field("created_address", &self.created_address.map(Labeled))
+            .field("stack_trace", &self.stack_trace)
+            .finish()
+    }
+}
+
 #[derive(Debug)]
 pub enum ExecRes {
-    Success(Bytes),
-    Revert(Bytes),
-    Halt(Halt),
+    Success(Bytes, Receipt),
+    Revert(Bytes, Receipt),
+    Halt(Halt, Receipt),
 }
 
 impl ExecRes {
     pub fn is_success(&self) -> bool {
-        matches!(self, ExecRes::Success(_))
+        matches!(self, ExecRes::Success(..))
     }
 
+    #[track_caller]
     pub fn success(&self) {
-        assert!(self.is_success());
+        let stack_trace = match &self.receipt().stack_trace {
+            Some(stack_trace) => format!("\n{stack_trace}"),
+            None => String::new(),
+        };
+        match self {
+            ExecRes::Success(..) => (),
+            ExecRes::Revert(data, _) => {
+                panic!("call reverted: {}{stack_trace}", RevertReason::decode(data))
+            }
+            ExecRes::Halt(reason, _) => panic!("call halted: {reason:?}{stack_trace}"),
+        }
+    }
+
+    /// Decoded revert data, `None` if the call did not revert.
+    pub fn revert_reason(&self) -> Option<RevertReason> {
+        match self {
+            ExecRes::Revert(data, _) => Some(RevertReason::decode(data)),
+            _ => None,
+        }
     }
 
     pub fn get_data(&self) -> &Bytes {
         match self {
-            ExecRes::Success(data) => data,
-            ExecRes::Revert(data) => data,
-            ExecRes::Halt(reason) => panic!("{:?}", reason),
+            ExecRes::Success(data, _) => data,
+            ExecRes::Revert(data, _) => data,
+            ExecRes::Halt(reason, _) => panic!("{:?}", reason),
+        }
+    }
+
+    pub fn receipt(&self) -> &Receipt {
+        match self {
+            ExecRes::Success(_, receipt)
+            | ExecRes::Revert(_, receipt)
+            | ExecRes::Halt(_, receipt) => receipt,
+        }
+    }
+
+    fn receipt_mut(&mut self) -> &mut Receipt {
+        match self {
+            ExecRes::Success(_, receipt)
+            | ExecRes::Revert(_, receipt)
+            | ExecRes::Halt(_, receipt) => receipt,
+        }
+    }
+
+    pub fn logs(&self) -> &[Log] {
+        &self.receipt().logs
+    }
+
+    pub fn gas_used(&self) -> u64 {
+        self.receipt().gas_used
+    }
+}
+
+impl fmt::Display for ExecRes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExecRes::Success(data, _) => write!(f, "success, returned {data}")?,
+            ExecRes::Revert(data, _) => write!(f, "reverted: {}", RevertReason::decode(data))?,
+            ExecRes::Halt(reason, _) => write!(f, "halted: {reason:?}")?,
+        }
+        let receipt = self.receipt();
+        if let Some(address) = receipt.created_address {
+            write!(f, ", created {}", Labeled(address))?;
+        }
+        for log in &receipt.logs {
+            write!(f, ", log from {}", Labeled(log.address))?;
+        }
+        write!(f, " (gas: {})", receipt.gas_used)
+    }
+}
+
+impl From<ExecutionResult> for ExecRes {
+    fn from(result: ExecutionResult) -> Self {
+        match result {
+            ExecutionResult::Success {
+                gas_used,
+                gas_refunded,
+                logs,
+                output,
+                ..
+            } => {
+                let (data, created_address) = match output {
+                    Output::Call(data) => (data, None),
+                    Output::Create(data, address) => (data, address),
+                };
+                let receipt = Receipt {
+                    logs,
+                    gas_used,
+                    gas_refunded,
+                    created_address,
+                    stack_trace: None,
+                };
+                ExecRes::Success(data, receipt)
+            }
+            ExecutionResult::Revert { gas_used, output } => ExecRes::Revert(
+                output,
+                Receipt {
+                    gas_used,
+                    ..Default::default()
+                },
+            ),
+            ExecutionResult::Halt { reason, gas_used } => ExecRes::Halt(
+                reason,
+                Receipt {
+                    gas_used,
+                    ..Default::default()
+                },
+            ),
+        }
+    }
+}
+
+/// Why a contract could not be deployed.
+#[derive(Debug, Clone)]
+pub enum DeployError {
+    /// The constructor reverted with this data.
+    Revert { data: Bytes, gas_used: u64 },
+    /// The execution halted, e.g. out of gas or the code size limit.
+    Halt { reason: Halt, gas_used: u64 },
+}
+
+impl DeployError {
+    pub fn gas_used(&self) -> u64 {
+        match self {
+            DeployError::Revert { gas_used, .. } | DeployError::Halt { gas_used, .. } => *gas_used,
+        }
+    }
+
+    /// Decoded revert data, `None` if the deployment halted.
+    pub fn revert_reason(&self) -> Option<RevertReason> {
+        match self {
+            DeployError::Revert { data, .. } => Some(RevertReason::decode(data)),
+            DeployError::Halt { .. } => None,
         }
     }
 }
 
+impl fmt::Display for DeployError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DeployError::Revert { data, gas_used } => write!(
+                f,
+                "deployment reverted: {} (gas used: {gas_used})",
+                RevertReason::decode(data)
+            ),
+            DeployError::Halt { reason, gas_used } => {
+                write!(f, "deployment halted: {reason:?} (gas used: {gas_used})")
+            }
+        }
+    }
+}
+
+impl std::error::Error for DeployError {}
+
 impl Provider {
     fn env(&mut self) -> &mut Env {
         &mut self.evm.env
@@ -68,18 +324,164 @@ impl Provider {
         self.evm.db().unwrap().insert_account_info(who, info);
     }
 
-    fn deploy_code(&mut self, from: Address, code: Bytes, value: Uint<256, 4>) -> Option<Address> {
+    /// A `STOP` for the accounts without code, as Solidity does not call them.
+    fn give_code(&mut self, who: Address) {
+        let mut info = self.load_account_info(who);
+        if info.code.as_ref().map_or(true, Bytecode::is_empty) {
+            let code = Bytecode::new_raw(Bytes::from_static(&[0x00]));
+            info.code_hash = code.hash_slow();
+            info.code = Some(code);
+            self.insert_account_info(who, info);
+            self.mocked_accounts.push(who);
+        }
+    }
+
+    /// Remove the `STOP` given by `give_code`, unless the code was replaced since.
+    fn take_code(&mut self) {
+        let stop = Bytecode::new_raw(Bytes::from_static(&[0x00])).hash_slow();
+        for who in std::mem::take(&mut self.mocked_accounts) {
+            let mut info = self.load_account_info(who);
+            if info.code_hash == stop {
+                info.code_hash = KECCAK_EMPTY;
+                info.code = Some(Bytecode::new());
+                self.insert_account_info(who, info);
+            }
+        }
+    }
+
+    /// Execute the transaction in the environment and commit the changes.
+    fn transact_commit(&mut self) -> ExecRes {
+        dictionary::collect_address(self.env().tx.caller);
+        let ResultAndState { result, state } = self.evm.inspect(&mut self.inspector).unwrap();
+        let db = self.evm.db().unwrap();
+        let pre_state = self.record_last_tx.then(|| PreState::capture(db, &state));
+        if let Some(state_diffs) = &mut self.state_diffs {
+            state_diffs.push(StateDiff::new(db, &state));
+        }
+        db.commit(state);
+        if let Some(pre_state) = pre_state {
+            self.last_tx = Some(LastTx {
+                env: self.evm.env.clone(),
+                static_call: false,
+                pre_state,
+            });
+        }
+        let mut res: ExecRes = result.into();
+        gas::record_gas(res.gas_used());
+        self.collect_traces(&mut res);
+        if let Some(recorded_logs) = &mut self.recorded_logs {
+            recorded_logs.extend_from_slice(res.logs());
+        }
+        res
+    }
+
+    fn create(&mut self, from: Address, code: Bytes, value: Uint<256, 4>) -> ExecRes {
         let tx = &mut self.env().tx;
         tx.caller = from;
         tx.transact_to = TransactTo::create();
         tx.data = code;
         tx.value = value;
-        match self.evm.transact_commit().unwrap() {
-            ExecutionResult::Success { output, .. } => match output {
-                Output::Create(_, addr) => addr,
-                _ => unreachable!(),
-            },
-            _ => None,
+        self.transact_commit()
+    }
+
+    /// Execute the transaction in the environment and discard the changes.
+    fn transact(&mut self) -> ExecRes {
+        dictionary::collect_address(self.env().tx.caller);
+        let mut res: ExecRes = self.evm.inspect(&mut self.inspector).unwrap().result.into();
+        if self.record_last_tx {
+            self.last_tx = Some(LastTx {
+                env: self.evm.env.clone(),
+                static_call: self.inspector.static_call,
+                pre_state: PreState::default(),
+            });
+        }
+        gas::record_gas(res.gas_used());
+        self.collect_traces(&mut res);
+        res
+    }
+
+    fn collect_traces(&mut self, res: &mut ExecRes) {
+        if let Some(frames) = self.inspector.reverts.take() {
+            res.receipt_mut().stack_trace = self.stack_trace(frames);
+        }
+        if let Some(trace) = self.inspector.tracer.as_mut().and_then(Tracer::take) {
+            if let Some(report) = &mut self.gas_report {
+                report.record(&trace, &self.contracts);
+            }
+            if !self.expected_calls.is_empty() {
+                self.observed_calls.push(trace.clone());
+            }
+            if self.verbosity.is_some() || self.inspector.struct_logger.is_some() {
+                self.traces.push(trace);
+            }
+        }
+        if let Some(logger) = &mut self.inspector.struct_logger {
+            let return_value = match res {
+                ExecRes::Success(data, _) | ExecRes::Revert(data, _) => hex::encode(data),
+                ExecRes::Halt(..) => String::new(),
+            };
+            self.struct_log_trace = Some(StructLogTrace {
+                gas: res.gas_used(),
+                failed: !res.is_success(),
+                return_value,
+                struct_logs: logger.take(),
+            });
+        }
+    }
+
+    /// Map the frames to the sources of the registered contracts, `None` if none has a source map.
+    fn stack_trace(&mut self, frames: Vec<RevertFrame>) -> Option<StackTrace> {
+        let frames = frames.into_iter().map(|frame| {
+            let Some(address) = frame.code_address else {
+                return StackFrame {
+                    contract: "constructor".to_string(),
+                    function: None,
+                    location: None,
+                };
+            };
+            let code = self.load_account_info(address).code;
+            let info = self.contracts.get(&address);
+            let function = frame
+                .selector
+                .and_then(|selector| info?.function(&selector))
+                .map(|function| function.name.clone());
+            let location = info
+                .and_then(|info| info.sources.as_ref())
+                .zip(code)
+                .and_then(|(sources, code)| sources.locate(&code.original_bytes(), frame.pc));
+            StackFrame {
+                contract: self
+                    .get_label(&address)
+                    .map_or_else(|| address.to_string(), ToString::to_string),
+                function,
+                location,
+            }
+        });
+        let frames: Vec<StackFrame> = frames.collect();
+        frames
+            .iter()
+            .any(|frame| frame.location.is_some())
+            .then_some(StackTrace(frames))
+    }
+
+    fn deploy_code(
+        &mut self,
+        from: Address,
+        code: Bytes,
+        value: Uint<256, 4>,
+    ) -> Result<Address, DeployError> {
+        match self.create(from, code, value) {
+            ExecRes::Success(_, receipt) => Ok(receipt
+                .created_address
+                .expect("successful create without an address")),
+            ExecRes::Revert(data, receipt) => Err(DeployError::Revert {
+                data,
+                gas_used: receipt.gas_used,
+            }),
+            ExecRes::Halt(reason, receipt) => Err(DeployError::Halt {
+                reason,
+                gas_used: receipt.gas_used,
+            }),
         }
     }
 
@@ -89,60 +491,469 @@ impl Provider {
         tx.transact_to = TransactTo::Call(to);
         tx.data = data;
         tx.value = value;
-
-        match self.evm.transact_commit().unwrap() {
-            ExecutionResult::Success { output, .. } => ExecRes::Success(output.into_data()),
-            ExecutionResult::Revert { output, .. } => ExecRes::Revert(output),
-            ExecutionResult::Halt { reason, .. } => ExecRes::Halt(reason),
-        }
+        self.transact_commit()
     }
 
-    fn send(&mut self, from: Address, to: Address, data: Bytes, value: Uint<256, 4>) -> ExecRes {
+    fn eth_call(&mut self, from: Address, to: Address, data: Bytes) -> ExecRes {
         let tx = &mut self.env().tx;
         tx.caller = from;
         tx.transact_to = TransactTo::Call(to);
         tx.data = data;
-        tx.value = value;
-
-        match self.evm.transact_commit().unwrap() {
-            ExecutionResult::Success { output, .. } => ExecRes::Success(output.into_data()),
-            ExecutionResult::Revert { output, .. } => ExecRes::Revert(output),
-            ExecutionResult::Halt { reason, .. } => ExecRes::Halt(reason),
-        }
+        tx.value = U256::ZERO;
+        self.transact()
     }
 
     fn staticcall(&mut self, from: Address, to: Address, data: Bytes) -> ExecRes {
+        self.inspector.static_call = true;
+        let res = self.eth_call(from, to, data);
+        self.inspector.static_call = false;
+        res
+    }
+
+    /// Staticcall `to` without committing nor tracing, the first word returned if any.
+    fn probe_call(&mut self, to: Address, data: Bytes, probe: &mut SlotProbe) -> Option<U256> {
+        let sender = self.sender;
         let tx = &mut self.env().tx;
-        tx.caller = from;
+        tx.caller = sender;
         tx.transact_to = TransactTo::Call(to);
         tx.data = data;
+        tx.value = U256::ZERO;
+        match self.evm.inspect_ref(probe).ok()?.result {
+            ExecutionResult::Success {
+                output: Output::Call(data),
+                ..
+            } => U256::try_from_be_slice(data.get(..32)?),
+            _ => None,
+        }
+    }
+
+    /// The slot read by the call to `token` which the returned word comes from, and its bytes
+    /// returned, found by writing to the slots read until the word changes.
+    fn find_slot(&mut self, token: Address, data: Bytes) -> Option<(U256, Packing, SlotProbe)> {
+        let mut probe = SlotProbe::default();
+        self.probe_call(token, data.clone(), &mut probe)?;
+        for slot in probe.candidates(token) {
+            let previous = self.load(token, slot);
+            let trial = Packing::trial_word(previous);
+            self.store(token, slot, trial);
+            let returned = self.probe_call(token, data.clone(), &mut SlotProbe::default());
+            self.store(token, slot, previous);
+            if let Some(packing) = returned.and_then(|returned| Packing::find(trial, returned)) {
+                return Some((slot, packing, probe));
+            }
+        }
+        None
+    }
+
+    #[track_caller]
+    fn balance_slot(&mut self, token: Address, who: Address) -> (U256, Packing) {
+        if let Some((balance_slot, packing)) = self.balance_slots.get(&token) {
+            return (balance_slot.slot(who), *packing);
+        }
+        if let Some(slot) = self.holder_slots.get(&(token, who)) {
+            return *slot;
+        }
+        let data = [
+            get_sig("balanceOf(address)").to_vec(),
+            encode(&[AbiValue::Address(who)]),
+        ];
+        let Some((slot, packing, probe)) = self.find_slot(token, data.concat().into()) else {
+            panic!(
+                "no slot of {} holds the balance of {}",
+                Labeled(token),
+                Labeled(who)
+            );
+        };
+        // the slots of the tokens not hashing the holder are not known for the other holders
+        let balance_slot = probe
+            .preimages
+            .get(&slot)
+            .and_then(|preimage| BalanceSlot::from_preimage(preimage, who));
+        match balance_slot {
+            Some(balance_slot) => {
+                self.balance_slots.insert(token, (balance_slot, packing));
+            }
+            None => {
+                self.holder_slots.insert((token, who), (slot, packing));
+            }
+        }
+        (slot, packing)
+    }
+
+    #[track_caller]
+    fn supply_slot(&mut self, token: Address) -> (U256, Packing) {
+        if let Some(slot) = self.supply_slots.get(&token) {
+            return *slot;
+        }
+        let data = get_sig("totalSupply()").to_vec();
+        let Some((slot, packing, _)) = self.find_slot(token, data.into()) else {
+            panic!("no slot of {} holds the total supply", Labeled(token));
+        };
+        self.supply_slots.insert(token, (slot, packing));
+        (slot, packing)
+    }
+}
+
+impl Provider {
+    /// Remember what the contract at `address` exposes, e.g. to fuzz its functions.
+    /// It is labeled with the name of the contract, unless it already has a label.
+    pub fn register_contract(&mut self, address: Address, info: ContractInfo) {
+        if !self.labels.contains_key(&address) {
+            self.label(address, info.name.clone());
+        }
+        self.contracts.insert(address, info);
+        if self.inspector.profiler.is_some() {
+            self.profile_functions(address);
+        }
+    }
+
+    /// Show `label` along with `address` in the traces and the failure messages.
+    pub fn label(&mut self, address: Address, label: impl Into<String>) {
+        let label = label.into();
+        labels::set_label(address, label.clone());
+        if let Some(previous) = self.labels.insert(address, label) {
+            labels::remove_label(&address, &previous);
+        }
+    }
+
+    pub fn get_label(&self, address: &Address) -> Option<&str> {
+        self.labels.get(address).map(String::as_str)
+    }
+
+    pub fn contract_info(&self, address: &Address) -> Option<&ContractInfo> {
+        self.contracts.get(address)
+    }
+
+    /// The registered contracts, ordered by address.
+    pub fn contracts(&self) -> &BTreeMap<Address, ContractInfo> {
+        &self.contracts
+    }
+
+    /// Trace the next transactions, or stop if `None`.
+    /// It starts at `RUSTRY_VERBOSITY`, between 1 and 3, if set.
+    pub fn set_verbosity(&mut self, verbosity: Option<Verbosity>) {
+        self.verbosity = verbosity;
+        match verbosity {
+            Some(_) => {
+                self.inspector.tracer.get_or_insert_with(Tracer::default);
+            }
+            // still needed for the geth traces, the gas report and the expected calls
+            None if self.inspector.struct_logger.is_some()
+                || self.gas_report.is_some()
+                || !self.expected_calls.is_empty() => {}
+            None => self.inspector.tracer = None,
+        }
+    }
+
+    /// The call trees of the transactions since the tracing started.
+    pub fn traces(&self) -> &[CallTrace] {
+        &self.traces
+    }
+
+    /// Record the geth traces of the next transactions, see `struct_log_trace` and `call_frame`.
+    pub fn record_geth_traces(&mut self) {
+        self.inspector
+            .struct_logger
+            .get_or_insert_with(Default::default);
+        self.inspector.tracer.get_or_insert_with(Tracer::default);
+    }
+
+    /// `debug_traceTransaction` of the last transaction, with the default struct logger.
+    pub fn struct_log_trace(&self) -> Option<&StructLogTrace> {
+        self.struct_log_trace.as_ref()
+    }
+
+    /// `debug_traceTransaction` of the last transaction, with the `callTracer`.
+    pub fn call_frame(&self) -> Option<CallFrame> {
+        self.traces.last().map(CallFrame::from)
+    }
+
+    /// Record the gas used by the next calls, as if `RUSTRY_GAS_REPORT` was set.
+    pub fn record_gas_report(&mut self) {
+        self.gas_report.get_or_insert_with(GasReport::default);
+        self.inspector.tracer.get_or_insert_with(Tracer::default);
+    }
+
+    /// The calls recorded for the gas report, submitted to the one of the binary on drop.
+    pub fn gas_report(&self) -> Option<&GasReport> {
+        self.gas_report.as_ref()
+    }
+
+    /// Expect `count` calls to `callee` with this calldata prefix during the next transactions,
+    /// checked by `verify_expected_calls`.
+    ///
+    /// The expected calls left unchecked are verified when the provider is dropped, which panics on
+    /// a mismatch wherever that is, e.g. at the end of a helper the provider was moved into rather
+    /// than at the line of the test. The drop skips them if the thread is already panicking, as a
+    /// second panic would abort the tests.
+    pub fn expect_call(&mut self, callee: Address, calldata: impl Into<Bytes>, count: usize) {
+        self.expect_call_with(ExpectedCall::new(callee, calldata).count(count));
+    }
+
+    /// Expect a call, optionally with a value and gas.
+    pub fn expect_call_with(&mut self, expected: ExpectedCall) {
+        self.expected_calls.push(expected);
+        self.inspector.tracer.get_or_insert_with(Tracer::default);
+    }
+
+    /// Check the expected calls against the calls made since they were expected, and forget them.
+    #[track_caller]
+    pub fn verify_expected_calls(&mut self) {
+        if let Err(message) = self.check_expected_calls() {
+            panic!("{message}");
+        }
+    }
+
+    fn check_expected_calls(&mut self) -> Result<(), String> {
+        let expected_calls = std::mem::take(&mut self.expected_calls);
+        let observed_calls = std::mem::take(&mut self.observed_calls);
+        let failures: Vec<String> = expected_calls
+            .iter()
+            .filter_map(|expected| check_calls(&observed_calls, expected).err())
+            .collect();
+        if failures.is_empty() {
+            Ok(())
+        } else {
+            Err(failures.join("\n"))
+        }
+    }
+
+    /// Start recording the changes of all the committed transactions.
+    pub fn record_state_diffs(&mut self) {
+        self.state_diffs = Some(Vec::new());
+    }
+
+    /// Changes of the transactions committed since the last call or `record_state_diffs`.
+    pub fn state_diffs(&mut self) -> Vec<StateDiff> {
+        self.state_diffs
+            .as_mut()
+            .map(std::mem::take)
+            .expect("state diffs are not being recorded, call `record_state_diffs` first")
+    }
+
+    /// The diff with the slots named after the variables of the registered contracts.
+    pub fn render_state_diff(&self, diff: &StateDiff) -> String {
+        diff.render(|address, slot| {
+            self.contracts
+                .get(address)?
+                .storage_layout
+                .as_ref()?
+                .slot_name(slot)
+        })
+    }
+
+    /// Profile the gas of the next transactions, see `gas_profile`.
+    pub fn record_gas_profile(&mut self) {
+        self.inspector.profiler = Some(GasProfiler::default());
+        let addresses: Vec<Address> = self.contracts.keys().copied().collect();
+        for address in addresses {
+            self.profile_functions(address);
+        }
+    }
+
+    /// Follow the internal functions of a registered contract in the gas profile.
+    fn profile_functions(&mut self, address: Address) {
+        let Some(sources) = &self.contracts[&address].sources else {
+            return;
+        };
+        let functions = sources.functions.clone();
+        let code_hash = self.load_account_info(address).code_hash;
+        if let Some(profiler) = &mut self.inspector.profiler {
+            profiler.add_functions(code_hash, &functions);
+        }
+    }
+
+    /// The gas spent by the calls, internal functions and instructions since `record_gas_profile`.
+    pub fn gas_profile(&self) -> Option<GasProfile> {
+        Some(self.inspector.profiler.as_ref()?.profile(&self.contracts))
+    }
+
+    /// Count the executed instructions, as if `RUSTRY_COVERAGE` was set.
+    pub fn record_coverage(&mut self) {
+        self.inspector.coverage.get_or_insert_with(Default::default);
+    }
+
+    /// The coverage of the registered contracts with a source map, if it is being recorded.
+    pub fn coverage(&mut self) -> Option<CoverageReport> {
+        let covered = self.covered_code()?;
+        Some(CoverageReport::new(covered.iter().map(|(_, code)| code)))
+    }
+
+    /// The executions of the registered contracts with a source map, by code hash.
+    fn covered_code(&mut self) -> Option<Vec<(B256, CoveredCode)>> {
+        self.inspector.coverage.as_ref()?;
+        let contracts: Vec<_> = self
+            .contracts
+            .iter()
+            .filter_map(|(address, info)| Some((*address, info.sources.clone()?)))
+            .collect();
+        let mut covered: Vec<(B256, CoveredCode)> = Vec::new();
+        for (address, sources) in contracts {
+            let info = self.load_account_info(address);
+            // the same code deployed twice
+            if covered.iter().any(|(hash, _)| *hash == info.code_hash) {
+                continue;
+            }
+            let Some(code) = info.code else {
+                continue;
+            };
+            let hits = self.inspector.coverage.as_ref()?.hits.get(&info.code_hash);
+            covered.push((
+                info.code_hash,
+                CoveredCode {
+                    code: code.original_bytes(),
+                    sources,
+                    hits: hits.cloned().unwrap_or_default(),
+                },
+            ));
+        }
+        Some(covered)
+    }
+
+    /// Record the next transactions, and the state before them, to execute the last one again in
+    /// the debugger.
+    pub fn record_last_tx(&mut self) {
+        self.record_last_tx = true;
+    }
 
-        match self.evm.transact().unwrap().result {
-            ExecutionResult::Success { output, .. } => ExecRes::Success(output.into_data()),
-            ExecutionResult::Revert { output, .. } => ExecRes::Revert(output),
-            ExecutionResult::Halt { reason, .. } => ExecRes::Halt(reason),
+    /// Execute the last transaction again to step through it, `None` if none was recorded since
+    /// `record_last_tx`.
+    pub fn debugger(&mut self) -> Option<Debugger> {
+        let last_tx = self.last_tx.clone()?;
+        let contracts: Vec<_> = self
+            .contracts
+            .iter()
+            .filter_map(|(address, info)| Some((*address, info.sources.clone()?)))
+            .collect();
+        let mut locations = HashMap::new();
+        for (address, sources) in contracts {
+            let info = self.load_account_info(address);
+            if let Some(code) = info.code {
+                locations
+                    .entry(info.code_hash)
+                    .or_insert_with(|| sources.locate_all(&code.original_bytes()));
+            }
+        }
+
+        let mut db = self.evm.db.clone()?;
+        last_tx.pre_state.restore(&mut db);
+        let mut evm = EVM::new();
+        evm.env = last_tx.env;
+        evm.database(db);
+        let mut inspector = ProviderInspector {
+            static_call: last_tx.static_call,
+            debugger: Some(DebugRecorder::default()),
+            mocks: self.inspector.mocks.clone(),
+            ..Default::default()
+        };
+        let result = evm.inspect(&mut inspector).unwrap().result;
+        let steps = inspector.debugger.as_mut().map(DebugRecorder::take);
+        Some(Debugger::new(
+            steps.unwrap_or_default(),
+            locations,
+            result.into(),
+        ))
+    }
+
+    /// Step through the last transaction again on the terminal, e.g. the one which failed.
+    /// The commands are read from the standard input, `h` lists them.
+    #[track_caller]
+    pub fn debug_last_tx(&mut self) {
+        let mut debugger = self
+            .debugger()
+            .expect("no transaction to debug, call `record_last_tx` first");
+        debugger
+            .run(io::stdin().lock(), io::stderr())
+            .expect("cannot debug on the terminal");
+    }
+
+    pub fn render_trace(&self, trace: &CallTrace) -> String {
+        TraceRenderer {
+            contracts: &self.contracts,
+            labels: &self.labels,
+            verbosity: self.verbosity.unwrap_or(Verbosity::Calls),
+        }
+        .render(trace)
+    }
+}
+
+impl Drop for Provider {
+    /// Show what happened when the test failed, submit the gas report, forget the labels, and
+    /// verify the expected calls left.
+    fn drop(&mut self) {
+        if let Some(report) = self.gas_report.take() {
+            report.submit();
+        }
+        if let Some(covered) = self.covered_code() {
+            coverage::submit(covered);
+        }
+        if std::thread::panicking()
+            && !fuzz::is_quiet()
+            && self.verbosity.is_some()
+            && !self.traces.is_empty()
+        {
+            let traces: Vec<String> = self
+                .traces
+                .iter()
+                .map(|trace| self.render_trace(trace))
+                .collect();
+            eprintln!("Traces:\n{}", traces.join("\n"));
+        }
+        // rendered with the labels
+        let expected_calls = if std::thread::panicking() {
+            None
+        } else {
+            self.check_expected_calls().err()
+        };
+        // the other providers of the thread may label the same addresses
+        for (address, label) in &self.labels {
+            labels::remove_label(address, label);
+        }
+        if let Some(message) = expected_calls {
+            panic!("{message}");
         }
     }
 }
 
 pub trait Frontend {
-    fn deploy(&mut self, code: Bytes) -> Option<Address>;
-    fn deploy_with_value(&mut self, code: Bytes, value: Uint<256, 4>) -> Option<Address>;
+    fn deploy(&mut self, code: Bytes) -> Result<Address, DeployError>;
+    fn deploy_with_value(
+        &mut self,
+        code: Bytes,
+        value: Uint<256, 4>,
+    ) -> Result<Address, DeployError>;
+    fn create(&mut self, code: Bytes) -> ExecRes;
+    fn create_with_value(&mut self, code: Bytes, value: Uint<256, 4>) -> ExecRes;
     fn call(&mut self, to: Address, data: Bytes) -> ExecRes;
     fn call_value(&mut self, to: Address, data: Bytes, value: Uint<256, 4>) -> ExecRes;
     fn send(&mut self, to: Address, value: Uint<256, 4>) -> ExecRes;
+    /// Call in a static context, any state change makes the call fail.
     fn staticcall(&mut self, to: Address, data: Bytes) -> ExecRes;
+    /// Call without committing, the state changes are discarded.
+    fn eth_call(&mut self, to: Address, data: Bytes) -> ExecRes;
 }
 
 impl Frontend for Provider {
-    fn deploy(&mut self, code: Bytes) -> Option<Address> {
+    fn deploy(&mut self, code: Bytes) -> Result<Address, DeployError> {
         self.deploy_code(self.sender, code, U256::ZERO)
     }
 
-    fn deploy_with_value(&mut self, code: Bytes, value: Uint<256, 4>) -> Option<Address> {
+    fn deploy_with_value(
+        &mut self,
+        code: Bytes,
+        value: Uint<256, 4>,
+    ) -> Result<Address, DeployError> {
         self.deploy_code(self.sender, code, value)
     }
 
+    fn create(&mut self, code: Bytes) -> ExecRes {
+        self.create(self.sender, code, U256::ZERO)
+    }
+
+    fn create_with_value(&mut self, code: Bytes, value: Uint<256, 4>) -> ExecRes {
+        self.create(self.sender, code, value)
+    }
+
     fn call(&mut self, to: Address, data: Bytes) -> ExecRes {
         self.call(self.sender, to, data, U256::ZERO)
     }
@@ -152,17 +963,42 @@ impl Frontend for Provider {
     }
 
     fn send(&mut self, to: Address, value: Uint<256, 4>) -> ExecRes {
-        self.send(self.sender, to, Bytes::default(), value)
+        self.call(self.sender, to, Bytes::default(), value)
     }
 
     fn staticcall(&mut self, to: Address, data: Bytes) -> ExecRes {
         self.staticcall(self.sender, to, data)
     }
+
+    fn eth_call(&mut self, to: Address, data: Bytes) -> ExecRes {
+        self.eth_call(self.sender, to, data)
+    }
 }
 
 pub trait Cheats {
     fn impersonate(&mut self, who: Address);
     fn mint(&mut self, wad: Uint<256, 4>, who: Address);
+    /// Start recording the logs of all the committed transactions.
+    fn record_logs(&mut self);
+    /// Logs recorded since the last call to `recorded_logs` or `record_logs`.
+    fn recorded_logs(&mut self) -> Vec<Log>;
+    /// Read a storage slot.
+    fn load(&self, who: Address, slot: U256) -> U256;
+    /// Overwrite a storage slot.
+    fn store(&mut self, who: Address, slot: U256, value: U256);
+    /// Set the balance of `who` in an ERC20 `token`, its slot being found by probing the storage
+    /// read by `balanceOf`. The values packed with it and the total supply are left as is.
+    fn deal_erc20(&mut self, token: Address, who: Address, amount: U256);
+    /// As `deal_erc20`, the total supply changing as much as the balance.
+    fn deal_erc20_adjust_supply(&mut self, token: Address, who: Address, amount: U256);
+    /// Return `return_data` to the calls to `callee` whose calldata starts with `calldata`, e.g.
+    /// only a selector, without executing them. The longest matching prefix wins.
+    /// A `callee` without code is given a `STOP` until `clear_mocked_calls`, to be called.
+    fn mock_call(&mut self, callee: Address, calldata: Bytes, return_data: Bytes);
+    /// As `mock_call`, the calls reverting with `revert_data`.
+    fn mock_call_revert(&mut self, callee: Address, calldata: Bytes, revert_data: Bytes);
+    /// Remove all the mocks, and the code given to the mocked accounts which had none.
+    fn clear_mocked_calls(&mut self);
 }
 
 impl Cheats for Provider {
@@ -175,4 +1011,65 @@ impl Cheats for Provider {
         info.balance += wad;
         self.insert_account_info(who, info);
     }
+
+    fn record_logs(&mut self) {
+        self.recorded_logs = Some(Vec::new());
+    }
+
+    fn recorded_logs(&mut self) -> Vec<Log> {
+        self.recorded_logs
+            .as_mut()
+            .map(std::mem::take)
+            .expect("logs are not being recorded, call `record_logs` first")
+    }
+
+    fn load(&self, who: Address, slot: U256) -> U256 {
+        let db = self.evm.db.as_ref().expect("no database");
+        DatabaseRef::storage(db, who, slot).unwrap_or_default()
+    }
+
+    fn store(&mut self, who: Address, slot: U256, value: U256) {
+        let db = self.evm.db.as_mut().expect("no database");
+        let _ = db.insert_account_storage(who, slot, value);
+    }
+
+    #[track_caller]
+    fn deal_erc20(&mut self, token: Address, who: Address, amount: U256) {
+        let (slot, Packing { offset, width }) = self.balance_slot(token, who);
+        store_packed(self, token, slot, offset, width, amount);
+    }
+
+    #[track_caller]
+    fn deal_erc20_adjust_supply(&mut self, token: Address, who: Address, amount: U256) {
+        let (slot, balance) = self.balance_slot(token, who);
+        let previous = load_packed(self, token, slot, balance.offset, balance.width);
+        let (supply_slot, supply) = self.supply_slot(token);
+        let total = load_packed(self, token, supply_slot, supply.offset, supply.width);
+        let total = if amount >= previous {
+            total.saturating_add(amount - previous)
+        } else {
+            total.saturating_sub(previous - amount)
+        };
+        store_packed(self, token, supply_slot, supply.offset, supply.width, total);
+        store_packed(self, token, slot, balance.offset, balance.width, amount);
+    }
+
+    fn mock_call(&mut self, callee: Address, calldata: Bytes, return_data: Bytes) {
+        self.give_code(callee);
+        self.inspector
+            .mocks
+            .add(callee, calldata, false, return_data);
+    }
+
+    fn mock_call_revert(&mut self, callee: Address, calldata: Bytes, revert_data: Bytes) {
+        self.give_code(callee);
+        self.inspector
+            .mocks
+            .add(callee, calldata, true, revert_data);
+    }
+
+    fn clear_mocked_calls(&mut self) {
+        self.inspector.mocks.clear();
+        self.take_code();
+    }
 }
@@ -0,0 +1,130 @@
+//! Where the tokens keep the balances, found by probing their storage.
+
+use crate::utils::slots::{mapping_slot, read_packed};
+use revm::{
+    interpreter::{opcode, CallInputs, Gas, InstructionResult, Interpreter},
+    primitives::{keccak256, Address, Bytes, U256},
+    Database, EVMData, Inspector,
+};
+use std::collections::HashMap;
+
+/// Records the slots read and the hashes computed during a call, run in a static context.
+#[derive(Debug, Default)]
+pub struct SlotProbe {
+    /// The account whose storage is read and the slot, in order
+    pub reads: Vec<(Address, U256)>,
+    /// By hash
+    pub preimages: HashMap<U256, Vec<u8>>,
+}
+
+impl SlotProbe {
+    /// The slots read in the storage of `address`, the last read first.
+    pub fn candidates(&self, address: Address) -> Vec<U256> {
+        let mut candidates = Vec::new();
+        for (_, slot) in self.reads.iter().rev().filter(|(read, _)| *read == address) {
+            if !candidates.contains(slot) {
+                candidates.push(*slot);
+            }
+        }
+        candidates
+    }
+}
+
+impl<DB: Database> Inspector<DB> for SlotProbe {
+    fn call(
+        &mut self,
+        data: &mut EVMData<'_, DB>,
+        inputs: &mut CallInputs,
+    ) -> (InstructionResult, Gas, Bytes) {
+        if data.journaled_state.depth() == 0 {
+            inputs.is_static = true;
+        }
+        (InstructionResult::Continue, Gas::new(0), Bytes::new())
+    }
+
+    fn step(&mut self, interp: &mut Interpreter, _data: &mut EVMData<'_, DB>) -> InstructionResult {
+        match interp.current_opcode() {
+            opcode::SLOAD => {
+                if let Ok(slot) = interp.stack.peek(0) {
+                    self.reads.push((interp.contract.address, slot));
+                }
+            }
+            opcode::KECCAK256 => {
+                if let (Ok(offset), Ok(len)) = (interp.stack.peek(0), interp.stack.peek(1)) {
+                    let (offset, len) = (offset.saturating_to::<usize>(), len.saturating_to());
+                    // the memory is not expanded yet, the keys of the mappings are already written
+                    let preimage = offset
+                        .checked_add(len)
+                        .and_then(|end| interp.memory.data().get(offset..end));
+                    if let Some(preimage) = preimage {
+                        let hash = U256::from_be_bytes(keccak256(preimage).0);
+                        self.preimages.insert(hash, preimage.to_vec());
+                    }
+                }
+            }
+            _ => (),
+        }
+        InstructionResult::Continue
+    }
+}
+
+/// The bytes of a slot a value is packed in, the other ones being kept when it is written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Packing {
+    /// In bytes from the right of the word
+    pub offset: usize,
+    pub width: usize,
+}
+
+impl Packing {
+    /// A word whose bytes are all different and not zero, to tell which ones are returned.
+    pub fn trial_word(previous: U256) -> U256 {
+        let trial = U256::from_be_bytes(std::array::from_fn::<u8, 32, _>(|i| i as u8 + 1));
+        if trial == previous {
+            !trial
+        } else {
+            trial
+        }
+    }
+
+    /// The bytes of the `trial` word which were `returned`, if they were as is.
+    pub fn find(trial: U256, returned: U256) -> Option<Self> {
+        (0..32)
+            .flat_map(|offset| (1..=32 - offset).map(move |width| Packing { offset, width }))
+            .find(|packing| read_packed(trial, packing.offset, packing.width) == returned)
+    }
+}
+
+/// How a token derives the slot of a balance from its holder.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BalanceSlot {
+    /// `keccak256(holder . slot)`, as laid out by Solidity
+    KeyFirst(U256),
+    /// `keccak256(slot . holder)`, as laid out by Vyper
+    SlotFirst(U256),
+}
+
+impl BalanceSlot {
+    /// From the preimage of the slot of the balance of `holder`.
+    pub fn from_preimage(preimage: &[u8], holder: Address) -> Option<Self> {
+        let (left, right) = (preimage.get(..32)?, preimage.get(32..)?);
+        let holder = holder.into_word();
+        if left == holder.as_slice() && right.len() == 32 {
+            Some(BalanceSlot::KeyFirst(U256::try_from_be_slice(right)?))
+        } else if right == holder.as_slice() {
+            Some(BalanceSlot::SlotFirst(U256::try_from_be_slice(left)?))
+        } else {
+            None
+        }
+    }
+
+    pub fn slot(&self, holder: Address) -> U256 {
+        match self {
+            BalanceSlot::KeyFirst(slot) => mapping_slot(*slot, holder),
+            BalanceSlot::SlotFirst(slot) => {
+                let preimage = [slot.to_be_bytes(), holder.into_word().0].concat();
+                U256::from_be_bytes(keccak256(preimage).0)
+            }
+        }
+    }
+}
@@ -0,0 +1,476 @@
+//! Step through a transaction again, forward and back, instruction by instruction.
+
+use super::{db::ExecRes, geth::hex_quantity};
+use crate::{common::labels::Labeled, compilers::solidity::source_map::CodeLocation};
+use revm::{
+    db::{CacheDB, DbAccount, EmptyDB},
+    interpreter::{opcode, InstructionResult, Interpreter},
+    primitives::{hex, Address, Bytes, Env, State, B256, U256},
+};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::{self, Write as _},
+    io::{self, BufRead, Write},
+};
+
+/// A slot written by an `SSTORE`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StorageChange {
+    pub address: Address,
+    pub slot: U256,
+    /// Before the transaction
+    pub original: U256,
+    pub value: U256,
+}
+
+/// The execution before an instruction.
+#[derive(Debug, Clone)]
+pub struct DebugStep {
+    /// 1 for the transaction itself
+    pub depth: u64,
+    /// Whose storage is used
+    pub address: Address,
+    pub code_hash: B256,
+    pub pc: usize,
+    pub op: u8,
+    /// Remaining before the step
+    pub gas: u64,
+    pub gas_cost: u64,
+    /// The top last
+    pub stack: Vec<U256>,
+    pub memory: Bytes,
+    /// The slot written, at the `SSTORE`s
+    pub storage_change: Option<StorageChange>,
+}
+
+impl DebugStep {
+    pub fn op_name(&self) -> String {
+        opcode::OPCODE_JUMPMAP[self.op as usize]
+            .map_or_else(|| format!("opcode {:#04x}", self.op), ToString::to_string)
+    }
+}
+
+/// Records the steps through the inspector hooks.
+#[derive(Debug, Default)]
+pub struct DebugRecorder {
+    steps: Vec<DebugStep>,
+    /// Index of the steps not ended yet, the innermost last
+    pending: Vec<usize>,
+}
+
+impl DebugRecorder {
+    pub fn step(&mut self, interp: &Interpreter, depth: u64) {
+        self.pending.push(self.steps.len());
+        self.steps.push(DebugStep {
+            depth,
+            address: interp.contract.address,
+            code_hash: interp.contract.hash,
+            pc: interp.program_counter(),
+            op: interp.current_opcode(),
+            gas: interp.gas.remaining(),
+            gas_cost: 0,
+            stack: interp.stack.data().clone(),
+            memory: interp.memory.data().clone().into(),
+            storage_change: None,
+        });
+    }
+
+    pub fn step_end(&mut self, interp: &Interpreter, state: &State, result: InstructionResult) {
+        let Some(index) = self.pending.pop() else {
+            return;
+        };
+        let step = &mut self.steps[index];
+        step.gas_cost = step.gas.saturating_sub(interp.gas.remaining());
+        if step.op == opcode::SSTORE && result == InstructionResult::Continue {
+            let slot = step.stack.last().copied().unwrap_or_default();
+            if let Some(value) = state
+                .get(&step.address)
+                .and_then(|account| account.storage.get(&slot))
+            {
+                step.storage_change = Some(StorageChange {
+                    address: step.address,
+                    slot,
+                    original: value.previous_or_original_value,
+                    value: value.present_value,
+                });
+            }
+        }
+    }
+
+    pub fn take(&mut self) -> Vec<DebugStep> {
+        self.pending.clear();
+        std::mem::take(&mut self.steps)
+    }
+}
+
+/// An account touched by a transaction, as it was before it.
+#[derive(Debug, Clone)]
+struct PreAccount {
+    address: Address,
+    /// `None` if it was not loaded yet
+    account: Option<DbAccount>,
+    /// The transaction cleared the storage, which is then kept whole
+    storage_cleared: bool,
+}
+
+/// The accounts touched by a committed transaction, as they were before it.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct PreState(Vec<PreAccount>);
+
+impl PreState {
+    /// To call before committing `state` to `db`.
+    pub fn capture(db: &CacheDB<EmptyDB>, state: &State) -> Self {
+        let accounts = state.iter().filter(|(_, account)| account.is_touched());
+        PreState(
+            accounts
+                .map(|(address, account)| {
+                    let storage_cleared = account.is_selfdestructed() || account.is_created();
+                    let before = db.accounts.get(address).map(|before| DbAccount {
+                        info: before.info.clone(),
+                        account_state: before.account_state.clone(),
+                        storage: if storage_cleared {
+                            before.storage.clone()
+                        } else {
+                            account
+                                .storage
+                                .iter()
+                                .map(|(slot, value)| (*slot, value.previous_or_original_value))
+                                .collect()
+                        },
+                    });
+                    PreAccount {
+                        address: *address,
+                        account: before,
+                        storage_cleared,
+                    }
+                })
+                .collect(),
+        )
+    }
+
+    /// Undo the transaction in `db`.
+    pub fn restore(&self, db: &mut CacheDB<EmptyDB>) {
+        for pre in &self.0 {
+            let Some(before) = pre.account.clone() else {
+                db.accounts.remove(&pre.address);
+                continue;
+            };
+            let account = db.accounts.entry(pre.address).or_default();
+            account.info = before.info;
+            account.account_state = before.account_state;
+            if pre.storage_cleared {
+                account.storage = before.storage;
+            } else {
+                account.storage.extend(before.storage);
+            }
+        }
+    }
+}
+
+/// What is needed to execute the last transaction again.
+#[derive(Debug, Clone)]
+pub(crate) struct LastTx {
+    pub env: Env,
+    pub static_call: bool,
+    pub pre_state: PreState,
+}
+
+/// Where to stop when continuing.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Breakpoint {
+    Pc(usize),
+    /// A line of the sources, as in the locations
+    Line(usize),
+}
+
+impl fmt::Display for Breakpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Breakpoint::Pc(pc) => write!(f, "pc {pc:#x}"),
+            Breakpoint::Line(line) => write!(f, "line {line}"),
+        }
+    }
+}
+
+const HELP: &str = "\
+n, next [count]      step forward
+p, prev [count]      step back
+g, goto <step>       go to a step
+c, continue          continue to the next breakpoint
+rc                   continue back to the previous breakpoint
+b, break pc <pc>     stop at a program counter
+b, break line <line> stop at a line of the sources
+b, break             list the breakpoints
+d, delete            delete the breakpoints
+m, memory            show the memory
+h, help              show the commands
+q, quit              stop debugging
+An empty line repeats the last command.";
+
+/// The steps of a transaction executed again, with a cursor to move through them.
+#[derive(Debug)]
+pub struct Debugger {
+    steps: Vec<DebugStep>,
+    /// Of the registered contracts with a source map, by code hash and program counter
+    locations: HashMap<B256, HashMap<usize, CodeLocation>>,
+    result: ExecRes,
+    cursor: usize,
+    breakpoints: Vec<Breakpoint>,
+}
+
+impl Debugger {
+    pub(crate) fn new(
+        steps: Vec<DebugStep>,
+        locations: HashMap<B256, HashMap<usize, CodeLocation>>,
+        result: ExecRes,
+    ) -> Self {
+        Debugger {
+            steps,
+            locations,
+            result,
+            cursor: 0,
+            breakpoints: Vec::new(),
+        }
+    }
+
+    pub fn steps(&self) -> &[DebugStep] {
+        &self.steps
+    }
+
+    /// How the transaction ended.
+    pub fn result(&self) -> &ExecRes {
+        &self.result
+    }
+
+    /// Index of the current step.
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    pub fn current(&self) -> Option<&DebugStep> {
+        self.steps.get(self.cursor)
+    }
+
+    /// The source of the step at `index`, if its contract has a source map.
+    pub fn location(&self, index: usize) -> Option<&CodeLocation> {
+        let step = self.steps.get(index)?;
+        self.locations.get(&step.code_hash)?.get(&step.pc)
+    }
+
+    pub fn goto(&mut self, index: usize) {
+        self.cursor = index.min(self.steps.len().saturating_sub(1));
+    }
+
+    pub fn forward(&mut self, count: usize) {
+        self.goto(self.cursor.saturating_add(count));
+    }
+
+    pub fn back(&mut self, count: usize) {
+        self.cursor = self.cursor.saturating_sub(count);
+    }
+
+    pub fn add_breakpoint(&mut self, breakpoint: Breakpoint) {
+        self.breakpoints.push(breakpoint);
+    }
+
+    pub fn breakpoints(&self) -> &[Breakpoint] {
+        &self.breakpoints
+    }
+
+    pub fn clear_breakpoints(&mut self) {
+        self.breakpoints.clear();
+    }
+
+    /// A breakpoint is at the step, the lines only on their first step.
+    fn is_breakpoint(&self, index: usize) -> bool {
+        let line = |index| self.location(index).map(|location| location.line);
+        self.breakpoints.iter().any(|breakpoint| match breakpoint {
+            Breakpoint::Pc(pc) => self.steps[index].pc == *pc,
+            Breakpoint::Line(number) => {
+                line(index) == Some(*number) && (index == 0 || line(index - 1) != Some(*number))
+            }
+        })
+    }
+
+    /// Move to the next breakpoint, or to the last step if none is hit.
+    pub fn continue_forward(&mut self) -> bool {
+        match (self.cursor + 1..self.steps.len()).find(|index| self.is_breakpoint(*index)) {
+            Some(index) => {
+                self.cursor = index;
+                true
+            }
+            None => {
+                self.goto(usize::MAX);
+                false
+            }
+        }
+    }
+
+    /// Move to the previous breakpoint, or to the first step if none is hit.
+    pub fn continue_back(&mut self) -> bool {
+        match (0..self.cursor)
+            .rev()
+            .find(|index| self.is_breakpoint(*index))
+        {
+            Some(index) => {
+                self.cursor = index;
+                true
+            }
+            None => {
+                self.cursor = 0;
+                false
+            }
+        }
+    }
+
+    /// The slots written before the current step, with their last value.
+    pub fn storage_changes(&self) -> Vec<StorageChange> {
+        let mut changes = BTreeMap::new();
+        for step in &self.steps[..self.cursor.min(self.steps.len())] {
+            if let Some(change) = &step.storage_change {
+                changes.insert((change.address, change.slot), change.clone());
+            }
+        }
+        changes.into_values().collect()
+    }
+
+    /// The current step, where it is in the sources, its stack and the storage written so far.
+    pub fn render(&self) -> String {
+        let Some(step) = self.current() else {
+            return format!("no step, the transaction {}", self.result);
+        };
+        let mut out = format!(
+            "step {}/{}, depth {}, in {}\npc {:#x} {}, gas {} (cost {})\n",
+            self.cursor + 1,
+            self.steps.len(),
+            step.depth,
+            Labeled(step.address),
+            step.pc,
+            step.op_name(),
+            step.gas,
+            step.gas_cost
+        );
+        if let Some(location) = self.location(self.cursor) {
+            let _ = writeln!(out, "at {location}\n{}", location.snippet());
+        }
+        out.push_str("stack:\n");
+        for (index, value) in step.stack.iter().rev().enumerate() {
+            let _ = writeln!(out, "  {index}: {}", hex_quantity(value));
+        }
+        let changes = self.storage_changes();
+        if !changes.is_empty() {
+            out.push_str("storage:\n");
+            for change in changes {
+                let _ = writeln!(
+                    out,
+                    "  {} [{}]: {} -> {}",
+                    Labeled(change.address),
+                    hex_quantity(&change.slot),
+                    hex_quantity(&change.original),
+                    hex_quantity(&change.value)
+                );
+            }
+        }
+        if self.cursor + 1 == self.steps.len() {
+            let _ = writeln!(out, "last step, the transaction {}", self.result);
+        }
+        out
+    }
+
+    fn render_memory(&self) -> String {
+        let Some(step) = self.current() else {
+            return String::new();
+        };
+        let mut out = String::new();
+        for (index, word) in step.memory.chunks(32).enumerate() {
+            let _ = writeln!(out, "{:#06x}: {}", index * 32, hex::encode(word));
+        }
+        out
+    }
+
+    /// Run a command, `None` to quit.
+    fn execute(&mut self, command: &str) -> Option<Result<String, String>> {
+        let words: Vec<&str> = command.split_whitespace().collect();
+        let number = |index: usize| -> Result<Option<usize>, String> {
+            words
+                .get(index)
+                .map(|word| match word.strip_prefix("0x") {
+                    Some(digits) => usize::from_str_radix(digits, 16),
+                    None => word.parse(),
+                })
+                .transpose()
+                .map_err(|_| format!("invalid number `{}`", words[index]))
+        };
+        let res = match words.as_slice() {
+            ["n" | "next", ..] => number(1).map(|count| self.forward(count.unwrap_or(1))),
+            ["p" | "prev", ..] => number(1).map(|count| self.back(count.unwrap_or(1))),
+            ["g" | "goto", _] => number(1).map(|step| self.goto(step.unwrap_or(1).max(1) - 1)),
+            ["c" | "continue"] => {
+                if !self.continue_forward() {
+                    return Some(Ok(format!("no breakpoint hit\n{}", self.render())));
+                }
+                Ok(())
+            }
+            ["rc"] => {
+                if !self.continue_back() {
+                    return Some(Ok(format!("no breakpoint hit\n{}", self.render())));
+                }
+                Ok(())
+            }
+            ["b" | "break"] if self.breakpoints.is_empty() => {
+                return Some(Ok("no breakpoint".to_string()))
+            }
+            ["b" | "break"] => {
+                let breakpoints: Vec<String> =
+                    self.breakpoints.iter().map(ToString::to_string).collect();
+                return Some(Ok(breakpoints.join("\n")));
+            }
+            ["b" | "break", kind @ ("pc" | "line"), _] => {
+                return Some(number(2).map(|value| {
+                    let value = value.unwrap_or_default();
+                    let breakpoint = match *kind {
+                        "pc" => Breakpoint::Pc(value),
+                        _ => Breakpoint::Line(value),
+                    };
+                    let added = format!("breakpoint at {breakpoint}");
+                    self.add_breakpoint(breakpoint);
+                    added
+                }))
+            }
+            ["d" | "delete"] => {
+                self.clear_breakpoints();
+                return Some(Ok("deleted the breakpoints".to_string()));
+            }
+            ["m" | "memory"] => return Some(Ok(self.render_memory())),
+            ["h" | "help"] => return Some(Ok(HELP.to_string())),
+            ["q" | "quit"] => return None,
+            _ => Err(format!("unknown command `{command}`, `h` for the commands")),
+        };
+        Some(res.map(|()| self.render()))
+    }
+
+    /// Read the commands from `input` until it ends or `q`, see `h` for the commands.
+    pub fn run(&mut self, mut input: impl BufRead, mut output: impl Write) -> io::Result<()> {
+        writeln!(output, "{}", self.render())?;
+        let mut last = "n".to_string();
+        let mut line = String::new();
+        loop {
+            write!(output, "> ")?;
+            output.flush()?;
+            line.clear();
+            if input.read_line(&mut line)? == 0 {
+                return Ok(());
+            }
+            let command = match line.trim() {
+                "" => last.clone(),
+                command => command.to_string(),
+            };
+            match self.execute(&command) {
+                Some(Ok(text)) => writeln!(output, "{text}")?,
+                Some(Err(message)) => writeln!(output, "{message}")?,
+                None => return Ok(()),
+            }
+            last = command;
+        }
+    }
+}
@@ -0,0 +1,189 @@
+//! Traces in the formats of geth's `debug_traceTransaction`, to reuse the existing tooling.
+
+use super::trace::{CallKind, CallTrace};
+use crate::common::revert::RevertReason;
+use revm::{
+    interpreter::{opcode, InstructionResult, Interpreter},
+    primitives::{hex, Address, U256},
+};
+use serde::Serialize;
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs, io,
+    path::Path,
+};
+
+/// A step of the execution, as logged by the default struct logger.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StructLog {
+    pub pc: u64,
+    pub op: String,
+    /// Remaining before the step
+    pub gas: u64,
+    pub gas_cost: u64,
+    /// 1 for the transaction itself
+    pub depth: u64,
+    /// Hex values, the top last
+    pub stack: Vec<String>,
+    /// 32 bytes words, in hex without prefix
+    pub memory: Vec<String>,
+    /// The slots of the contract read or written so far, only at `SLOAD` and `SSTORE`
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub storage: BTreeMap<String, String>,
+}
+
+/// Output of `debug_traceTransaction` with the default tracer.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StructLogTrace {
+    pub gas: u64,
+    pub failed: bool,
+    /// In hex without prefix
+    pub return_value: String,
+    pub struct_logs: Vec<StructLog>,
+}
+
+/// Output of `debug_traceTransaction` with the `callTracer`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CallFrame {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub from: String,
+    pub to: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value: Option<String>,
+    pub gas: String,
+    pub gas_used: String,
+    pub input: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub revert_reason: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub calls: Vec<CallFrame>,
+}
+
+fn hex_address(address: &Address) -> String {
+    format!("0x{}", hex::encode(address))
+}
+
+fn hex_word(value: &U256) -> String {
+    hex::encode(value.to_be_bytes::<32>())
+}
+
+/// `0x` and the digits without the leading zeros, as in the JSON-RPC quantities.
+pub(crate) fn hex_quantity(value: &U256) -> String {
+    let word = hex_word(value);
+    let digits = word.trim_start_matches('0');
+    format!("0x{}", if digits.is_empty() { "0" } else { digits })
+}
+
+impl From<&CallTrace> for CallFrame {
+    fn from(trace: &CallTrace) -> Self {
+        let kind = match trace.kind {
+            CallKind::Call => "CALL",
+            CallKind::CallCode => "CALLCODE",
+            CallKind::DelegateCall => "DELEGATECALL",
+            CallKind::StaticCall => "STATICCALL",
+            CallKind::Create => "CREATE",
+            CallKind::Create2 => "CREATE2",
+        };
+        let error = match trace.result {
+            result if result.is_ok() => None,
+            result if result.is_revert() => Some("execution reverted".to_string()),
+            result => Some(format!("{result:?}")),
+        };
+        let revert_reason = match RevertReason::decode(&trace.output) {
+            RevertReason::Error(message) if trace.is_revert() => Some(message),
+            _ => None,
+        };
+        CallFrame {
+            kind: kind.to_string(),
+            from: hex_address(&trace.from),
+            to: hex_address(&trace.to),
+            // the delegate calls keep the value of their caller
+            value: (trace.kind != CallKind::DelegateCall && trace.kind != CallKind::StaticCall)
+                .then(|| hex_quantity(&trace.value)),
+            gas: format!("{:#x}", trace.gas_limit),
+            gas_used: format!("{:#x}", trace.gas_used),
+            input: format!("0x{}", hex::encode(&trace.input)),
+            output: (!trace.output.is_empty()).then(|| format!("0x{}", hex::encode(&trace.output))),
+            error,
+            revert_reason,
+            calls: trace.children.iter().map(CallFrame::from).collect(),
+        }
+    }
+}
+
+/// Records the steps through the inspector hooks.
+#[derive(Debug, Default)]
+pub struct StructLogger {
+    logs: Vec<StructLog>,
+    /// Index and remaining gas of the steps not ended yet, the innermost last
+    pending: Vec<(usize, u64)>,
+    /// The slots read or written per contract
+    storage: HashMap<Address, BTreeMap<String, String>>,
+}
+
+impl StructLogger {
+    pub fn step(&mut self, interp: &Interpreter, depth: u64) {
+        let op = interp.current_opcode();
+        let gas = interp.gas.remaining();
+        let stack = interp.stack.data();
+        let mut storage = BTreeMap::new();
+        if op == opcode::SSTORE && stack.len() >= 2 {
+            let slots = self.storage.entry(interp.contract.address).or_default();
+            let (key, value) = (stack[stack.len() - 1], stack[stack.len() - 2]);
+            slots.insert(hex_word(&key), hex_word(&value));
+            storage = slots.clone();
+        }
+
+        self.pending.push((self.logs.len(), gas));
+        self.logs.push(StructLog {
+            pc: interp.program_counter() as u64,
+            op: opcode::OPCODE_JUMPMAP[op as usize]
+                .map_or_else(|| format!("opcode {op:#04x}"), ToString::to_string),
+            gas,
+            gas_cost: 0,
+            depth,
+            stack: stack.iter().map(hex_quantity).collect(),
+            memory: interp.memory.data().chunks(32).map(hex::encode).collect(),
+            storage,
+        });
+    }
+
+    pub fn step_end(&mut self, interp: &Interpreter, result: InstructionResult) {
+        let Some((index, gas)) = self.pending.pop() else {
+            return;
+        };
+        let log = &mut self.logs[index];
+        log.gas_cost = gas.saturating_sub(interp.gas.remaining());
+        // the loaded value is only known now
+        if log.op == "SLOAD" && result == InstructionResult::Continue {
+            if let (Some(key), Ok(value)) = (log.stack.last(), interp.stack.peek(0)) {
+                let key =
+                    U256::from_str_radix(key.trim_start_matches("0x"), 16).unwrap_or_default();
+                let slots = self.storage.entry(interp.contract.address).or_default();
+                slots.insert(hex_word(&key), hex_word(&value));
+                log.storage = slots.clone();
+            }
+        }
+    }
+
+    /// The steps of the last transaction.
+    pub fn take(&mut self) -> Vec<StructLog> {
+        self.pending.clear();
+        self.storage.clear();
+        std::mem::take(&mut self.logs)
+    }
+}
+
+/// Pretty JSON of a trace, e.g. to diff it with the one of a node.
+pub fn write_json(trace: &impl Serialize, path: impl AsRef<Path>) -> io::Result<()> {
+    let json = serde_json::to_string_pretty(trace)?;
+    fs::write(path, json)
+}
@@ -0,0 +1,205 @@
+use super::{
+    debugger::DebugRecorder,
+    geth::StructLogger,
+    mock::CallMocks,
+    stack_trace::RevertRecorder,
+    trace::{CallKind, CallTrace, Tracer},
+};
+use crate::{
+    coverage::HitRecorder,
+    fuzz::dictionary,
+    gas::{GasProfiler, ProfileFrame},
+};
+use revm::{
+    interpreter::{
+        opcode, CallInputs, CallScheme, CreateInputs, Gas, InstructionResult, Interpreter,
+    },
+    primitives::{Address, Bytes, CreateScheme},
+    Database, EVMData, Inspector,
+};
+
+/// Hooks of the provider into every execution.
+#[derive(Debug, Default)]
+pub struct ProviderInspector {
+    /// Run the top level call in a static context, as with `STATICCALL`.
+    pub static_call: bool,
+    /// Records the call tree when tracing
+    pub tracer: Option<Tracer>,
+    /// Records each step for the geth traces
+    pub struct_logger: Option<StructLogger>,
+    /// Records the frames of the failed transactions
+    pub reverts: RevertRecorder,
+    /// Counts the executed instructions for the coverage
+    pub coverage: Option<HitRecorder>,
+    /// Records each step to debug a transaction executed again
+    pub debugger: Option<DebugRecorder>,
+    /// Attributes the gas of each step to its calls and internal functions
+    pub profiler: Option<GasProfiler>,
+    /// Answers the mocked calls instead of their callee
+    pub mocks: CallMocks,
+}
+
+impl<DB: Database> Inspector<DB> for ProviderInspector {
+    fn step(&mut self, interp: &mut Interpreter, data: &mut EVMData<'_, DB>) -> InstructionResult {
+        if interp.current_opcode() == opcode::SSTORE {
+            if let (Ok(key), Ok(value)) = (interp.stack.peek(0), interp.stack.peek(1)) {
+                dictionary::collect_value(key);
+                dictionary::collect_value(value);
+            }
+        }
+        if let Some(logger) = &mut self.struct_logger {
+            logger.step(interp, data.journaled_state.depth());
+        }
+        self.reverts.step(interp.program_counter());
+        if let Some(coverage) = &mut self.coverage {
+            coverage.step(interp);
+        }
+        if let Some(debugger) = &mut self.debugger {
+            debugger.step(interp, data.journaled_state.depth());
+        }
+        if let Some(profiler) = &mut self.profiler {
+            profiler.step(interp);
+        }
+        InstructionResult::Continue
+    }
+
+    fn step_end(
+        &mut self,
+        interp: &mut Interpreter,
+        data: &mut EVMData<'_, DB>,
+        eval: InstructionResult,
+    ) -> InstructionResult {
+        if let Some(logger) = &mut self.struct_logger {
+            logger.step_end(interp, eval);
+        }
+        if let Some(debugger) = &mut self.debugger {
+            debugger.step_end(interp, &data.journaled_state.state, eval);
+        }
+        if let Some(profiler) = &mut self.profiler {
+            profiler.step_end(interp);
+        }
+        InstructionResult::Continue
+    }
+
+    fn call(
+        &mut self,
+        data: &mut EVMData<'_, DB>,
+        inputs: &mut CallInputs,
+    ) -> (InstructionResult, Gas, Bytes) {
+        if self.static_call && data.journaled_state.depth() == 0 {
+            inputs.is_static = true;
+        }
+        self.reverts
+            .start(Some(inputs.context.code_address), &inputs.input);
+        if let Some(profiler) = &mut self.profiler {
+            profiler.start(ProfileFrame::Call {
+                code_address: inputs.context.code_address,
+                selector: inputs.input.iter().take(4).copied().collect(),
+            });
+        }
+        if let Some(tracer) = &mut self.tracer {
+            let kind = match inputs.context.scheme {
+                // the top level call of `staticcall`
+                CallScheme::Call if inputs.is_static => CallKind::StaticCall,
+                scheme => scheme.into(),
+            };
+            tracer.start(CallTrace {
+                kind,
+                depth: data.journaled_state.depth() as usize,
+                from: inputs.context.caller,
+                to: inputs.context.address,
+                code_address: inputs.context.code_address,
+                value: inputs.transfer.value,
+                gas_limit: inputs.gas_limit,
+                gas_used: 0,
+                input: inputs.input.clone(),
+                output: Bytes::new(),
+                result: InstructionResult::Continue,
+                children: Vec::new(),
+            });
+        }
+        if matches!(
+            inputs.context.scheme,
+            CallScheme::Call | CallScheme::StaticCall
+        ) {
+            if let Some((ret, out)) = self.mocks.find(inputs.contract, &inputs.input) {
+                return (ret, Gas::new(inputs.gas_limit), out);
+            }
+        }
+        (InstructionResult::Continue, Gas::new(0), Bytes::new())
+    }
+
+    fn call_end(
+        &mut self,
+        _data: &mut EVMData<'_, DB>,
+        _inputs: &CallInputs,
+        remaining_gas: Gas,
+        ret: InstructionResult,
+        out: Bytes,
+    ) -> (InstructionResult, Gas, Bytes) {
+        dictionary::collect_words(&out);
+        self.reverts.end(ret, &out);
+        if let Some(profiler) = &mut self.profiler {
+            profiler.end(remaining_gas.spend());
+        }
+        if let Some(tracer) = &mut self.tracer {
+            tracer.end(ret, remaining_gas.spend(), &out, None);
+        }
+        (ret, remaining_gas, out)
+    }
+
+    fn create(
+        &mut self,
+        data: &mut EVMData<'_, DB>,
+        inputs: &mut CreateInputs,
+    ) -> (InstructionResult, Option<Address>, Gas, Bytes) {
+        self.reverts.start(None, &[]);
+        if let Some(profiler) = &mut self.profiler {
+            profiler.start(ProfileFrame::Create);
+        }
+        if let Some(tracer) = &mut self.tracer {
+            let kind = match inputs.scheme {
+                CreateScheme::Create => CallKind::Create,
+                CreateScheme::Create2 { .. } => CallKind::Create2,
+            };
+            tracer.start(CallTrace {
+                kind,
+                depth: data.journaled_state.depth() as usize,
+                from: inputs.caller,
+                to: Address::ZERO,
+                code_address: Address::ZERO,
+                value: inputs.value,
+                gas_limit: inputs.gas_limit,
+                gas_used: 0,
+                input: inputs.init_code.clone(),
+                output: Bytes::new(),
+                result: InstructionResult::Continue,
+                children: Vec::new(),
+            });
+        }
+        (InstructionResult::Continue, None, Gas::new(0), Bytes::new())
+    }
+
+    fn create_end(
+        &mut self,
+        _data: &mut EVMData<'_, DB>,
+        _inputs: &CreateInputs,
+        ret: InstructionResult,
+        address: Option<Address>,
+        remaining_gas: Gas,
+        out: Bytes,
+    ) -> (InstructionResult, Option<Address>, Gas, Bytes) {
+        if let Some(address) = address.filter(|_| ret.is_ok()) {
+            dictionary::collect_address(address);
+            dictionary::collect_code(&out);
+        }
+        self.reverts.end(ret, &out);
+        if let Some(profiler) = &mut self.profiler {
+            profiler.end(remaining_gas.spend());
+        }
+        if let Some(tracer) = &mut self.tracer {
+            tracer.end(ret, remaining_gas.spend(), &out, address);
+        }
+        (ret, address, remaining_gas, out)
+    }
+}
@@ -0,0 +1,58 @@
+//! Calls answered without executing the code they target.
+
+use revm::{
+    interpreter::InstructionResult,
+    primitives::{Address, Bytes},
+};
+
+#[derive(Debug, Clone)]
+struct MockedCall {
+    callee: Address,
+    /// Prefix of the calldata, e.g. only the selector to mock all the calls to a function
+    calldata: Bytes,
+    reverts: bool,
+    return_data: Bytes,
+}
+
+/// The mocked calls, matched on their callee and a prefix of their calldata.
+#[derive(Debug, Clone, Default)]
+pub struct CallMocks {
+    mocks: Vec<MockedCall>,
+}
+
+impl CallMocks {
+    /// Answer the calls to `callee` with this calldata prefix, replacing a previous mock of it.
+    pub fn add(&mut self, callee: Address, calldata: Bytes, reverts: bool, return_data: Bytes) {
+        self.mocks
+            .retain(|mock| mock.callee != callee || mock.calldata != calldata);
+        self.mocks.push(MockedCall {
+            callee,
+            calldata,
+            reverts,
+            return_data,
+        });
+    }
+
+    pub fn clear(&mut self) {
+        self.mocks.clear();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.mocks.is_empty()
+    }
+
+    /// The result of the mock with the longest prefix of `input`, if any.
+    pub fn find(&self, callee: Address, input: &[u8]) -> Option<(InstructionResult, Bytes)> {
+        let mock = self
+            .mocks
+            .iter()
+            .filter(|mock| mock.callee == callee && input.starts_with(&mock.calldata))
+            .max_by_key(|mock| mock.calldata.len())?;
+        let result = if mock.reverts {
+            InstructionResult::Revert
+        } else {
+            InstructionResult::Return
+        };
+        Some((result, mock.return_data.clone()))
+    }
+}
@@ -1,3 +1,14 @@
 pub mod db;
+pub mod deal;
+pub mod debugger;
+pub mod geth;
+pub mod inspector;
+pub mod mock;
+pub mod stack_trace;
+pub mod state_diff;
+pub mod trace;
 
 pub use db::Provider;
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,133 @@
+//! Where the calls were when a transaction reverted, down to the revert that failed it.
+
+use crate::compilers::solidity::source_map::CodeLocation;
+use revm::{
+    interpreter::InstructionResult,
+    primitives::{Address, Bytes},
+};
+use std::fmt;
+
+/// A call of the reverted stack, before it is mapped to the sources.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RevertFrame {
+    /// Where the code was loaded from, `None` for the creates
+    pub code_address: Option<Address>,
+    pub selector: Option<[u8; 4]>,
+    /// The reverting instruction, or the call into the next frame
+    pub pc: usize,
+}
+
+#[derive(Debug)]
+struct ActiveFrame {
+    frame: RevertFrame,
+    /// The last call which failed, if none was made since
+    failed_call: Option<FailedCall>,
+}
+
+#[derive(Debug)]
+struct FailedCall {
+    /// Of the call instruction
+    pc: usize,
+    output: Bytes,
+    frames: Vec<RevertFrame>,
+}
+
+/// Follows the program counters through the inspector hooks.
+#[derive(Debug, Default)]
+pub struct RevertRecorder {
+    /// The calls not ended yet, the innermost last
+    active: Vec<ActiveFrame>,
+    last: Option<Vec<RevertFrame>>,
+}
+
+impl RevertRecorder {
+    pub fn start(&mut self, code_address: Option<Address>, input: &[u8]) {
+        if let Some(parent) = self.active.last_mut() {
+            parent.failed_call = None;
+        }
+        self.active.push(ActiveFrame {
+            frame: RevertFrame {
+                code_address,
+                selector: input.get(..4).and_then(|selector| selector.try_into().ok()),
+                pc: 0,
+            },
+            failed_call: None,
+        });
+    }
+
+    pub fn step(&mut self, pc: usize) {
+        if let Some(active) = self.active.last_mut() {
+            active.frame.pc = pc;
+        }
+    }
+
+    pub fn end(&mut self, result: InstructionResult, output: &Bytes) {
+        let Some(active) = self.active.pop() else {
+            return;
+        };
+        let frames = (!result.is_ok()).then(|| match active.failed_call {
+            // the failure of the call was bubbled up, as by solidity
+            Some(call) if &call.output == output => {
+                let frame = RevertFrame {
+                    pc: call.pc,
+                    ..active.frame
+                };
+                [vec![frame], call.frames].concat()
+            }
+            _ => vec![active.frame],
+        });
+        match self.active.last_mut() {
+            Some(parent) => {
+                parent.failed_call = frames.map(|frames| FailedCall {
+                    // not moved since the call
+                    pc: parent.frame.pc,
+                    output: output.clone(),
+                    frames,
+                })
+            }
+            None => self.last = frames,
+        }
+    }
+
+    /// The frames of the last transaction, from the outermost call, if it failed.
+    pub fn take(&mut self) -> Option<Vec<RevertFrame>> {
+        self.active.clear();
+        self.last.take()
+    }
+}
+
+/// A frame mapped to the sources.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StackFrame {
+    /// Name or label of the contract, or its address
+    pub contract: String,
+    pub function: Option<String>,
+    /// `None` if the contract has no source map, or the instruction is not mapped to a file
+    pub location: Option<CodeLocation>,
+}
+
+/// The frames of a failed transaction, the reverting one last.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StackTrace(pub Vec<StackFrame>);
+
+/// The innermost frame first, as the stack traces of the other languages.
+impl fmt::Display for StackTrace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for frame in self.0.iter().rev() {
+            write!(f, "  at {}", frame.contract)?;
+            if let Some(function) = &frame.function {
+                write!(f, "::{function}")?;
+            }
+            match &frame.location {
+                Some(location) => {
+                    writeln!(f, " ({location})")?;
+                    for line in location.snippet().lines() {
+                        writeln!(f, "    {line}")?;
+                    }
+                }
+                None => writeln!(f)?,
+            }
+        }
+        Ok(())
+    }
+}
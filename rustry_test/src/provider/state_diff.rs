@@ -0,0 +1,161 @@
+//! What a committed transaction changed in the accounts it touched.
+
+use super::geth::hex_quantity;
+use crate::common::labels::Labeled;
+use revm::{
+    db::{AccountState, CacheDB, EmptyDB},
+    primitives::{keccak256, AccountInfo, Address, Bytes, State, U256},
+};
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Write as _},
+};
+
+/// A value before and after the transaction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Change<T> {
+    pub before: T,
+    pub after: T,
+}
+
+impl<T: PartialEq> Change<T> {
+    /// `None` if the value did not change.
+    fn new(before: T, after: T) -> Option<Self> {
+        (before != after).then_some(Change { before, after })
+    }
+}
+
+/// The changes of an account, the unchanged fields being `None`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AccountDiff {
+    pub balance: Option<Change<U256>>,
+    pub nonce: Option<Change<u64>>,
+    pub code: Option<Change<Bytes>>,
+    pub storage: BTreeMap<U256, Change<U256>>,
+}
+
+impl AccountDiff {
+    pub fn is_empty(&self) -> bool {
+        self.balance.is_none()
+            && self.nonce.is_none()
+            && self.code.is_none()
+            && self.storage.is_empty()
+    }
+}
+
+/// The accounts changed by a transaction.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StateDiff {
+    pub accounts: BTreeMap<Address, AccountDiff>,
+}
+
+fn code(db: &CacheDB<EmptyDB>, info: &AccountInfo) -> Bytes {
+    match &info.code {
+        Some(code) => code.original_bytes(),
+        None => db
+            .contracts
+            .get(&info.code_hash)
+            .map(|code| code.original_bytes())
+            .unwrap_or_default(),
+    }
+}
+
+impl StateDiff {
+    /// The changes of `state`, to compute before committing it to `db`.
+    pub fn new(db: &CacheDB<EmptyDB>, state: &State) -> Self {
+        let mut accounts = BTreeMap::new();
+        for (address, account) in state.iter().filter(|(_, account)| account.is_touched()) {
+            let before = db
+                .accounts
+                .get(address)
+                .filter(|before| before.account_state != AccountState::NotExisting);
+            let before_info = before.map(|before| before.info.clone()).unwrap_or_default();
+            let destroyed = account.is_selfdestructed();
+            let after_info = if destroyed {
+                AccountInfo::default()
+            } else {
+                account.info.clone()
+            };
+
+            let mut storage: BTreeMap<U256, Change<U256>> = account
+                .storage
+                .iter()
+                .filter_map(|(slot, value)| {
+                    let after = if destroyed {
+                        U256::ZERO
+                    } else {
+                        value.present_value
+                    };
+                    Some((*slot, Change::new(value.previous_or_original_value, after)?))
+                })
+                .collect();
+            // the slots which were not touched are cleared too
+            if destroyed || account.is_created() {
+                for (slot, value) in before.iter().flat_map(|before| &before.storage) {
+                    if !account.storage.contains_key(slot) {
+                        storage
+                            .extend(Change::new(*value, U256::ZERO).map(|change| (*slot, change)));
+                    }
+                }
+            }
+
+            let diff = AccountDiff {
+                balance: Change::new(before_info.balance, after_info.balance),
+                nonce: Change::new(before_info.nonce, after_info.nonce),
+                code: (before_info.code_hash != after_info.code_hash)
+                    .then(|| Change::new(code(db, &before_info), code(db, &after_info)))
+                    .flatten(),
+                storage,
+            };
+            if !diff.is_empty() {
+                accounts.insert(*address, diff);
+            }
+        }
+        StateDiff { accounts }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.accounts.is_empty()
+    }
+
+    /// One account per paragraph, with the slots named by `slot_name` if it knows them.
+    pub fn render(&self, slot_name: impl Fn(&Address, &U256) -> Option<String>) -> String {
+        let mut out = String::new();
+        for (address, diff) in &self.accounts {
+            let _ = writeln!(out, "{}:", Labeled(*address));
+            if let Some(Change { before, after }) = &diff.balance {
+                let _ = writeln!(out, "  balance: {before} -> {after}");
+            }
+            if let Some(Change { before, after }) = &diff.nonce {
+                let _ = writeln!(out, "  nonce: {before} -> {after}");
+            }
+            if let Some(Change { before, after }) = &diff.code {
+                let _ = writeln!(
+                    out,
+                    "  code: {} bytes ({}) -> {} bytes ({})",
+                    before.len(),
+                    keccak256(before),
+                    after.len(),
+                    keccak256(after)
+                );
+            }
+            for (slot, Change { before, after }) in &diff.storage {
+                let name =
+                    slot_name(address, slot).unwrap_or_else(|| format!("[{}]", hex_quantity(slot)));
+                let _ = writeln!(
+                    out,
+                    "  {name}: {} -> {}",
+                    hex_quantity(before),
+                    hex_quantity(after)
+                );
+            }
+        }
+        out
+    }
+}
+
+impl fmt::Display for StateDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.render(|_, _| None))
+    }
+}
@@ -0,0 +1,1029 @@
+use super::{
+    db::{Cheats, DeployError, ExecRes, Frontend},
+    debugger::{Breakpoint, StorageChange},
+    geth,
+    stack_trace::StackTrace,
+    state_diff::Change,
+    trace::{CallKind, Verbosity},
+    Provider,
+};
+use crate::{
+    common::{
+        assertions::{check_emit, ExpectedCall, ExpectedLog},
+        contract::{ContractInfo, FunctionInfo},
+        labels::Labeled,
+        revert::{PanicCode, RevertReason},
+    },
+    compilers::solidity::source_map::{ContractSources, InternalFunction, SourceFile},
+    coverage::BranchCoverage,
+    fuzz::{
+        self,
+        invariant::{self, Call, InvariantConfig, InvariantRun},
+        FuzzConfig,
+    },
+    utils::{
+        abi::get_sig,
+        codec::{encode, AbiValue},
+        slots::{
+            load_packed, proxy_admin, proxy_implementation, store_packed, EIP1967_BEACON_SLOT,
+            EIP1967_IMPLEMENTATION_SLOT,
+        },
+    },
+};
+use revm::primitives::{Address, Bytes, Halt, B256, U256};
+use std::{collections::BTreeMap, panic};
+
+/// Wrap some runtime code in a constructor returning it.
+pub fn initcode(runtime: &[u8]) -> Bytes {
+    let len = u8::try_from(runtime.len()).expect("runtime too big");
+    [
+        &[
+            0x60, len, 0x80, 0x60, 0x0b, 0x60, 0x00, 0x39, 0x60, 0x00, 0xf3,
+        ],
+        runtime,
+    ]
+    .concat()
+    .into()
+}
+
+pub fn deploy(provider: &mut Provider, runtime: &[u8]) -> Address {
+    provider.deploy(initcode(runtime)).unwrap()
+}
+
+// mstore(0, 42) log1(0, 32, 1)
+const EMIT: [u8; 13] = [
+    0x60, 0x2a, 0x60, 0x00, 0x52, 0x60, 0x01, 0x60, 0x20, 0x60, 0x00, 0xa1, 0x00,
+];
+
+#[test]
+fn deploy_receipt() {
+    let mut provider = Provider::default();
+    let res = provider.create(initcode(&EMIT));
+    res.success();
+    let address = res.receipt().created_address.unwrap();
+    assert_ne!(address, Address::ZERO);
+    assert!(res.gas_used() > 0);
+    assert_eq!(
+        provider
+            .call(address, Bytes::new())
+            .receipt()
+            .created_address,
+        None
+    );
+}
+
+#[test]
+fn call_logs() {
+    let mut provider = Provider::default();
+    let emitter = deploy(&mut provider, &EMIT);
+
+    let res = provider.call(emitter, Bytes::new());
+    res.success();
+    let log = &res.logs()[0];
+    assert_eq!(log.address, emitter);
+    assert_eq!(log.topics, vec![B256::with_last_byte(1)]);
+    assert_eq!(
+        log.data.to_vec(),
+        U256::from(42).to_be_bytes::<32>().to_vec()
+    );
+}
+
+#[test]
+fn record_logs() {
+    let mut provider = Provider::default();
+    let emitter = deploy(&mut provider, &EMIT);
+
+    provider.call(emitter, Bytes::new());
+    provider.record_logs();
+    provider.call(emitter, Bytes::new());
+    provider.staticcall(emitter, Bytes::new());
+    provider.call(emitter, Bytes::new());
+
+    assert_eq!(provider.recorded_logs().len(), 2);
+    assert!(provider.recorded_logs().is_empty());
+}
+
+// mstore(0, shl(224, 0x4e487b71)) mstore(4, 0x11) revert(0, 36)
+const PANIC: [u8; 21] = [
+    0x63, 0x4e, 0x48, 0x7b, 0x71, 0x60, 0xe0, 0x1b, 0x60, 0x00, 0x52, 0x60, 0x11, 0x60, 0x04, 0x52,
+    0x60, 0x24, 0x60, 0x00, 0xfd,
+];
+
+#[test]
+fn revert_reason() {
+    let mut provider = Provider::default();
+    let panics = deploy(&mut provider, &PANIC);
+    let emitter = deploy(&mut provider, &EMIT);
+
+    let res = provider.call(panics, Bytes::new());
+    assert_eq!(
+        res.revert_reason(),
+        Some(RevertReason::Panic(PanicCode::ArithmeticOverflow))
+    );
+    assert_eq!(provider.call(emitter, Bytes::new()).revert_reason(), None);
+}
+
+#[test]
+#[should_panic(expected = "call reverted: Panic(0x11): arithmetic overflow or underflow")]
+fn success_shows_reason() {
+    let mut provider = Provider::default();
+    let panics = deploy(&mut provider, &PANIC);
+    provider.call(panics, Bytes::new()).success();
+}
+
+// sstore(0, add(sload(0), 1)) and return the new value
+const COUNTER: [u8; 18] = [
+    0x60, 0x00, 0x54, 0x60, 0x01, 0x01, 0x80, 0x60, 0x00, 0x55, 0x60, 0x00, 0x52, 0x60, 0x20, 0x60,
+    0x00, 0xf3,
+];
+
+fn count(res: ExecRes) -> U256 {
+    res.success();
+    U256::try_from_be_slice(res.get_data()).unwrap()
+}
+
+#[test]
+fn staticcall_is_static() {
+    let mut provider = Provider::default();
+    let counter = deploy(&mut provider, &COUNTER);
+    let emitter = deploy(&mut provider, &EMIT);
+
+    assert!(matches!(
+        provider.staticcall(counter, Bytes::new()),
+        ExecRes::Halt(Halt::StateChangeDuringStaticCall, _)
+    ));
+    assert!(matches!(
+        provider.staticcall(emitter, Bytes::new()),
+        ExecRes::Halt(Halt::StateChangeDuringStaticCall, _)
+    ));
+    // the static context does not leak to the next calls
+    assert_eq!(count(provider.call(counter, Bytes::new())), U256::from(1));
+}
+
+#[test]
+fn eth_call_discards() {
+    let mut provider = Provider::default();
+    let counter = deploy(&mut provider, &COUNTER);
+
+    assert_eq!(
+        count(provider.eth_call(counter, Bytes::new())),
+        U256::from(1)
+    );
+    assert_eq!(
+        count(provider.eth_call(counter, Bytes::new())),
+        U256::from(1)
+    );
+    assert_eq!(count(provider.call(counter, Bytes::new())), U256::from(1));
+    assert_eq!(
+        count(provider.eth_call(counter, Bytes::new())),
+        U256::from(2)
+    );
+}
+
+#[test]
+fn deploy_errors() {
+    let mut provider = Provider::default();
+
+    // revert(0, 0)
+    let err = provider
+        .deploy(vec![0x60, 0x00, 0x60, 0x00, 0xfd].into())
+        .unwrap_err();
+    assert!(matches!(err, DeployError::Revert { ref data, .. } if data.is_empty()));
+    assert_eq!(err.revert_reason(), Some(RevertReason::Empty));
+    assert!(err.gas_used() > 0);
+
+    let err = provider.deploy(vec![0xfe].into()).unwrap_err();
+    assert!(matches!(
+        err,
+        DeployError::Halt {
+            reason: Halt::InvalidFEOpcode,
+            ..
+        }
+    ));
+    assert_eq!(err.revert_reason(), None);
+}
+
+// revert if calldataload(0) == 0x0123..cdef
+const MAGIC: [u8; 47] = [
+    0x60, 0x00, 0x35, 0x7f, 0x01, 0x23, 0x45, 0x67, 0x89, 0xab, 0xcd, 0xef, 0x01, 0x23, 0x45, 0x67,
+    0x89, 0xab, 0xcd, 0xef, 0x01, 0x23, 0x45, 0x67, 0x89, 0xab, 0xcd, 0xef, 0x01, 0x23, 0x45, 0x67,
+    0x89, 0xab, 0xcd, 0xef, 0x14, 0x60, 0x29, 0x57, 0x00, 0x5b, 0x60, 0x00, 0x60, 0x00, 0xfd,
+];
+
+#[test]
+fn fuzz_dictionary() {
+    let config = FuzzConfig {
+        runs: 1000,
+        seed: 1,
+        name: None,
+    };
+    let res = panic::catch_unwind(|| {
+        fuzz::run(&config, |(x,): (U256,)| {
+            let mut provider = Provider::default();
+            let magic = deploy(&mut provider, &MAGIC);
+            provider
+                .call(magic, x.to_be_bytes::<32>().to_vec().into())
+                .success();
+        })
+    });
+    let err = res.unwrap_err();
+    let message = err.downcast_ref::<String>().unwrap();
+    assert_eq!(message, "call reverted: empty revert data");
+}
+
+// mstore(0, sload(0)) sstore(0, calldataload(4)) return(0, 32)
+const STORE: [u8; 17] = [
+    0x60, 0x00, 0x54, 0x60, 0x00, 0x52, 0x60, 0x04, 0x35, 0x60, 0x00, 0x55, 0x60, 0x20, 0x60, 0x00,
+    0xf3,
+];
+
+fn deploy_store(provider: &mut Provider) -> Address {
+    let store = deploy(provider, &STORE);
+    let function = |name: &str, inputs: &[&str], state_mutability: &str| FunctionInfo {
+        name: name.to_string(),
+        inputs: inputs.iter().map(ToString::to_string).collect(),
+        outputs: vec!["uint256".to_string()],
+        state_mutability: state_mutability.to_string(),
+    };
+    provider.register_contract(
+        store,
+        ContractInfo {
+            name: "Store".to_string(),
+            functions: vec![
+                function("set", &["uint256"], "nonpayable"),
+                function("get", &[], "view"),
+            ],
+            errors: Vec::new(),
+            sources: None,
+            storage_layout: None,
+        },
+    );
+    store
+}
+
+fn set(value: u64) -> Bytes {
+    [
+        get_sig("set(uint256)").to_vec(),
+        encode(&[AbiValue::Uint(U256::from(value))]),
+    ]
+    .concat()
+    .into()
+}
+
+#[test]
+fn state_diffs() {
+    let mut provider = Provider::default();
+    provider.record_state_diffs();
+    let store = deploy_store(&mut provider);
+    provider.call(store, set(5)).success();
+    provider.eth_call(store, set(7)).success();
+
+    let diffs = provider.state_diffs();
+    assert_eq!(diffs.len(), 2);
+    let deployed = &diffs[0].accounts[&store];
+    assert_eq!(deployed.code.as_ref().unwrap().after, Bytes::from(STORE));
+    assert_eq!(
+        deployed.nonce,
+        Some(Change {
+            before: 0,
+            after: 1
+        })
+    );
+    let set = &diffs[1];
+    assert_eq!(set.accounts.len(), 2);
+    assert_eq!(
+        set.accounts[&Address::ZERO].nonce,
+        Some(Change {
+            before: 1,
+            after: 2
+        })
+    );
+    assert_eq!(
+        set.accounts[&store].storage,
+        BTreeMap::from([(
+            U256::ZERO,
+            Change {
+                before: U256::ZERO,
+                after: U256::from(5)
+            }
+        )])
+    );
+    let rendered = set.render(|_, slot| (*slot == U256::ZERO).then(|| "value".to_string()));
+    assert!(rendered.contains(&format!("Store ({store}):\n  value: 0x0 -> 0x5\n")));
+    assert!(set.to_string().contains("  [0x0]: 0x0 -> 0x5\n"));
+    assert!(provider.state_diffs().is_empty());
+}
+
+#[test]
+fn proxy_slots() {
+    let mut provider = Provider::default();
+    let implementation = Address::repeat_byte(0x11);
+    let proxy = Address::repeat_byte(0x22);
+    assert_eq!(proxy_implementation(&mut provider, proxy), None);
+    provider.store(
+        proxy,
+        EIP1967_IMPLEMENTATION_SLOT,
+        U256::from_be_bytes(implementation.into_word().0),
+    );
+    assert_eq!(
+        proxy_implementation(&mut provider, proxy),
+        Some(implementation)
+    );
+    assert_eq!(proxy_admin(&provider, proxy), None);
+
+    // mstore(0, implementation) return(0, 32)
+    let beacon = deploy(
+        &mut provider,
+        &[
+            [0x73].as_slice(),
+            implementation.as_slice(),
+            &[0x5f, 0x52, 0x60, 0x20, 0x5f, 0xf3],
+        ]
+        .concat(),
+    );
+    let beacon_proxy = Address::repeat_byte(0x33);
+    provider.store(
+        beacon_proxy,
+        EIP1967_BEACON_SLOT,
+        U256::from_be_bytes(beacon.into_word().0),
+    );
+    assert_eq!(
+        proxy_implementation(&mut provider, beacon_proxy),
+        Some(implementation)
+    );
+
+    store_packed(&mut provider, proxy, U256::ZERO, 20, 1, U256::from(1));
+    store_packed(&mut provider, proxy, U256::ZERO, 21, 8, U256::from(7));
+    assert_eq!(provider.load(proxy, U256::ZERO), U256::from(0x0701) << 160);
+    assert_eq!(
+        load_packed(&provider, proxy, U256::ZERO, 21, 8),
+        U256::from(7)
+    );
+}
+
+/// Reads a decoy slot, then returns `sload(2)` without arguments, else `sload(balance)` where
+/// `balance` pushes the slot of the holder, and `read` unpacks the word loaded.
+fn token(balance: &[u8], read: &[u8]) -> Vec<u8> {
+    let supply = u8::try_from(10 + balance.len() + read.len() + 7).unwrap();
+    [
+        &[0x5f, 0x54, 0x50, 0x36, 0x60, 0x04, 0x14, 0x60, supply, 0x57],
+        balance,
+        &[0x54],
+        read,
+        &[0x5f, 0x52, 0x60, 0x20, 0x5f, 0xf3],
+        &[0x5b, 0x60, 0x02, 0x54, 0x5f, 0x52, 0x60, 0x20, 0x5f, 0xf3],
+    ]
+    .concat()
+}
+
+#[test]
+fn deal_erc20() {
+    let alice = Address::repeat_byte(0xa1);
+    let bob = Address::repeat_byte(0xb0);
+    for balance in [
+        // keccak256(holder . 1)
+        [
+            0x60, 0x04, 0x35, 0x5f, 0x52, 0x60, 0x01, 0x60, 0x20, 0x52, 0x60, 0x40, 0x5f, 0x20,
+        ]
+        .as_slice(),
+        // keccak256(1 . holder)
+        &[
+            0x60, 0x01, 0x5f, 0x52, 0x60, 0x04, 0x35, 0x60, 0x20, 0x52, 0x60, 0x40, 0x5f, 0x20,
+        ],
+        // the holder itself
+        &[0x60, 0x04, 0x35],
+    ] {
+        let mut provider = Provider::default();
+        let token = deploy(&mut provider, &token(balance, &[]));
+        let balance_of = |provider: &mut Provider, who: Address| {
+            let data = [
+                get_sig("balanceOf(address)").to_vec(),
+                encode(&[AbiValue::Address(who)]),
+            ];
+            count(provider.staticcall(token, data.concat().into()))
+        };
+        provider.store(token, U256::from(2), U256::from(1000));
+
+        provider.deal_erc20(token, alice, U256::from(100));
+        provider.deal_erc20(token, bob, U256::from(200));
+        assert_eq!(balance_of(&mut provider, alice), U256::from(100));
+        assert_eq!(balance_of(&mut provider, bob), U256::from(200));
+        assert_eq!(provider.load(token, U256::from(2)), U256::from(1000));
+
+        provider.deal_erc20_adjust_supply(token, alice, U256::from(30));
+        provider.deal_erc20_adjust_supply(token, bob, U256::from(250));
+        assert_eq!(balance_of(&mut provider, alice), U256::from(30));
+        assert_eq!(balance_of(&mut provider, bob), U256::from(250));
+        assert_eq!(provider.load(token, U256::from(2)), U256::from(980));
+    }
+}
+
+#[test]
+fn deal_erc20_packed() {
+    let alice = Address::repeat_byte(0xa1);
+    let mut provider = Provider::default();
+    // the balance in the 12 bytes on the left of the slot of the holder, `sload(holder) >> 160`
+    let token = deploy(
+        &mut provider,
+        &token(&[0x60, 0x04, 0x35], &[0x60, 0xa0, 0x1c]),
+    );
+    let slot = U256::from_be_bytes(alice.into_word().0);
+    let other = U256::from(0xdead);
+    provider.store(token, slot, other);
+    provider.store(token, U256::from(2), U256::from(1000));
+
+    provider.deal_erc20_adjust_supply(token, alice, U256::from(100));
+    assert_eq!(provider.load(token, slot), (U256::from(100) << 160) | other);
+    assert_eq!(provider.load(token, U256::from(2)), U256::from(1100));
+    provider.deal_erc20(token, alice, U256::from(7));
+    assert_eq!(provider.load(token, slot), (U256::from(7) << 160) | other);
+}
+
+#[test]
+#[should_panic(expected = "holds the balance")]
+fn deal_erc20_not_a_token() {
+    let mut provider = Provider::default();
+    let token = deploy(&mut provider, &[0x00]);
+    provider.deal_erc20(token, Address::repeat_byte(0xa1), U256::from(1));
+}
+
+#[test]
+fn invariant_breaks() {
+    let config = InvariantConfig {
+        runs: 10,
+        depth: 20,
+        seed: 1,
+        ..Default::default()
+    };
+    let res = panic::catch_unwind(|| {
+        invariant::run(&config, |run: &mut InvariantRun| {
+            let mut provider = Provider::default();
+            let store = deploy_store(&mut provider);
+            run.drive(&mut provider, |provider| {
+                let res = provider.eth_call(store, Bytes::new());
+                let value = U256::try_from_be_slice(res.get_data()).unwrap();
+                assert!(value < U256::from(1u128 << 100), "too big");
+            });
+        })
+    });
+    let err = res.unwrap_err();
+    assert_eq!(err.downcast_ref::<&str>(), Some(&"too big"));
+}
+
+#[test]
+fn invariant_shrinks() {
+    let call = |x: u64| Call {
+        sender: Address::ZERO,
+        target: Address::ZERO,
+        contract: "Store".to_string(),
+        function: "set(uint256)".to_string(),
+        args: vec![AbiValue::Uint(U256::from(x))],
+    };
+    let calls: Vec<Call> = (0..6).map(call).collect();
+    // fails once both 2 and 4 were called
+    let shrunk = invariant::shrink(calls, |calls| {
+        let end = calls.iter().position(|c| c == &call(4))?;
+        calls[..end]
+            .contains(&call(2))
+            .then(|| calls[..=end].to_vec())
+    });
+    assert_eq!(shrunk, vec![call(2), call(4)]);
+}
+
+/// call(gas(), target, 0, 0, 0, 0, 32) return(0, 32)
+fn proxy(target: Address) -> Vec<u8> {
+    [
+        &[
+            0x60, 0x20, 0x60, 0x00, 0x60, 0x00, 0x60, 0x00, 0x60, 0x00, 0x73,
+        ][..],
+        target.as_slice(),
+        &[0x5a, 0xf1, 0x50, 0x60, 0x20, 0x60, 0x00, 0xf3],
+    ]
+    .concat()
+}
+
+/// call(gas(), target, 0, 0, 0, 0, 0) returndatacopy(0, 0, returndatasize())
+/// then return or revert with the returned data
+fn bubbling_proxy(target: Address) -> Vec<u8> {
+    [
+        &[
+            0x60, 0x00, 0x60, 0x00, 0x60, 0x00, 0x60, 0x00, 0x60, 0x00, 0x73,
+        ][..],
+        target.as_slice(),
+        &[
+            0x5a, 0xf1, 0x3d, 0x60, 0x00, 0x60, 0x00, 0x3e, 0x60, 0x2e, 0x57, 0x3d, 0x60, 0x00,
+            0xfd, 0x5b, 0x3d, 0x60, 0x00, 0xf3,
+        ],
+    ]
+    .concat()
+}
+
+/// calldatacopy(0, 0, calldatasize()) call(gas(), target, 0, 0, calldatasize(), 0, 0)
+/// returndatacopy(0, 0, returndatasize()) then return or revert with the returned data
+fn forwarding_proxy(target: Address) -> Vec<u8> {
+    [
+        &[0x36, 0x5f, 0x5f, 0x37, 0x5f, 0x5f, 0x36, 0x5f, 0x5f, 0x73][..],
+        target.as_slice(),
+        &[
+            0x5a, 0xf1, 0x3d, 0x5f, 0x5f, 0x3e, 0x60, 0x2a, 0x57, 0x3d, 0x5f, 0xfd, 0x5b, 0x3d,
+            0x5f, 0xf3,
+        ],
+    ]
+    .concat()
+}
+
+#[test]
+fn mock_calls() {
+    let mut provider = Provider::default();
+    let oracle = Address::repeat_byte(0x0c);
+    let caller = deploy(&mut provider, &forwarding_proxy(oracle));
+    let price = |arg: u64| -> Bytes {
+        [
+            get_sig("price(uint256)").to_vec(),
+            encode(&[AbiValue::Uint(U256::from(arg))]),
+        ]
+        .concat()
+        .into()
+    };
+    let word = |value: u64| -> Bytes { encode(&[AbiValue::Uint(U256::from(value))]).into() };
+
+    let res = provider.call(caller, price(1));
+    res.success();
+    assert!(res.get_data().is_empty());
+
+    provider.mock_call(oracle, get_sig("price(uint256)").to_vec().into(), word(42));
+    provider.mock_call(oracle, price(1), word(7));
+    assert_eq!(count(provider.call(caller, price(1))), U256::from(7));
+    assert_eq!(count(provider.call(caller, price(2))), U256::from(42));
+    assert_eq!(count(provider.staticcall(oracle, price(2))), U256::from(42));
+    // replaces the previous one
+    provider.mock_call(oracle, price(1), word(8));
+    assert_eq!(count(provider.call(caller, price(1))), U256::from(8));
+
+    provider.mock_call_revert(oracle, price(3), Bytes::from_static(b"stale"));
+    let res = provider.call(caller, price(3));
+    assert!(matches!(&res, ExecRes::Revert(data, _) if data.as_ref() == b"stale"));
+
+    // returns `extcodesize(oracle)`
+    let code_size = deploy(
+        &mut provider,
+        &[
+            &[0x73][..],
+            oracle.as_slice(),
+            &[0x3b, 0x5f, 0x52, 0x60, 0x20, 0x5f, 0xf3],
+        ]
+        .concat(),
+    );
+    assert_eq!(
+        count(provider.staticcall(code_size, Bytes::new())),
+        U256::from(1)
+    );
+
+    provider.clear_mocked_calls();
+    let res = provider.call(caller, price(1));
+    res.success();
+    assert!(res.get_data().is_empty());
+    // an account without code again
+    assert_eq!(
+        count(provider.staticcall(code_size, Bytes::new())),
+        U256::ZERO
+    );
+}
+
+#[test]
+fn expect_calls() {
+    let mut provider = Provider::default();
+    let oracle = Address::repeat_byte(0x0c);
+    let caller = deploy(&mut provider, &forwarding_proxy(oracle));
+    let price = get_sig("price(uint256)").to_vec();
+    let call = |arg: u64| -> Bytes {
+        [price.clone(), encode(&[AbiValue::Uint(U256::from(arg))])]
+            .concat()
+            .into()
+    };
+
+    provider.expect_call(oracle, price.clone(), 2);
+    provider.expect_call_with(ExpectedCall::new(oracle, call(1)).value(U256::ZERO));
+    provider.expect_call(caller, call(3), 0);
+    provider.call(caller, call(1)).success();
+    provider.call(caller, call(2)).success();
+    provider.verify_expected_calls();
+
+    // the calls made before are not counted
+    provider.expect_call(oracle, price.clone(), 1);
+    provider.expect_call_with(ExpectedCall::new(oracle, call(1)).value(U256::from(1)));
+    provider.call(caller, call(1)).success();
+    let message = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        provider.verify_expected_calls()
+    }))
+    .unwrap_err()
+    .downcast::<String>()
+    .unwrap();
+    assert!(message.starts_with("expected call made 0 times instead of 1"));
+    assert!(message.contains("value: 1, gas: any"));
+    // the observed calls, the one to the proxy and the one it forwards
+    assert!(message.contains(&format!("call #0: Call from {} to {caller}", Address::ZERO)));
+    assert!(message.contains(&format!("call #1: Call from {caller} to {oracle}")));
+    assert!(!message.contains("call #2"));
+}
+
+#[test]
+#[should_panic(expected = "no call was made")]
+fn expect_calls_on_drop() {
+    let mut provider = Provider::default();
+    provider.expect_call(Address::repeat_byte(0x0c), Bytes::new(), 1);
+}
+
+/// Maps the instruction at `index` to `snippet`, and the others to the whole `content`.
+fn register_sources(
+    provider: &mut Provider,
+    address: Address,
+    name: &str,
+    content: &str,
+    (index, snippet): (usize, &str),
+) {
+    let offset = content.find(snippet).unwrap();
+    let source_map = format!(
+        "0:{}:0{}{offset}:{}",
+        content.len(),
+        ";".repeat(index),
+        snippet.len()
+    );
+    provider.register_contract(
+        address,
+        ContractInfo {
+            name: name.to_string(),
+            sources: Some(ContractSources {
+                source_map,
+                files: vec![SourceFile {
+                    id: 0,
+                    name: format!("{name}.sol"),
+                    content: content.to_string(),
+                    origin: None,
+                }],
+                functions: Vec::new(),
+            }),
+            ..Default::default()
+        },
+    );
+}
+
+#[test]
+fn revert_stack_trace() {
+    let mut provider = Provider::default();
+    let panics = deploy(&mut provider, &PANIC);
+    let swallowing_proxy = deploy(&mut provider, &proxy(panics));
+    let proxy = deploy(&mut provider, &bubbling_proxy(panics));
+    register_sources(
+        &mut provider,
+        panics,
+        "Panicker",
+        "contract Panicker {\n    fallback() external { revert(); }\n}",
+        (10, "revert()"),
+    );
+    register_sources(
+        &mut provider,
+        proxy,
+        "Proxy",
+        "contract Proxy {\n    fallback() external {\n        target.call();\n    }\n}",
+        (7, "target.call()"),
+    );
+
+    let res = provider.call(proxy, Bytes::new());
+    let StackTrace(frames) = res.receipt().stack_trace.clone().unwrap();
+    let frames: Vec<_> = frames
+        .iter()
+        .map(|frame| {
+            (
+                frame.contract.as_str(),
+                frame.location.as_ref().unwrap().to_string(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        frames,
+        vec![
+            ("Proxy", "Proxy.sol:3:9".to_string()),
+            ("Panicker", "Panicker.sol:2:27".to_string())
+        ]
+    );
+    let message = panic::catch_unwind(|| res.success()).unwrap_err();
+    let message = message.downcast_ref::<String>().unwrap();
+    assert!(
+        message.contains(
+            "  at Panicker (Panicker.sol:2:27)\n      |\n    2 |     fallback() external { revert(); }\n"
+        ),
+        "{message}"
+    );
+    assert!(
+        message.find("at Panicker") < message.find("at Proxy"),
+        "{message}"
+    );
+
+    assert!(provider
+        .call(swallowing_proxy, Bytes::new())
+        .receipt()
+        .stack_trace
+        .is_none());
+}
+
+#[test]
+fn coverage() {
+    let mut provider = Provider::default();
+    provider.record_coverage();
+    let magic = deploy(&mut provider, &MAGIC);
+    register_sources(
+        &mut provider,
+        magic,
+        "Magic",
+        "contract Magic {\n    if (x == MAGIC) revert();\n}",
+        (5, "if (x == MAGIC)"),
+    );
+    provider.call(magic, Bytes::new()).success();
+    provider.call(magic, Bytes::new()).success();
+
+    let report = provider.coverage().unwrap();
+    let file = &report.files["Magic.sol"];
+    assert_eq!(file.lines, BTreeMap::from([(1, 2), (2, 2)]));
+    assert_eq!(
+        file.branches,
+        vec![BranchCoverage {
+            line: 2,
+            taken: Some([0, 2])
+        }]
+    );
+}
+
+#[test]
+fn debugger() {
+    let mut provider = Provider::default();
+    let store = deploy_store(&mut provider);
+    provider.call(store, set(5)).success();
+    assert!(provider.debugger().is_none());
+    provider.record_last_tx();
+    provider.call(store, set(7)).success();
+
+    // executed again from the state before it
+    let mut debugger = provider.debugger().unwrap();
+    assert_eq!(
+        U256::try_from_be_slice(debugger.result().get_data()),
+        Some(U256::from(5))
+    );
+    assert_eq!(debugger.steps().len(), 11);
+    debugger.add_breakpoint(Breakpoint::Pc(0x0b));
+    assert!(debugger.continue_forward());
+    assert_eq!(debugger.current().unwrap().op_name(), "SSTORE");
+    assert!(debugger.storage_changes().is_empty());
+    debugger.forward(1);
+    let change = StorageChange {
+        address: store,
+        slot: U256::ZERO,
+        original: U256::from(5),
+        value: U256::from(7),
+    };
+    assert_eq!(debugger.storage_changes(), vec![change]);
+    assert!(debugger.continue_back() && debugger.cursor() == 7);
+    assert!(!debugger.continue_back() && debugger.cursor() == 0);
+
+    let mut output = Vec::new();
+    debugger
+        .run("b pc 0xb\nc\n\nm\nq\nn\n".as_bytes(), &mut output)
+        .unwrap();
+    let output = String::from_utf8(output).unwrap();
+    assert!(output.contains("step 8/11, depth 1, in Store"));
+    assert!(output.contains("pc 0xb SSTORE"));
+    assert!(output.contains("no breakpoint hit"));
+    assert!(output.contains("last step, the transaction success"));
+    assert!(output.contains(&format!("0x0000: {}", "0".repeat(63) + "5")));
+    assert!(output.contains(&format!("Store ({store}) [0x0]: 0x5 -> 0x7")));
+
+    let magic = deploy(&mut provider, &MAGIC);
+    register_sources(
+        &mut provider,
+        magic,
+        "Magic",
+        "contract Magic {\n    if (x == MAGIC) revert();\n}",
+        (5, "if (x == MAGIC)"),
+    );
+    provider.call(magic, Bytes::new()).success();
+    let mut debugger = provider.debugger().unwrap();
+    debugger.add_breakpoint(Breakpoint::Line(2));
+    assert!(debugger.continue_forward());
+    assert_eq!(debugger.cursor(), 5);
+    assert_eq!(debugger.location(5).unwrap().to_string(), "Magic.sol:2:5");
+    assert!(!debugger.continue_forward());
+}
+
+/// sstore(0, double(42)) with double(x) being x + x, an internal function at 0x0c
+const DOUBLE: [u8; 17] = [
+    0x60, 0x07, 0x60, 0x2a, 0x60, 0x0c, 0x56, 0x5b, 0x60, 0x00, 0x55, 0x00, 0x5b, 0x80, 0x01, 0x90,
+    0x56,
+];
+
+#[test]
+fn gas_profile() {
+    let mut provider = Provider::default();
+    provider.record_gas_profile();
+    let double = deploy(&mut provider, &DOUBLE);
+    let proxy = deploy(&mut provider, &proxy(double));
+    provider.register_contract(
+        double,
+        ContractInfo {
+            name: "Double".to_string(),
+            sources: Some(ContractSources {
+                functions: vec![InternalFunction {
+                    name: "double".to_string(),
+                    entry_point: 0x0c,
+                    parameter_slots: 1,
+                    return_slots: 1,
+                }],
+                ..Default::default()
+            }),
+            ..Default::default()
+        },
+    );
+    provider.register_contract(
+        proxy,
+        ContractInfo {
+            name: "Proxy".to_string(),
+            ..Default::default()
+        },
+    );
+    let res = provider.call(proxy, Bytes::new());
+    res.success();
+
+    let profile = provider.gas_profile().unwrap();
+    assert!(profile.stacks.contains_key("create;PUSH1"));
+    let stacks: BTreeMap<&str, u64> = profile
+        .stacks
+        .iter()
+        .filter_map(|(stack, gas)| Some((stack.strip_prefix("Proxy::fallback;")?, *gas)))
+        .collect();
+    // the call excludes the gas spent by the callee
+    assert_eq!(stacks["CALL"], 2603);
+    assert_eq!(stacks["Double::fallback;PUSH1"], 12);
+    assert_eq!(stacks["Double::fallback;SSTORE"], 22100);
+    assert_eq!(stacks["Double::fallback;double;ADD"], 3);
+    assert_eq!(stacks["Double::fallback;double;JUMP"], 8);
+    assert_eq!(stacks.values().sum::<u64>(), res.gas_used() - 21000);
+    assert!(profile
+        .to_folded()
+        .contains("Proxy::fallback;Double::fallback;double;DUP1 3\n"));
+}
+
+#[test]
+fn call_trace() {
+    let mut provider = Provider::default();
+    provider.set_verbosity(Some(Verbosity::Returns));
+    let store = deploy_store(&mut provider);
+    let proxy = deploy(&mut provider, &proxy(store));
+    let panic = deploy(&mut provider, &PANIC);
+    provider.call(store, set(5)).success();
+    provider.call(proxy, Bytes::new()).success();
+    provider.staticcall(panic, Bytes::new());
+
+    let traces = provider.traces();
+    assert_eq!(traces.len(), 6);
+    assert_eq!(traces[0].kind, CallKind::Create);
+    assert_eq!(traces[0].to, store);
+    let trace = &traces[4];
+    assert_eq!(
+        (trace.kind, trace.to, trace.depth),
+        (CallKind::Call, proxy, 0)
+    );
+    let inner = &trace.children[0];
+    assert_eq!((inner.from, inner.to, inner.depth), (proxy, store, 1));
+    assert!(inner.gas_used > 0 && inner.gas_used < trace.gas_used);
+
+    let rendered = provider.render_trace(&traces[0]);
+    assert!(rendered.contains("new Store@"), "{rendered}");
+    let rendered = provider.render_trace(&traces[3]);
+    assert!(rendered.contains("Store::set(5)"), "{rendered}");
+    assert!(rendered.contains("└─ ← (0)"), "{rendered}");
+    let rendered = provider.render_trace(trace);
+    assert!(rendered.contains("├─ ["), "{rendered}");
+    assert!(rendered.contains("Store::fallback()"), "{rendered}");
+    let rendered = provider.render_trace(&traces[5]);
+    assert!(rendered.contains("[staticcall]"), "{rendered}");
+    assert!(rendered.contains("[Revert] Panic(0x11)"), "{rendered}");
+}
+
+#[test]
+fn gas_report() {
+    let mut provider = Provider::default();
+    provider.record_gas_report();
+    let store = deploy_store(&mut provider);
+    let proxy = deploy(&mut provider, &proxy(store));
+    for value in [5, 0] {
+        let set = [
+            get_sig("set(uint256)").to_vec(),
+            encode(&[AbiValue::Uint(U256::from(value))]),
+        ]
+        .concat();
+        provider.call(store, set.into()).success();
+    }
+    provider.call(proxy, Bytes::new()).success();
+
+    // the proxy is not registered, its call to the store is
+    let report = provider.gas_report().unwrap();
+    let stats = report.stats();
+    assert_eq!(stats.len(), 1);
+    let store_stats = &stats["Store"];
+    assert_eq!(store_stats["set(uint256)"].calls, 2);
+    assert!(store_stats["set(uint256)"].max > store_stats["set(uint256)"].min);
+    assert_eq!(store_stats["fallback()"].calls, 1);
+    assert!(provider.traces().is_empty());
+}
+
+#[test]
+fn labels() {
+    let mut provider = Provider::default();
+    let store = deploy_store(&mut provider);
+    let emitter = deploy(&mut provider, &EMIT);
+    assert_eq!(provider.get_label(&store), Some("Store"));
+    provider.label(emitter, "emitter");
+
+    provider.set_verbosity(Some(Verbosity::Calls));
+    let res = provider.call(emitter, Bytes::new());
+    let labeled = format!("emitter ({emitter})");
+    assert!(res.to_string().contains(&format!("log from {labeled}")));
+    assert!(format!("{res:?}").contains(&labeled));
+    let expected = ExpectedLog::new("Nope()").emitter(emitter);
+    let err = check_emit(res.logs(), &expected).unwrap_err();
+    assert!(err.contains(&format!("address: {labeled}, topics")));
+    let rendered = provider.render_trace(&provider.traces()[0]);
+    assert!(rendered.contains("emitter::fallback()"), "{rendered}");
+
+    drop(provider);
+    assert_eq!(Labeled(emitter).to_string(), emitter.to_string());
+}
+
+#[test]
+fn geth_traces() {
+    let mut provider = Provider::default();
+    provider.record_geth_traces();
+    let store = deploy_store(&mut provider);
+    let proxy = deploy(&mut provider, &proxy(store));
+    let set = |x: u64| -> Bytes {
+        [
+            get_sig("set(uint256)").to_vec(),
+            encode(&[AbiValue::Uint(U256::from(x))]),
+        ]
+        .concat()
+        .into()
+    };
+    provider.call(store, set(5)).success();
+    provider.call(store, set(7)).success();
+
+    let trace = provider.struct_log_trace().unwrap();
+    assert!(!trace.failed);
+    assert_eq!(trace.return_value, format!("{:064x}", 5));
+    let first = &trace.struct_logs[0];
+    assert_eq!(
+        (first.pc, first.op.as_str(), first.gas_cost, first.depth),
+        (0, "PUSH1", 3, 1)
+    );
+    let word = |x: u64| format!("{x:064x}");
+    let sload = trace
+        .struct_logs
+        .iter()
+        .find(|log| log.op == "SLOAD")
+        .unwrap();
+    assert_eq!(sload.storage.get(&word(0)), Some(&word(5)));
+    let sstore = trace
+        .struct_logs
+        .iter()
+        .find(|log| log.op == "SSTORE")
+        .unwrap();
+    assert_eq!(sstore.stack, vec!["0x7", "0x0"]);
+    assert_eq!(sstore.storage.get(&word(0)), Some(&word(7)));
+    assert_eq!(sstore.memory, vec![word(5)]);
+
+    provider.call(proxy, Bytes::new()).success();
+    let frame = provider.call_frame().unwrap();
+    assert_eq!(frame.kind, "CALL");
+    assert_eq!(
+        frame.calls[0].to,
+        format!("0x{}", revm::primitives::hex::encode(store))
+    );
+    assert!(trace_depths(&provider).contains(&2));
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("call.json");
+    geth::write_json(&frame, &path).unwrap();
+    let json: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap();
+    assert_eq!(json["type"], "CALL");
+    assert_eq!(json["calls"][0]["type"], "CALL");
+    assert!(json["calls"][0]["gasUsed"]
+        .as_str()
+        .unwrap()
+        .starts_with("0x"));
+    let json = serde_json::to_value(provider.struct_log_trace().unwrap()).unwrap();
+    assert!(json["structLogs"][0]["gasCost"].is_u64());
+}
+
+fn trace_depths(provider: &Provider) -> Vec<u64> {
+    let trace = provider.struct_log_trace().unwrap();
+    trace.struct_logs.iter().map(|log| log.depth).collect()
+}
@@ -0,0 +1,282 @@
+//! Call tree of the executions, rendered with the ABIs of the registered contracts.
+
+use crate::{
+    common::{contract::ContractInfo, revert::RevertReason},
+    utils::{
+        abi::get_sig,
+        codec::{decode, AbiValue, ParamType},
+    },
+};
+use revm::{
+    interpreter::{CallScheme, InstructionResult},
+    primitives::{hex, Address, Bytes, U256},
+};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Write,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CallKind {
+    Call,
+    CallCode,
+    DelegateCall,
+    StaticCall,
+    Create,
+    Create2,
+}
+
+impl From<CallScheme> for CallKind {
+    fn from(scheme: CallScheme) -> Self {
+        match scheme {
+            CallScheme::Call => CallKind::Call,
+            CallScheme::CallCode => CallKind::CallCode,
+            CallScheme::DelegateCall => CallKind::DelegateCall,
+            CallScheme::StaticCall => CallKind::StaticCall,
+        }
+    }
+}
+
+impl CallKind {
+    pub fn is_create(&self) -> bool {
+        matches!(self, CallKind::Create | CallKind::Create2)
+    }
+}
+
+/// A call or a create, and the ones it made.
+#[derive(Debug, Clone)]
+pub struct CallTrace {
+    pub kind: CallKind,
+    /// 0 for the transaction itself
+    pub depth: usize,
+    pub from: Address,
+    /// The called address, or the created one
+    pub to: Address,
+    /// Where the code was loaded from, differs from `to` for the delegate calls
+    pub code_address: Address,
+    pub value: U256,
+    pub gas_limit: u64,
+    pub gas_used: u64,
+    /// Calldata, or init code for the creates
+    pub input: Bytes,
+    /// Returned data, or runtime code for the creates
+    pub output: Bytes,
+    pub result: InstructionResult,
+    pub children: Vec<CallTrace>,
+}
+
+impl CallTrace {
+    pub fn is_success(&self) -> bool {
+        self.result.is_ok()
+    }
+
+    pub fn is_revert(&self) -> bool {
+        self.result.is_revert()
+    }
+}
+
+/// Builds the call trees through the inspector hooks.
+#[derive(Debug, Default)]
+pub struct Tracer {
+    /// The calls not ended yet, the innermost last
+    stack: Vec<CallTrace>,
+    finished: Option<CallTrace>,
+}
+
+impl Tracer {
+    pub fn start(&mut self, trace: CallTrace) {
+        self.stack.push(trace);
+    }
+
+    /// `to` is set for the creates, once the address is known.
+    pub fn end(
+        &mut self,
+        result: InstructionResult,
+        gas_used: u64,
+        output: &Bytes,
+        to: Option<Address>,
+    ) {
+        let Some(mut trace) = self.stack.pop() else {
+            return;
+        };
+        trace.result = result;
+        trace.gas_used = gas_used;
+        trace.output = output.clone();
+        if let Some(to) = to {
+            trace.to = to;
+            trace.code_address = to;
+        }
+        match self.stack.last_mut() {
+            Some(parent) => parent.children.push(trace),
+            None => self.finished = Some(trace),
+        }
+    }
+
+    /// The trace of the last transaction, if not taken yet.
+    pub fn take(&mut self) -> Option<CallTrace> {
+        self.stack.clear();
+        self.finished.take()
+    }
+}
+
+/// How much of the traces is rendered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Verbosity {
+    /// The call tree, with the failures
+    Calls,
+    /// And what each call returned
+    Returns,
+    /// And the raw calldata and returned data
+    Raw,
+}
+
+impl Verbosity {
+    /// From `RUSTRY_VERBOSITY`, between 1 and 3.
+    pub fn from_env() -> Option<Self> {
+        match std::env::var("RUSTRY_VERBOSITY").ok()?.trim() {
+            "1" => Some(Verbosity::Calls),
+            "2" => Some(Verbosity::Returns),
+            "3" => Some(Verbosity::Raw),
+            _ => None,
+        }
+    }
+}
+
+fn join(values: &[AbiValue]) -> String {
+    let values: Vec<String> = values.iter().map(ToString::to_string).collect();
+    values.join(", ")
+}
+
+fn decode_types(types: &[String], data: &[u8]) -> Option<Vec<AbiValue>> {
+    let types = types
+        .iter()
+        .map(|ty| ParamType::parse(ty))
+        .collect::<Option<Vec<_>>>()?;
+    decode(&types, data)
+}
+
+/// Renders the traces with the labels, and the ABIs of the registered contracts.
+pub struct TraceRenderer<'a> {
+    pub contracts: &'a BTreeMap<Address, ContractInfo>,
+    pub labels: &'a HashMap<Address, String>,
+    pub verbosity: Verbosity,
+}
+
+impl TraceRenderer<'_> {
+    pub fn render(&self, trace: &CallTrace) -> String {
+        let mut out = String::new();
+        self.render_node(trace, &mut out, "", "");
+        out
+    }
+
+    fn name(&self, address: &Address) -> String {
+        match self.labels.get(address) {
+            Some(label) => label.clone(),
+            None => address.to_string(),
+        }
+    }
+
+    fn call(&self, trace: &CallTrace) -> String {
+        if trace.kind.is_create() {
+            let name = self
+                .contracts
+                .get(&trace.to)
+                .map_or("<unknown>", |info| info.name.as_str());
+            return format!("new {name}@{}", trace.to);
+        }
+
+        let function = self
+            .contracts
+            .get(&trace.code_address)
+            .and_then(|info| info.function(&trace.input));
+        let call = match function {
+            Some(function) => {
+                let args = decode_types(&function.inputs, &trace.input[4..])
+                    .map(|args| join(&args))
+                    .unwrap_or_else(|| format!("0x{}", hex::encode(&trace.input[4..])));
+                format!("{}({args})", function.name)
+            }
+            None if trace.input.is_empty() => "fallback()".to_string(),
+            None => format!("0x{}", hex::encode(&trace.input)),
+        };
+
+        let mut rendered = format!("{}::{call}", self.name(&trace.to));
+        if trace.value != U256::ZERO {
+            write!(rendered, " {{value: {}}}", trace.value).unwrap();
+        }
+        match trace.kind {
+            CallKind::DelegateCall => rendered.push_str(" [delegatecall]"),
+            CallKind::StaticCall => rendered.push_str(" [staticcall]"),
+            CallKind::CallCode => rendered.push_str(" [callcode]"),
+            _ => {}
+        }
+        rendered
+    }
+
+    fn returned(&self, trace: &CallTrace) -> String {
+        if trace.is_revert() {
+            let errors = self
+                .contracts
+                .get(&trace.code_address)
+                .map_or(&[][..], |info| info.errors.as_slice());
+            let custom = errors.iter().find_map(|signature| {
+                let data = trace.output.strip_prefix(get_sig(signature).as_slice())?;
+                let (name, types) = signature.split_once('(')?;
+                let ParamType::Tuple(types) = ParamType::parse(&format!("({types}"))? else {
+                    return None;
+                };
+                let args = decode(&types, data)?;
+                Some(format!("{name}({})", join(&args)))
+            });
+            let reason = custom.unwrap_or_else(|| RevertReason::decode(&trace.output).to_string());
+            return format!("[Revert] {reason}");
+        }
+        if !trace.is_success() {
+            return format!("[{:?}]", trace.result);
+        }
+        if trace.kind.is_create() {
+            return format!("{} bytes of code", trace.output.len());
+        }
+
+        let outputs = self
+            .contracts
+            .get(&trace.code_address)
+            .and_then(|info| info.function(&trace.input))
+            .and_then(|function| decode_types(&function.outputs, &trace.output));
+        match outputs {
+            Some(values) => format!("({})", join(&values)),
+            None => format!("0x{}", hex::encode(&trace.output)),
+        }
+    }
+
+    fn render_node(&self, trace: &CallTrace, out: &mut String, left: &str, child_left: &str) {
+        writeln!(out, "{left}[{}] {}", trace.gas_used, self.call(trace)).unwrap();
+        if self.verbosity >= Verbosity::Raw {
+            writeln!(
+                out,
+                "{child_left}│   input: 0x{}",
+                hex::encode(&trace.input)
+            )
+            .unwrap();
+        }
+        for child in &trace.children {
+            self.render_node(
+                child,
+                out,
+                &format!("{child_left}├─ "),
+                &format!("{child_left}│  "),
+            );
+        }
+        if !trace.is_success() || self.verbosity >= Verbosity::Returns {
+            writeln!(out, "{child_left}└─ ← {}", self.returned(trace)).unwrap();
+        }
+        if self.verbosity >= Verbosity::Raw && !trace.kind.is_create() {
+            writeln!(
+                out,
+                "{child_left}    output: 0x{}",
+                hex::encode(&trace.output)
+            )
+            .unwrap();
+        }
+    }
+}
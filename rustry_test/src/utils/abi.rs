@@ -1,4 +1,4 @@
-use revm::primitives::Bytes;
+use revm::primitives::{Bytes, B256, U256};
 use tiny_keccak::{Hasher, Keccak};
 
 pub enum AbiType {
@@ -78,13 +78,35 @@ pub fn abi_decode(data: &Bytes, types: Vec<AbiType>) -> Vec<u8> {
 pub fn abi_encode(types: Vec<AbiValueType>) -> Vec<u8> {
     types
         .into_iter()
-        .flat_map(|abi_ty| match abi_ty {
-            AbiValueType::Uint(data) | AbiValueType::Int(data) => data.to_vec(),
-            AbiValueType::Address(data) => data.to_vec(),
-        })
+        .flat_map(|abi_ty| abi_ty.inner().to_vec())
         .collect()
 }
 
+/// ABI encode a single `string` as it would be in the arguments of a call,
+/// that is the offset, the length and then the right-padded content.
+pub fn abi_encode_string(value: &str) -> Vec<u8> {
+    let bytes = value.as_bytes();
+    let padded_len = bytes.len().div_ceil(32) * 32;
+    let mut encoded = U256::from(32).to_be_bytes::<32>().to_vec();
+    encoded.extend_from_slice(&U256::from(bytes.len()).to_be_bytes::<32>());
+    encoded.extend_from_slice(bytes);
+    encoded.resize(64 + padded_len, 0);
+    encoded
+}
+
+/// Decode a single ABI encoded `string`, returns `None` if the data is malformed.
+pub fn abi_decode_string(data: &[u8]) -> Option<String> {
+    let offset = word_to_usize(data.get(0..32)?)?;
+    let len = word_to_usize(data.get(offset..offset.checked_add(32)?)?)?;
+    let start = offset + 32;
+    let content = data.get(start..start.checked_add(len)?)?;
+    String::from_utf8(content.to_vec()).ok()
+}
+
+fn word_to_usize(word: &[u8]) -> Option<usize> {
+    U256::try_from_be_slice(word)?.try_into().ok()
+}
+
 // doesn't support tuples **yet**
 pub fn abi_encode_signature(signature: &str, values: Vec<Vec<u8>>) -> Vec<u8> {
     assert!(signature.ends_with(')'));
@@ -109,17 +131,27 @@ pub fn abi_encode_signature(signature: &str, values: Vec<Vec<u8>>) -> Vec<u8> {
     [sig.as_ref(), &abi_encode(types)].concat()
 }
 
-pub fn get_sig(signature: &str) -> [u8; 4] {
+pub fn keccak(data: &[u8]) -> [u8; 32] {
     let mut keccak = Keccak::v256();
-    keccak.update(signature.as_bytes());
-    let mut sig_raw = [0u8; 32];
-    keccak.finalize(&mut sig_raw);
+    keccak.update(data);
+    let mut hash = [0u8; 32];
+    keccak.finalize(&mut hash);
+    hash
+}
+
+pub fn get_sig(signature: &str) -> [u8; 4] {
+    let sig_raw = keccak(signature.as_bytes());
     sig_raw[0..4].try_into().unwrap()
 }
 
+/// The first topic of a non-anonymous event, e.g. `Transfer(address,address,uint256)`.
+pub fn get_event_topic(signature: &str) -> B256 {
+    keccak(signature.as_bytes()).into()
+}
+
 #[cfg(test)]
 mod tests {
-    use super::{abi_encode_signature, get_sig};
+    use super::{abi_decode_string, abi_encode_signature, abi_encode_string, get_sig};
 
     #[test]
     fn good_sig() {
@@ -142,4 +174,15 @@ mod tests {
             .concat()
         );
     }
+
+    #[test]
+    fn string_roundtrip() {
+        let encoded = abi_encode_string("deadline not met (yet)");
+        assert_eq!(encoded.len(), 96);
+        assert_eq!(
+            abi_decode_string(&encoded).as_deref(),
+            Some("deadline not met (yet)")
+        );
+        assert_eq!(abi_decode_string(&encoded[..40]), None);
+    }
 }
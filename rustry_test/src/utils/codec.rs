@@ -0,0 +1,635 @@
+//! Dynamic ABI encoding and decoding, driven by the type strings found in the ABI.
+
+use super::abi::get_event_topic;
+use revm::primitives::{alloy_primitives::I256, Address, Bytes, FixedBytes, Log, B256, U256};
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParamType {
+    Uint(usize),
+    Int(usize),
+    Address,
+    Bool,
+    FixedBytes(usize),
+    Bytes,
+    String,
+    Array(Box<ParamType>),
+    FixedArray(Box<ParamType>, usize),
+    Tuple(Vec<ParamType>),
+}
+
+impl ParamType {
+    /// Parse a canonical type, e.g. `uint256`, `bytes32[]` or `(address,uint8)[2]`.
+    pub fn parse(ty: &str) -> Option<Self> {
+        let ty = ty.trim();
+        if let Some(inner) = ty.strip_suffix(']') {
+            let open = inner.rfind('[')?;
+            let elem = Box::new(Self::parse(&inner[..open])?);
+            let size = &inner[(open + 1)..];
+            return if size.is_empty() {
+                Some(ParamType::Array(elem))
+            } else {
+                Some(ParamType::FixedArray(elem, size.parse().ok()?))
+            };
+        }
+        if let Some(inner) = ty.strip_prefix('(') {
+            let inner = inner.strip_suffix(')')?;
+            return split_tuple(inner)
+                .into_iter()
+                .map(Self::parse)
+                .collect::<Option<_>>()
+                .map(ParamType::Tuple);
+        }
+
+        match ty {
+            "address" => Some(ParamType::Address),
+            "bool" => Some(ParamType::Bool),
+            "bytes" => Some(ParamType::Bytes),
+            "string" => Some(ParamType::String),
+            "function" => Some(ParamType::FixedBytes(24)),
+            _ => {
+                if let Some(bits) = ty.strip_prefix("uint") {
+                    Some(ParamType::Uint(parse_size(bits, 256)?))
+                } else if let Some(bits) = ty.strip_prefix("int") {
+                    Some(ParamType::Int(parse_size(bits, 256)?))
+                } else if let Some(len) = ty.strip_prefix("bytes") {
+                    Some(ParamType::FixedBytes(len.parse().ok()?))
+                } else {
+                    None
+                }
+            }
+        }
+    }
+
+    pub fn is_dynamic(&self) -> bool {
+        match self {
+            ParamType::Bytes | ParamType::String | ParamType::Array(_) => true,
+            ParamType::FixedArray(elem, _) => elem.is_dynamic(),
+            ParamType::Tuple(types) => types.iter().any(|ty| ty.is_dynamic()),
+            _ => false,
+        }
+    }
+
+    /// Size taken in the head of the encoding.
+    fn head_len(&self) -> usize {
+        match self {
+            _ if self.is_dynamic() => 32,
+            ParamType::FixedArray(elem, len) => elem.head_len() * len,
+            ParamType::Tuple(types) => types.iter().map(|ty| ty.head_len()).sum(),
+            _ => 32,
+        }
+    }
+}
+
+impl fmt::Display for ParamType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParamType::Uint(bits) => write!(f, "uint{bits}"),
+            ParamType::Int(bits) => write!(f, "int{bits}"),
+            ParamType::Address => write!(f, "address"),
+            ParamType::Bool => write!(f, "bool"),
+            ParamType::FixedBytes(len) => write!(f, "bytes{len}"),
+            ParamType::Bytes => write!(f, "bytes"),
+            ParamType::String => write!(f, "string"),
+            ParamType::Array(elem) => write!(f, "{elem}[]"),
+            ParamType::FixedArray(elem, len) => write!(f, "{elem}[{len}]"),
+            ParamType::Tuple(types) => {
+                let types: Vec<_> = types.iter().map(|ty| ty.to_string()).collect();
+                write!(f, "({})", types.join(","))
+            }
+        }
+    }
+}
+
+fn parse_size(size: &str, default: usize) -> Option<usize> {
+    if size.is_empty() {
+        Some(default)
+    } else {
+        size.parse().ok()
+    }
+}
+
+/// Split the inner types of a tuple on the top-level commas.
+fn split_tuple(inner: &str) -> Vec<&str> {
+    if inner.is_empty() {
+        return Vec::new();
+    }
+    let mut depth = 0;
+    let mut start = 0;
+    let mut types = Vec::new();
+    for (i, c) in inner.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                types.push(&inner[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    types.push(&inner[start..]);
+    types
+}
+
+/// A decoded ABI value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AbiValue {
+    Uint(U256),
+    Int(I256),
+    Address(Address),
+    Bool(bool),
+    FixedBytes(Vec<u8>),
+    Bytes(Bytes),
+    String(String),
+    Array(Vec<AbiValue>),
+    FixedArray(Vec<AbiValue>),
+    Tuple(Vec<AbiValue>),
+}
+
+impl AbiValue {
+    pub fn is_dynamic(&self) -> bool {
+        match self {
+            AbiValue::Bytes(_) | AbiValue::String(_) | AbiValue::Array(_) => true,
+            AbiValue::FixedArray(values) | AbiValue::Tuple(values) => {
+                values.iter().any(|value| value.is_dynamic())
+            }
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for AbiValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AbiValue::Uint(value) => write!(f, "{value}"),
+            AbiValue::Int(value) => write!(f, "{value}"),
+            AbiValue::Address(address) => write!(f, "{address}"),
+            AbiValue::Bool(value) => write!(f, "{value}"),
+            AbiValue::FixedBytes(bytes) => write!(f, "{}", Bytes::from(bytes.clone())),
+            AbiValue::Bytes(bytes) => write!(f, "{bytes}"),
+            AbiValue::String(string) => write!(f, "{string:?}"),
+            AbiValue::Array(values) | AbiValue::FixedArray(values) => {
+                write!(f, "[{}]", join(values))
+            }
+            AbiValue::Tuple(values) => write!(f, "({})", join(values)),
+        }
+    }
+}
+
+fn join(values: &[AbiValue]) -> String {
+    let values: Vec<_> = values.iter().map(|value| value.to_string()).collect();
+    values.join(", ")
+}
+
+fn word(data: &[u8], offset: usize) -> Option<&[u8]> {
+    data.get(offset..offset.checked_add(32)?)
+}
+
+fn read_usize(data: &[u8], offset: usize) -> Option<usize> {
+    U256::try_from_be_slice(word(data, offset)?)?
+        .try_into()
+        .ok()
+}
+
+/// Decode the ABI encoded `data` as a tuple of `types`, e.g. the arguments of a call.
+pub fn decode(types: &[ParamType], data: &[u8]) -> Option<Vec<AbiValue>> {
+    let mut offset = 0;
+    let mut values = Vec::with_capacity(types.len());
+    for ty in types {
+        let value = if ty.is_dynamic() {
+            let location = read_usize(data, offset)?;
+            decode_value(ty, data.get(location..)?)?
+        } else {
+            decode_value(ty, data.get(offset..)?)?
+        };
+        values.push(value);
+        offset += ty.head_len();
+    }
+    Some(values)
+}
+
+fn decode_value(ty: &ParamType, data: &[u8]) -> Option<AbiValue> {
+    match ty {
+        ParamType::Uint(_) => Some(AbiValue::Uint(U256::try_from_be_slice(word(data, 0)?)?)),
+        ParamType::Int(_) => Some(AbiValue::Int(I256::from_raw(U256::try_from_be_slice(
+            word(data, 0)?,
+        )?))),
+        ParamType::Address => Some(AbiValue::Address(Address::from_slice(
+            &word(data, 0)?[12..],
+        ))),
+        ParamType::Bool => Some(AbiValue::Bool(word(data, 0)?.iter().any(|b| *b != 0))),
+        ParamType::FixedBytes(len) => {
+            Some(AbiValue::FixedBytes(word(data, 0)?.get(..*len)?.to_vec()))
+        }
+        ParamType::Bytes => Some(AbiValue::Bytes(decode_bytes(data)?.to_vec().into())),
+        ParamType::String => Some(AbiValue::String(
+            String::from_utf8(decode_bytes(data)?.to_vec()).ok()?,
+        )),
+        ParamType::Array(elem) => {
+            let len = read_usize(data, 0)?;
+            // each element takes at least a word, don't trust the length blindly
+            if len > data.len() / 32 {
+                return None;
+            }
+            let types = vec![(**elem).clone(); len];
+            decode(&types, data.get(32..)?).map(AbiValue::Array)
+        }
+        ParamType::FixedArray(elem, len) => {
+            let types = vec![(**elem).clone(); *len];
+            decode(&types, data).map(AbiValue::FixedArray)
+        }
+        ParamType::Tuple(types) => decode(types, data).map(AbiValue::Tuple),
+    }
+}
+
+fn decode_bytes(data: &[u8]) -> Option<&[u8]> {
+    let len = read_usize(data, 0)?;
+    data.get(32..32usize.checked_add(len)?)
+}
+
+/// ABI encode the `values` as a tuple, e.g. the arguments of a call.
+pub fn encode(values: &[AbiValue]) -> Vec<u8> {
+    let heads_len: usize = values
+        .iter()
+        .map(|value| {
+            if value.is_dynamic() {
+                32
+            } else {
+                encode_value(value).len()
+            }
+        })
+        .sum();
+
+    let mut heads = Vec::with_capacity(heads_len);
+    let mut tails = Vec::new();
+    for value in values {
+        if value.is_dynamic() {
+            heads.extend_from_slice(&U256::from(heads_len + tails.len()).to_be_bytes::<32>());
+            tails.extend(encode_value(value));
+        } else {
+            heads.extend(encode_value(value));
+        }
+    }
+    [heads, tails].concat()
+}
+
+fn encode_value(value: &AbiValue) -> Vec<u8> {
+    match value {
+        AbiValue::Uint(value) => value.to_be_bytes::<32>().to_vec(),
+        AbiValue::Int(value) => value.into_raw().to_be_bytes::<32>().to_vec(),
+        AbiValue::Address(address) => address.into_word().to_vec(),
+        AbiValue::Bool(value) => U256::from(*value as u8).to_be_bytes::<32>().to_vec(),
+        AbiValue::FixedBytes(bytes) => pad_right(bytes),
+        AbiValue::Bytes(bytes) => encode_bytes(bytes),
+        AbiValue::String(string) => encode_bytes(string.as_bytes()),
+        AbiValue::Array(values) => [
+            U256::from(values.len()).to_be_bytes::<32>().to_vec(),
+            encode(values),
+        ]
+        .concat(),
+        AbiValue::FixedArray(values) | AbiValue::Tuple(values) => encode(values),
+    }
+}
+
+fn encode_bytes(bytes: &[u8]) -> Vec<u8> {
+    [
+        U256::from(bytes.len()).to_be_bytes::<32>().to_vec(),
+        pad_right(bytes),
+    ]
+    .concat()
+}
+
+fn pad_right(bytes: &[u8]) -> Vec<u8> {
+    let mut padded = bytes.to_vec();
+    padded.resize(bytes.len().div_ceil(32) * 32, 0);
+    padded
+}
+
+/// Decode an indexed parameter.
+/// Dynamic types are only stored as their hash, returned as `bytes32`.
+pub fn decode_topic(ty: &ParamType, topic: &B256) -> Option<AbiValue> {
+    if ty.is_dynamic() || matches!(ty, ParamType::Tuple(_) | ParamType::FixedArray(..)) {
+        Some(AbiValue::FixedBytes(topic.to_vec()))
+    } else {
+        decode_value(ty, topic.as_slice())
+    }
+}
+
+/// Decode the parameters of an event from a `log`, in the order of the declaration.
+/// `params` are the canonical types of the parameters and whether they are indexed.
+/// `signature` is `None` for anonymous events, which don't have a topic for it.
+pub fn decode_log(
+    log: &Log,
+    signature: Option<&str>,
+    params: &[(&str, bool)],
+) -> Option<Vec<AbiValue>> {
+    let mut topics = log.topics.iter();
+    if let Some(signature) = signature {
+        if *topics.next()? != get_event_topic(signature) {
+            return None;
+        }
+    }
+
+    let types: Vec<(ParamType, bool)> = params
+        .iter()
+        .map(|(ty, indexed)| Some((ParamType::parse(ty)?, *indexed)))
+        .collect::<Option<_>>()?;
+    if types.iter().filter(|(_, indexed)| *indexed).count() != topics.len() {
+        return None;
+    }
+
+    let data_types: Vec<ParamType> = types
+        .iter()
+        .filter(|(_, indexed)| !indexed)
+        .map(|(ty, _)| ty.clone())
+        .collect();
+    let mut data = decode(&data_types, &log.data)?.into_iter();
+
+    types
+        .iter()
+        .map(|(ty, indexed)| {
+            if *indexed {
+                decode_topic(ty, topics.next()?)
+            } else {
+                data.next()
+            }
+        })
+        .collect()
+}
+
+/// Conversion from a decoded value to a Rust type.
+pub trait FromAbi: Sized {
+    fn from_abi(value: AbiValue) -> Option<Self>;
+}
+
+impl FromAbi for AbiValue {
+    fn from_abi(value: AbiValue) -> Option<Self> {
+        Some(value)
+    }
+}
+
+impl FromAbi for U256 {
+    fn from_abi(value: AbiValue) -> Option<Self> {
+        match value {
+            AbiValue::Uint(value) => Some(value),
+            _ => None,
+        }
+    }
+}
+
+impl FromAbi for I256 {
+    fn from_abi(value: AbiValue) -> Option<Self> {
+        match value {
+            AbiValue::Int(value) => Some(value),
+            _ => None,
+        }
+    }
+}
+
+impl FromAbi for Address {
+    fn from_abi(value: AbiValue) -> Option<Self> {
+        match value {
+            AbiValue::Address(address) => Some(address),
+            _ => None,
+        }
+    }
+}
+
+impl FromAbi for bool {
+    fn from_abi(value: AbiValue) -> Option<Self> {
+        match value {
+            AbiValue::Bool(value) => Some(value),
+            _ => None,
+        }
+    }
+}
+
+impl<const N: usize> FromAbi for FixedBytes<N> {
+    fn from_abi(value: AbiValue) -> Option<Self> {
+        match value {
+            AbiValue::FixedBytes(bytes) if bytes.len() == N => Some(FixedBytes::from_slice(&bytes)),
+            _ => None,
+        }
+    }
+}
+
+impl FromAbi for Bytes {
+    fn from_abi(value: AbiValue) -> Option<Self> {
+        match value {
+            AbiValue::Bytes(bytes) => Some(bytes),
+            _ => None,
+        }
+    }
+}
+
+impl FromAbi for String {
+    fn from_abi(value: AbiValue) -> Option<Self> {
+        match value {
+            AbiValue::String(string) => Some(string),
+            _ => None,
+        }
+    }
+}
+
+impl<T: FromAbi> FromAbi for Vec<T> {
+    fn from_abi(value: AbiValue) -> Option<Self> {
+        match value {
+            AbiValue::Array(values) | AbiValue::FixedArray(values) => {
+                values.into_iter().map(T::from_abi).collect()
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Conversion from a Rust type back to its ABI value.
+/// Fixed size arrays can't be told apart from the dynamic ones and convert to `AbiValue::Array`.
+pub trait ToAbi {
+    fn to_abi(&self) -> AbiValue;
+}
+
+impl ToAbi for AbiValue {
+    fn to_abi(&self) -> AbiValue {
+        self.clone()
+    }
+}
+
+impl ToAbi for U256 {
+    fn to_abi(&self) -> AbiValue {
+        AbiValue::Uint(*self)
+    }
+}
+
+macro_rules! impl_to_abi_uint {
+    ($($ty:ty),*) => {
+        $(
+            impl ToAbi for $ty {
+                fn to_abi(&self) -> AbiValue {
+                    AbiValue::Uint(U256::from(*self))
+                }
+            }
+        )*
+    };
+}
+
+impl_to_abi_uint!(u8, u16, u32, u64, u128);
+
+impl ToAbi for I256 {
+    fn to_abi(&self) -> AbiValue {
+        AbiValue::Int(*self)
+    }
+}
+
+impl ToAbi for Address {
+    fn to_abi(&self) -> AbiValue {
+        AbiValue::Address(*self)
+    }
+}
+
+impl ToAbi for bool {
+    fn to_abi(&self) -> AbiValue {
+        AbiValue::Bool(*self)
+    }
+}
+
+impl<const N: usize> ToAbi for FixedBytes<N> {
+    fn to_abi(&self) -> AbiValue {
+        AbiValue::FixedBytes(self.to_vec())
+    }
+}
+
+impl ToAbi for Bytes {
+    fn to_abi(&self) -> AbiValue {
+        AbiValue::Bytes(self.clone())
+    }
+}
+
+impl ToAbi for String {
+    fn to_abi(&self) -> AbiValue {
+        AbiValue::String(self.clone())
+    }
+}
+
+impl ToAbi for str {
+    fn to_abi(&self) -> AbiValue {
+        AbiValue::String(self.to_string())
+    }
+}
+
+impl<T: ToAbi + ?Sized> ToAbi for &T {
+    fn to_abi(&self) -> AbiValue {
+        (**self).to_abi()
+    }
+}
+
+impl<T: ToAbi> ToAbi for Vec<T> {
+    fn to_abi(&self) -> AbiValue {
+        AbiValue::Array(self.iter().map(T::to_abi).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode, decode_log, encode, AbiValue, FromAbi, ParamType, ToAbi};
+    use crate::utils::abi::get_event_topic;
+    use revm::primitives::{Address, Log, U256};
+
+    #[test]
+    fn parse_types() {
+        for ty in [
+            "uint256",
+            "int8",
+            "bytes32",
+            "address[]",
+            "(uint256,(bool,string)[])[3]",
+        ] {
+            assert_eq!(ParamType::parse(ty).unwrap().to_string(), ty);
+        }
+        assert_eq!(ParamType::parse("uint"), Some(ParamType::Uint(256)));
+        assert_eq!(ParamType::parse("foo"), None);
+    }
+
+    #[test]
+    fn roundtrip() {
+        let types: Vec<_> = [
+            "uint256",
+            "string",
+            "address[]",
+            "(bool,bytes)",
+            "bytes4[2]",
+        ]
+        .into_iter()
+        .map(|ty| ParamType::parse(ty).unwrap())
+        .collect();
+        let values = vec![
+            AbiValue::Uint(U256::from(42)),
+            AbiValue::String(String::from("rustry")),
+            AbiValue::Array(vec![
+                AbiValue::Address(Address::repeat_byte(1)),
+                AbiValue::Address(Address::repeat_byte(2)),
+            ]),
+            AbiValue::Tuple(vec![
+                AbiValue::Bool(true),
+                AbiValue::Bytes(vec![0xde, 0xad].into()),
+            ]),
+            AbiValue::FixedArray(vec![
+                AbiValue::FixedBytes(vec![1, 2, 3, 4]),
+                AbiValue::FixedBytes(vec![5, 6, 7, 8]),
+            ]),
+        ];
+        let encoded = encode(&values);
+        assert_eq!(decode(&types, &encoded), Some(values));
+        assert_eq!(decode(&types, &encoded[..100]), None);
+    }
+
+    #[test]
+    fn known_encoding() {
+        // transfer(address,uint256) arguments
+        let encoded = encode(&[
+            AbiValue::Address(Address::repeat_byte(0xaa)),
+            AbiValue::Uint(U256::from(1)),
+        ]);
+        assert_eq!(encoded.len(), 64);
+        assert_eq!(&encoded[12..32], &[0xaa; 20]);
+        assert_eq!(encoded[63], 1);
+    }
+
+    #[test]
+    fn event() {
+        let signature = "Transfer(address,address,uint256)";
+        let log = Log {
+            address: Address::ZERO,
+            topics: vec![
+                get_event_topic(signature),
+                Address::repeat_byte(1).into_word(),
+                Address::repeat_byte(2).into_word(),
+            ],
+            data: encode(&[AbiValue::Uint(U256::from(7))]).into(),
+        };
+        let params = [("address", true), ("address", true), ("uint256", false)];
+
+        let values = decode_log(&log, Some(signature), &params).unwrap();
+        assert_eq!(
+            values,
+            vec![
+                AbiValue::Address(Address::repeat_byte(1)),
+                AbiValue::Address(Address::repeat_byte(2)),
+                AbiValue::Uint(U256::from(7)),
+            ]
+        );
+        assert_eq!(
+            decode_log(&log, Some("Approval(address,address,uint256)"), &params),
+            None
+        );
+        assert_eq!(decode_log(&log, None, &params), None);
+    }
+
+    #[test]
+    fn to_abi() {
+        let values = vec![U256::from(1), U256::from(2)];
+        let value = values.to_abi();
+        assert_eq!(value.to_string(), "[1, 2]");
+        assert_eq!(Vec::<U256>::from_abi(value), Some(values));
+    }
+}
@@ -1,5 +1,7 @@
 pub mod abi;
+pub mod codec;
 pub mod constants;
+pub mod slots;
 
 #[allow(clippy::module_inception)]
 mod utils;
@@ -0,0 +1,214 @@
+//! Slot math to seed the storage of the contracts with raw writes, as laid out by Solidity.
+//!
+//! https://docs.soliditylang.org/en/latest/internals/layout_in_storage.html
+
+use crate::{
+    common::storage::StorageKey,
+    provider::{
+        db::{Cheats, Frontend},
+        Provider,
+    },
+    utils::abi::abi_encode_signature,
+};
+use revm::primitives::{keccak256, Address, U256};
+
+/// `bytes32(uint256(keccak256("eip1967.proxy.implementation")) - 1)`, the limbs from the right
+pub const EIP1967_IMPLEMENTATION_SLOT: U256 = U256::from_limbs([
+    0x20a3ca505d382bbc,
+    0xca3e2076cc3735a9,
+    0x0667c828492db98d,
+    0x360894a13ba1a321,
+]);
+/// `bytes32(uint256(keccak256("eip1967.proxy.admin")) - 1)`
+pub const EIP1967_ADMIN_SLOT: U256 = U256::from_limbs([
+    0xd6a717850b5d6103,
+    0x243e63b6e8ee1178,
+    0x73ae13b9f8a6016e,
+    0xb53127684a568b31,
+]);
+/// `bytes32(uint256(keccak256("eip1967.proxy.beacon")) - 1)`
+pub const EIP1967_BEACON_SLOT: U256 = U256::from_limbs([
+    0x6cb3582b35133d50,
+    0x35a9a72aeaee59ff,
+    0xfd80d3ef43465783,
+    0xa3f0ad74e5423aeb,
+]);
+
+fn hash_slot(preimage: &[u8]) -> U256 {
+    U256::from_be_bytes(keccak256(preimage).0)
+}
+
+/// Slot of the value at `key` of the mapping at `slot`, `keccak256(key . slot)`.
+pub fn mapping_slot(slot: U256, key: impl StorageKey) -> U256 {
+    hash_slot(&[key.key_bytes(), slot.to_be_bytes::<32>().to_vec()].concat())
+}
+
+/// Slot of `mapping[keys[0]][keys[1]]..`.
+pub fn nested_mapping_slot(slot: U256, keys: &[&dyn StorageKey]) -> U256 {
+    keys.iter().fold(slot, mapping_slot)
+}
+
+/// Where the elements of the dynamic array at `slot` start, its length being at `slot`.
+pub fn array_data_slot(slot: U256) -> U256 {
+    hash_slot(&slot.to_be_bytes::<32>())
+}
+
+/// Slot and offset of the element `index` of the elements starting at `start`, the elements
+/// smaller than a slot being packed.
+pub fn element_slot(start: U256, index: usize, element_size: usize) -> (U256, usize) {
+    let element_size = element_size.max(1);
+    let per_slot = if element_size < 32 {
+        32 / element_size
+    } else {
+        1
+    };
+    let slots = element_size.div_ceil(32);
+    (
+        start + U256::from(index / per_slot * slots),
+        index % per_slot * element_size,
+    )
+}
+
+/// Slot and offset of the element `index` of the dynamic array at `slot`.
+pub fn array_element_slot(slot: U256, index: usize, element_size: usize) -> (U256, usize) {
+    element_slot(array_data_slot(slot), index, element_size)
+}
+
+fn mask(width: usize) -> U256 {
+    match width {
+        0..=31 => (U256::from(1) << (width * 8)) - U256::from(1),
+        _ => U256::MAX,
+    }
+}
+
+/// The `width` bytes at `offset` bytes from the right of the word.
+pub fn read_packed(word: U256, offset: usize, width: usize) -> U256 {
+    (word >> (offset * 8)) & mask(width)
+}
+
+/// The word with the `width` bytes at `offset` replaced by `value`, truncated to fit.
+pub fn write_packed(word: U256, offset: usize, width: usize, value: U256) -> U256 {
+    let mask = mask(width) << (offset * 8);
+    (word & !mask) | ((value << (offset * 8)) & mask)
+}
+
+/// Read a value packed with others in a slot.
+pub fn load_packed(
+    provider: &Provider,
+    who: Address,
+    slot: U256,
+    offset: usize,
+    width: usize,
+) -> U256 {
+    read_packed(provider.load(who, slot), offset, width)
+}
+
+/// Overwrite a value packed with others in a slot, keeping the others.
+pub fn store_packed(
+    provider: &mut Provider,
+    who: Address,
+    slot: U256,
+    offset: usize,
+    width: usize,
+    value: U256,
+) {
+    let word = write_packed(provider.load(who, slot), offset, width, value);
+    provider.store(who, slot, word);
+}
+
+fn load_address(provider: &Provider, who: Address, slot: U256) -> Option<Address> {
+    let address = Address::from_word(provider.load(who, slot).into());
+    (address != Address::ZERO).then_some(address)
+}
+
+/// The admin of an EIP-1967 proxy.
+pub fn proxy_admin(provider: &Provider, proxy: Address) -> Option<Address> {
+    load_address(provider, proxy, EIP1967_ADMIN_SLOT)
+}
+
+/// The beacon of an EIP-1967 beacon proxy.
+pub fn proxy_beacon(provider: &Provider, proxy: Address) -> Option<Address> {
+    load_address(provider, proxy, EIP1967_BEACON_SLOT)
+}
+
+/// The implementation of an EIP-1967 proxy, asked to its beacon if it has one.
+pub fn proxy_implementation(provider: &mut Provider, proxy: Address) -> Option<Address> {
+    if let Some(implementation) = load_address(provider, proxy, EIP1967_IMPLEMENTATION_SLOT) {
+        return Some(implementation);
+    }
+    let beacon = proxy_beacon(provider, proxy)?;
+    let res = provider.staticcall(
+        beacon,
+        abi_encode_signature("implementation()", vec![]).into(),
+    );
+    let data = res.get_data();
+    if !res.is_success() || data.len() < 32 {
+        return None;
+    }
+    let address = Address::from_slice(&data[12..32]);
+    (address != Address::ZERO).then_some(address)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        array_element_slot, element_slot, mapping_slot, nested_mapping_slot, read_packed,
+        write_packed, EIP1967_ADMIN_SLOT, EIP1967_BEACON_SLOT, EIP1967_IMPLEMENTATION_SLOT,
+    };
+    use revm::primitives::{keccak256, Address, U256};
+
+    fn hash(preimage: &[u8]) -> U256 {
+        U256::from_be_bytes(keccak256(preimage).0)
+    }
+
+    #[test]
+    fn eip1967_slots() {
+        for (slot, name) in [
+            (EIP1967_IMPLEMENTATION_SLOT, "eip1967.proxy.implementation"),
+            (EIP1967_ADMIN_SLOT, "eip1967.proxy.admin"),
+            (EIP1967_BEACON_SLOT, "eip1967.proxy.beacon"),
+        ] {
+            assert_eq!(slot, hash(name.as_bytes()) - U256::from(1));
+        }
+    }
+
+    #[test]
+    fn slots() {
+        let alice = Address::repeat_byte(0xa1);
+        let bob = Address::repeat_byte(0xb0);
+        let slot = U256::from(3);
+        let balance = hash(&[alice.into_word().0, slot.to_be_bytes()].concat());
+        assert_eq!(mapping_slot(slot, alice), balance);
+        let allowance = hash(&[bob.into_word().0, balance.to_be_bytes()].concat());
+        assert_eq!(nested_mapping_slot(slot, &[&alice, &bob]), allowance);
+        assert_eq!(
+            mapping_slot(slot, "a"),
+            hash(&[b"a".as_slice(), &slot.to_be_bytes::<32>()].concat())
+        );
+
+        let start = hash(&slot.to_be_bytes::<32>());
+        assert_eq!(array_element_slot(slot, 3, 32), (start + U256::from(3), 0));
+        // 4 `uint64` per slot
+        assert_eq!(element_slot(start, 5, 8), (start + U256::from(1), 8));
+        // structs of 2 slots
+        assert_eq!(element_slot(start, 2, 64), (start + U256::from(4), 0));
+        // an `address` per slot
+        assert_eq!(element_slot(start, 1, 20), (start + U256::from(1), 0));
+    }
+
+    #[test]
+    fn packed() {
+        let word = U256::from(0xaabbccddu64);
+        assert_eq!(read_packed(word, 1, 2), U256::from(0xbbcc));
+        assert_eq!(read_packed(word, 0, 32), word);
+        assert_eq!(
+            write_packed(word, 1, 2, U256::from(0x1122)),
+            U256::from(0xaa1122ddu64)
+        );
+        // truncated to the width
+        assert_eq!(
+            write_packed(word, 0, 1, U256::from(0x1ff)),
+            U256::from(0xaabbccffu64)
+        );
+    }
+}
@@ -4,3 +4,37 @@
 // e.g. SimpleStore only from the contracts path.
 // could use generics, the bindings should be generated first !
 // pub fn deploy_contract<T>(path: Path) -> T {}
+
+use std::{env, path::PathBuf};
+
+/// Directory where rustry keeps its artifacts, `target/rustry` of the running test.
+pub fn rustry_dir() -> PathBuf {
+    target_dir().join("rustry")
+}
+
+fn target_dir() -> PathBuf {
+    if let Some(dir) = env::var_os("CARGO_TARGET_DIR") {
+        return dir.into();
+    }
+    // cargo tags its target directory, which contains the test binaries
+    env::current_exe()
+        .ok()
+        .and_then(|exe| {
+            exe.ancestors()
+                .find(|dir| dir.join("CACHEDIR.TAG").is_file())
+                .map(PathBuf::from)
+        })
+        .unwrap_or_else(|| PathBuf::from("target"))
+}
+
+/// Name of the running test binary, without the hash cargo appends to it.
+pub fn binary_name() -> String {
+    let binary = env::current_exe()
+        .ok()
+        .and_then(|exe| Some(exe.file_stem()?.to_string_lossy().into_owned()))
+        .unwrap_or_else(|| "rustry".to_string());
+    match binary.rsplit_once('-') {
+        Some((name, hash)) if hash.chars().all(|c| c.is_ascii_hexdigit()) => name.to_string(),
+        _ => binary,
+    }
+}